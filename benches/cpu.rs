@@ -0,0 +1,123 @@
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use nes::cpu::{Cpu, Registers, Status};
+
+const PROGRAM_START: u16 = 0x8000;
+// leaves room for the BRK and the vectors at the top of memory
+const PROGRAM_SPACE: usize = 0x7000;
+// NTSC: 341 dots x 262 scanlines / 3 dots per CPU cycle
+const CYCLES_PER_FRAME: u64 = 29_781;
+
+// representative instruction mixes, each repeated to fill the program space
+const MIXES: &[(&str, &[u8])] = &[
+    // LDA #$10; STA $20; LDX $20; STX $0300; LDY #$01; TAY
+    (
+        "load_store",
+        &[
+            0xA9, 0x10, 0x85, 0x20, 0xA6, 0x20, 0x8E, 0x00, 0x03, 0xA0, 0x01, 0xA8,
+        ],
+    ),
+    // ADC #$11; SBC #$03; AND #$F7; ORA #$10; EOR #$AA; INX
+    (
+        "alu",
+        &[
+            0x69, 0x11, 0xE9, 0x03, 0x29, 0xF7, 0x09, 0x10, 0x49, 0xAA, 0xE8,
+        ],
+    ),
+    // INC $20; ASL $21; ROR $0300; DEC $22,X; LSR A
+    (
+        "read_modify_write",
+        &[0xE6, 0x20, 0x06, 0x21, 0x6E, 0x00, 0x03, 0xD6, 0x22, 0x4A],
+    ),
+    // LDA ($40),Y; STA ($42,X); LDA $0300,X; STA $0400,Y
+    (
+        "indexed_indirect",
+        &[0xB1, 0x40, 0x81, 0x42, 0xBD, 0x00, 0x03, 0x99, 0x00, 0x04],
+    ),
+];
+
+fn mix_cpu(mix: &[u8]) -> (Cpu, u64) {
+    let repeats = PROGRAM_SPACE / mix.len();
+    let mut program: Vec<u8> = mix
+        .iter()
+        .copied()
+        .cycle()
+        .take(repeats * mix.len())
+        .collect();
+    program.push(0x00);
+
+    let mut cpu = Cpu::new();
+    cpu.load(program);
+    cpu.reset();
+
+    // count what one pass executes, BRK excluded
+    let mut instructions = 0;
+    while cpu.step() == Status::Running {
+        instructions += 1;
+    }
+    (cpu, instructions)
+}
+
+fn restart(cpu: &mut Cpu) {
+    cpu.set_registers(Registers {
+        pc: PROGRAM_START,
+        ..cpu.registers()
+    });
+}
+
+fn dispatch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("dispatch");
+
+    for &(name, mix) in MIXES {
+        let (mut cpu, instructions) = mix_cpu(mix);
+        group.throughput(Throughput::Elements(instructions));
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                restart(&mut cpu);
+                black_box(cpu.run())
+            })
+        });
+
+        cpu.set_block_cache(true);
+        group.bench_function(format!("{}_block_cache", name), |b| {
+            b.iter(|| {
+                restart(&mut cpu);
+                black_box(cpu.run())
+            })
+        });
+    }
+
+    group.finish();
+}
+
+// there is no PPU yet, so a frame is a frame's worth of CPU cycles over the mixes in turn
+fn frame(c: &mut Criterion) {
+    let mut group = c.benchmark_group("frame");
+    group.throughput(Throughput::Elements(1));
+
+    let mut cpus: Vec<Cpu> = MIXES.iter().map(|&(_, mix)| mix_cpu(mix).0).collect();
+    let mut current = 0;
+    group.bench_function("cpu_only", |b| {
+        b.iter(|| {
+            let mut remaining = CYCLES_PER_FRAME;
+            while remaining > 0 {
+                let cpu = &mut cpus[current];
+                let start = cpu.cycles();
+                while cpu.cycles() - start < remaining {
+                    if cpu.step() != Status::Running {
+                        restart(cpu);
+                        current = (current + 1) % MIXES.len();
+                        break;
+                    }
+                }
+                remaining -= (cpu.cycles() - start).min(remaining);
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, dispatch, frame);
+criterion_main!(benches);
@@ -0,0 +1,121 @@
+// runs an easy6502 tutorial program, such as the snake game, with its 32x32 screen drawn in the
+// terminal; WASD are passed through to the key input at $FF
+//
+//     cargo run --example snake --features tui -- snake.bin
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{env, fs, io, process};
+
+use nes::cpu::{Cpu, Status};
+use nes::devices::{self, KeyInput, SCREEN_HEIGHT, SCREEN_WIDTH};
+use nes::pacing::FramePacer;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::style::{Color, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+// easy6502 runs programs much slower than a real 6502, and the games are tuned for that
+const STEPS_PER_FRAME: usize = 500;
+
+const PALETTE: [Color; 16] = [
+    Color::Black,
+    Color::White,
+    Color::Rgb(0x88, 0x00, 0x00),
+    Color::Rgb(0xAA, 0xFF, 0xEE),
+    Color::Rgb(0xCC, 0x44, 0xCC),
+    Color::Rgb(0x00, 0xCC, 0x55),
+    Color::Rgb(0x00, 0x00, 0xAA),
+    Color::Rgb(0xEE, 0xEE, 0x77),
+    Color::Rgb(0xDD, 0x88, 0x55),
+    Color::Rgb(0x66, 0x44, 0x00),
+    Color::Rgb(0xFF, 0x77, 0x77),
+    Color::Rgb(0x33, 0x33, 0x33),
+    Color::Rgb(0x77, 0x77, 0x77),
+    Color::Rgb(0xAA, 0xFF, 0x66),
+    Color::Rgb(0x00, 0x88, 0xFF),
+    Color::Rgb(0xBB, 0xBB, 0xBB),
+];
+
+fn draw(frame: &mut Frame, cpu: &Cpu, status: Status) {
+    let pixels: Vec<u8> = devices::screen(cpu).collect();
+    let lines: Vec<Line> = pixels
+        .chunks(SCREEN_WIDTH)
+        .take(SCREEN_HEIGHT)
+        .map(|row| {
+            // two cells per pixel keeps them roughly square
+            row.iter()
+                .map(|&colour| Span::styled("  ", Style::new().bg(PALETTE[colour as usize])))
+                .collect::<Line>()
+        })
+        .collect();
+
+    let title = match status {
+        Status::Running => "WASD to move, q to quit".to_string(),
+        status => format!("stopped: {:?}, q to quit", status),
+    };
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(title)),
+        frame.area(),
+    );
+}
+
+fn run(terminal: &mut DefaultTerminal, mut cpu: Cpu, keys: KeyInput) -> io::Result<()> {
+    let mut pacer = FramePacer::new(60.0);
+    let mut status = Status::Running;
+    loop {
+        while event::poll(Duration::ZERO)? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    KeyCode::Char(c @ ('w' | 'a' | 's' | 'd')) => keys.press(c as u8),
+                    _ => {}
+                }
+            }
+        }
+
+        for _ in 0..STEPS_PER_FRAME {
+            if status != Status::Running {
+                break;
+            }
+            status = cpu.step();
+        }
+
+        terminal.draw(|frame| draw(frame, &cpu, status))?;
+        pacer.wait();
+    }
+}
+
+fn main() {
+    let Some(path) = env::args().nth(1) else {
+        eprintln!("usage: snake <program.bin>  (assembled for $0600)");
+        process::exit(2);
+    };
+    let program = match fs::read(&path) {
+        Ok(program) => program,
+        Err(err) => {
+            eprintln!("failed to read {}: {}", path, err);
+            process::exit(1);
+        }
+    };
+
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(1, |time| time.as_nanos() as u64);
+    let keys = KeyInput::new();
+
+    let mut cpu = Cpu::new();
+    devices::install_random(&mut cpu, seed);
+    keys.install(&mut cpu);
+    devices::load_program(&mut cpu, &program);
+    cpu.reset();
+
+    let result = ratatui::run(|terminal| run(terminal, cpu, keys));
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
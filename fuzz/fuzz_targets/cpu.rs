@@ -0,0 +1,41 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use nes::cpu::{Cpu, Registers, Status};
+
+const CYCLE_BUDGET: u64 = 100_000;
+const RAM_SIZE: usize = 0x0800;
+const PROGRAM_START: u16 = 0x8000;
+
+// input: A X Y P SP, then 2K of RAM, then the program at $8000
+fuzz_target!(|data: &[u8]| {
+    let Some((&[a, x, y, status, sp], rest)) = data.split_first_chunk::<5>() else {
+        return;
+    };
+    let (ram, program) = rest.split_at(rest.len().min(RAM_SIZE));
+
+    let mut cpu = Cpu::new();
+    for (addr, &byte) in ram.iter().enumerate() {
+        cpu.mem_write(addr as u16, byte);
+    }
+    for (offset, &byte) in program.iter().take(0x10000 - PROGRAM_START as usize).enumerate() {
+        cpu.mem_write(PROGRAM_START + offset as u16, byte);
+    }
+    cpu.set_registers(Registers {
+        a,
+        x,
+        y,
+        // bit 5 isn't a real flip-flop and always reads back set
+        status: status | 0x20,
+        sp,
+        pc: PROGRAM_START,
+    });
+
+    while cpu.cycles() < CYCLE_BUDGET {
+        let status = cpu.step();
+        assert_ne!(cpu.registers().status & 0x20, 0, "bit 5 of P was cleared");
+        if status != Status::Running {
+            break;
+        }
+    }
+});
@@ -0,0 +1,104 @@
+#ifndef NES_H
+#define NES_H
+
+/* Generated by cbindgen from src/ffi.rs; do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define NES_OK 0
+
+#define NES_ERROR -1
+
+#define NES_STATUS_RUNNING 0
+
+#define NES_STATUS_HALTED 1
+
+#define NES_STATUS_BREAKPOINT 2
+
+#define NES_STATUS_WATCHPOINT 3
+
+#define NES_STATUS_UNKNOWN_OPCODE 4
+
+#define NES_STATUS_UNINITIALIZED_READ 5
+
+typedef struct NesCpu NesCpu;
+
+typedef struct NesRegisters {
+  uint8_t a;
+  uint8_t x;
+  uint8_t y;
+  uint8_t status;
+  uint8_t sp;
+  uint16_t pc;
+} NesRegisters;
+
+struct NesCpu *nes_cpu_new(void);
+
+// # Safety
+// `cpu` must come from nes_cpu_new and not be used afterwards; null is ignored.
+void nes_cpu_free(struct NesCpu *cpu);
+
+// # Safety
+// `cpu` must be live and `data` must point to `len` readable bytes.
+int32_t nes_cpu_load_rom(struct NesCpu *cpu, const uint8_t *data, uintptr_t len);
+
+// # Safety
+// `cpu` must be live and `data` must point to `len` readable bytes.
+int32_t nes_cpu_load_program(struct NesCpu *cpu, const uint8_t *data, uintptr_t len);
+
+// # Safety
+// `cpu` must be live.
+void nes_cpu_reset(struct NesCpu *cpu);
+
+// # Safety
+// `cpu` must be live.
+int32_t nes_cpu_step(struct NesCpu *cpu);
+
+// Runs until at least `cycles` more cycles have passed or the CPU stops, e.g. a frame's worth.
+//
+// # Safety
+// `cpu` must be live.
+int32_t nes_cpu_run_cycles(struct NesCpu *cpu, uint64_t cycles);
+
+// # Safety
+// `cpu` must be live.
+uint64_t nes_cpu_cycles(const struct NesCpu *cpu);
+
+// # Safety
+// `cpu` must be live and `out` writable.
+void nes_cpu_registers(const struct NesCpu *cpu, struct NesRegisters *out);
+
+// # Safety
+// `cpu` must be live and `registers` readable.
+void nes_cpu_set_registers(struct NesCpu *cpu, const struct NesRegisters *registers);
+
+// Reads without side effects, like Cpu::mem_peek.
+//
+// # Safety
+// `cpu` must be live.
+uint8_t nes_cpu_peek(const struct NesCpu *cpu, uint16_t address);
+
+// A bus write, e.g. for memory-mapped input.
+//
+// # Safety
+// `cpu` must be live.
+void nes_cpu_write(struct NesCpu *cpu, uint16_t address, uint8_t value);
+
+uintptr_t nes_state_size(void);
+
+// Writes nes_state_size() bytes into `buffer`, in the versioned save state format.
+//
+// # Safety
+// `cpu` must be live and `buffer` must point to `len` writable bytes.
+int32_t nes_cpu_save_state(const struct NesCpu *cpu, uint8_t *buffer, uintptr_t len);
+
+// Also accepts states saved by older versions.
+//
+// # Safety
+// `cpu` must be live and `buffer` must point to `len` readable bytes.
+int32_t nes_cpu_load_state(struct NesCpu *cpu, const uint8_t *buffer, uintptr_t len);
+
+#endif  /* NES_H */
@@ -0,0 +1,421 @@
+use std::collections::HashMap;
+
+use crate::cpu::instructions::{Instruction, CPU_INSTRUCTIONS};
+use crate::cpu::AddressingMode;
+use crate::symbols::{Symbol, SymbolTable};
+
+// where Cpu::load puts programs, used until the source says otherwise with .org
+const DEFAULT_ORIGIN: u16 = 0x8000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program {
+    pub origin: u16,
+    pub bytes: Vec<u8>,
+    pub symbols: SymbolTable,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("line {line}: {message}")]
+pub struct AssembleError {
+    pub line: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Value {
+    Byte(u8),
+    Word(u16),
+    Label(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Operand {
+    None,
+    Accumulator,
+    Immediate(Value),
+    Direct(Value, Option<char>),
+    Indirect(Value),
+    IndirectX(Value),
+    IndirectY(Value),
+}
+
+enum Item {
+    Instruction(&'static Instruction, Option<Value>),
+    Bytes(Vec<Value>),
+    Words(Vec<Value>),
+}
+
+struct Placed {
+    line: usize,
+    address: u16,
+    item: Item,
+}
+
+// mnemonic + operand syntax in the usual 6502 style: `LDA #$10`, `STA $0200,X`, `LDA ($10),Y`,
+// `label:` definitions and the `.org`, `.byte` and `.word` directives; `;` starts a comment
+pub fn assemble(source: &str) -> Result<Program, AssembleError> {
+    let mut labels = HashMap::new();
+    let mut symbols = SymbolTable::new();
+    let mut placed = Vec::new();
+    let mut origin = None;
+    let mut pc = DEFAULT_ORIGIN;
+
+    for (i, line) in source.lines().enumerate() {
+        let line_number = i + 1;
+        let error = |message: String| AssembleError {
+            line: line_number,
+            message,
+        };
+
+        let mut line = line.split(';').next().unwrap_or_default().trim();
+        if let Some((label, rest)) = line.split_once(':') {
+            if is_identifier(label.trim()) {
+                let label = label.trim();
+                if labels.insert(label.to_string(), pc).is_some() {
+                    return Err(error(format!("label '{}' is already defined", label)));
+                }
+                symbols.insert(
+                    pc,
+                    Symbol {
+                        name: label.to_string(),
+                        comment: None,
+                    },
+                );
+                line = rest.trim();
+            }
+        }
+        if line.is_empty() {
+            continue;
+        }
+
+        let (keyword, operand) = match line.split_once(char::is_whitespace) {
+            Some((keyword, operand)) => (keyword, operand.trim()),
+            None => (line, ""),
+        };
+
+        let (item, len) = match keyword.to_ascii_lowercase().as_str() {
+            ".org" => {
+                let address = match parse_value(operand).map_err(error)? {
+                    Value::Byte(byte) => byte as u16,
+                    Value::Word(word) => word,
+                    Value::Label(_) => return Err(error(".org needs a number".to_string())),
+                };
+                if origin.is_none() && placed.is_empty() {
+                    origin = Some(address);
+                } else if address < pc {
+                    return Err(error(format!(
+                        ".org ${:04X} is behind ${:04X}",
+                        address, pc
+                    )));
+                }
+                pc = address;
+                continue;
+            }
+            ".byte" => {
+                let values = parse_list(operand).map_err(error)?;
+                let len = values.len();
+                (Item::Bytes(values), len)
+            }
+            ".word" => {
+                let values = parse_list(operand).map_err(error)?;
+                let len = values.len() * 2;
+                (Item::Words(values), len)
+            }
+            mnemonic => {
+                let mnemonic = mnemonic.to_ascii_uppercase();
+                let operand = parse_operand(operand).map_err(error)?;
+                let (instruction, value) = select(&mnemonic, operand).map_err(error)?;
+                (
+                    Item::Instruction(instruction, value),
+                    instruction.bytes as usize,
+                )
+            }
+        };
+
+        placed.push(Placed {
+            line: line_number,
+            address: pc,
+            item,
+        });
+        pc = pc.wrapping_add(len as u16);
+    }
+
+    let origin = origin.unwrap_or(DEFAULT_ORIGIN);
+    let mut bytes = Vec::new();
+    for Placed {
+        line,
+        address,
+        item,
+    } in placed
+    {
+        let error = |message: String| AssembleError { line, message };
+        let resolve = |value: &Value| match value {
+            Value::Byte(byte) => Ok(*byte as u16),
+            Value::Word(word) => Ok(*word),
+            Value::Label(label) => labels
+                .get(label)
+                .copied()
+                .ok_or_else(|| error(format!("unknown label '{}'", label))),
+        };
+        let byte = |value: &Value| {
+            let resolved = resolve(value)?;
+            u8::try_from(resolved)
+                .map_err(|_| error(format!("${:04X} does not fit in a byte", resolved)))
+        };
+
+        let encoded = match &item {
+            Item::Instruction(instruction, value) => {
+                let mut encoded = vec![instruction.opcode];
+                match (instruction.addressing_mode, value) {
+                    (_, None) => {}
+                    (AddressingMode::Relative, Some(value)) => {
+                        let target = resolve(value)?;
+                        let offset = target.wrapping_sub(address.wrapping_add(2)) as i16;
+                        let offset = i8::try_from(offset).map_err(|_| {
+                            error(format!("branch to ${:04X} is out of range", target))
+                        })?;
+                        encoded.push(offset as u8);
+                    }
+                    (_, Some(value)) if instruction.bytes == 2 => encoded.push(byte(value)?),
+                    (_, Some(value)) => encoded.extend(resolve(value)?.to_le_bytes()),
+                }
+                encoded
+            }
+            Item::Bytes(values) => values.iter().map(byte).collect::<Result<_, _>>()?,
+            Item::Words(values) => {
+                let mut encoded = Vec::new();
+                for value in values {
+                    encoded.extend(resolve(value)?.to_le_bytes());
+                }
+                encoded
+            }
+        };
+
+        let start = address.wrapping_sub(origin) as usize;
+        if bytes.len() < start + encoded.len() {
+            bytes.resize(start + encoded.len(), 0x00);
+        }
+        bytes[start..start + encoded.len()].copy_from_slice(&encoded);
+    }
+
+    Ok(Program {
+        origin,
+        bytes,
+        symbols,
+    })
+}
+
+fn select(
+    mnemonic: &str,
+    operand: Operand,
+) -> Result<(&'static Instruction, Option<Value>), String> {
+    let find = |mode: AddressingMode| {
+        CPU_INSTRUCTIONS.iter().find(|instruction| {
+            instruction.mnemonic == mnemonic && instruction.addressing_mode == mode
+        })
+    };
+
+    if !CPU_INSTRUCTIONS
+        .iter()
+        .any(|instruction| instruction.mnemonic == mnemonic)
+    {
+        return Err(format!("unknown instruction '{}'", mnemonic));
+    }
+
+    let (instruction, value) = match operand {
+        Operand::None => (
+            find(AddressingMode::Accumulator).or_else(|| find(AddressingMode::Implicit)),
+            None,
+        ),
+        Operand::Accumulator => (find(AddressingMode::Accumulator), None),
+        Operand::Immediate(value) => (find(AddressingMode::Immediate), Some(value)),
+        Operand::Direct(value, index) => {
+            let (zero_page, absolute) = match index {
+                None => (AddressingMode::ZeroPage, AddressingMode::Absolute),
+                Some('X') => (AddressingMode::ZeroPageX, AddressingMode::AbsoluteX),
+                _ => (AddressingMode::ZeroPageY, AddressingMode::AbsoluteY),
+            };
+            // labels are resolved after sizing, so they always take the absolute form
+            let instruction = match (&value, index) {
+                (_, None) if find(AddressingMode::Relative).is_some() => {
+                    find(AddressingMode::Relative)
+                }
+                (Value::Byte(_), _) => find(zero_page).or_else(|| find(absolute)),
+                _ => find(absolute),
+            };
+            (instruction, Some(value))
+        }
+        Operand::Indirect(value) => (find(AddressingMode::Indirect), Some(value)),
+        Operand::IndirectX(value) => (find(AddressingMode::IndirectX), Some(value)),
+        Operand::IndirectY(value) => (find(AddressingMode::IndirectY), Some(value)),
+    };
+
+    instruction
+        .map(|instruction| (instruction, value))
+        .ok_or_else(|| format!("{} does not support that addressing mode", mnemonic))
+}
+
+fn parse_operand(text: &str) -> Result<Operand, String> {
+    let text: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    let upper = text.to_ascii_uppercase();
+
+    if text.is_empty() {
+        return Ok(Operand::None);
+    }
+    if upper == "A" {
+        return Ok(Operand::Accumulator);
+    }
+    if let Some(value) = text.strip_prefix('#') {
+        return Ok(Operand::Immediate(parse_value(value)?));
+    }
+    if let Some(inner) = text.strip_prefix('(') {
+        return if upper.ends_with(",X)") {
+            Ok(Operand::IndirectX(parse_value(&inner[..inner.len() - 3])?))
+        } else if upper.ends_with("),Y") {
+            Ok(Operand::IndirectY(parse_value(&inner[..inner.len() - 3])?))
+        } else if let Some(inner) = inner.strip_suffix(')') {
+            Ok(Operand::Indirect(parse_value(inner)?))
+        } else {
+            Err(format!("unbalanced parentheses in '{}'", text))
+        };
+    }
+
+    match upper.rsplit_once(',') {
+        Some((_, "X")) | Some((_, "Y")) => {
+            let (value, index) = text.split_at(text.len() - 2);
+            let index = index[1..].to_ascii_uppercase().chars().next();
+            Ok(Operand::Direct(parse_value(value)?, index))
+        }
+        Some((_, index)) => Err(format!("unknown index register '{}'", index)),
+        None => Ok(Operand::Direct(parse_value(&text)?, None)),
+    }
+}
+
+fn parse_list(text: &str) -> Result<Vec<Value>, String> {
+    text.split(',')
+        .map(|value| parse_value(value.trim()))
+        .collect()
+}
+
+// `$` hex, `%` binary or decimal; more than two hex digits forces a word, like `$0010`
+fn parse_value(text: &str) -> Result<Value, String> {
+    let invalid = || format!("invalid number '{}'", text);
+    let (digits, radix, byte_digits) = if let Some(hex) = text.strip_prefix('$') {
+        (hex, 16, 2)
+    } else if let Some(binary) = text.strip_prefix('%') {
+        (binary, 2, 8)
+    } else if text.starts_with(|c: char| c.is_ascii_digit()) {
+        (text, 10, usize::MAX)
+    } else if is_identifier(text) {
+        return Ok(Value::Label(text.to_string()));
+    } else {
+        return Err(invalid());
+    };
+
+    let value = u16::from_str_radix(digits, radix).map_err(|_| invalid())?;
+    match u8::try_from(value) {
+        Ok(byte) if digits.len() <= byte_digits => Ok(Value::Byte(byte)),
+        _ => Ok(Value::Word(value)),
+    }
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '@' || c == '.')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::Cpu;
+    use crate::disassembler::disassemble;
+
+    #[test]
+    fn test_operand_forms_round_trip_through_the_disassembler() {
+        let source = "\
+            LDA #$05
+            LDA $10
+            LDA $10,X
+            LDX $10,Y
+            LDA $1234
+            LDA $1234,X
+            LDA $1234,Y
+            LDA ($10,X)
+            LDA ($10),Y
+            ASL A
+            TAX
+            BRK";
+        let program = assemble(source).unwrap();
+        let lines: Vec<String> = disassemble(&program.bytes, program.origin)
+            .map(|line| line.to_string())
+            .collect();
+        let expected: Vec<&str> = source.lines().map(str::trim).collect();
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn test_number_formats_pick_the_addressing_mode() {
+        assert_eq!(assemble("LDA 16").unwrap().bytes, vec![0xA5, 0x10]);
+        assert_eq!(assemble("LDA $0010").unwrap().bytes, vec![0xAD, 0x10, 0x00]);
+        assert_eq!(assemble("LDA #%00001111").unwrap().bytes, vec![0xA9, 0x0F]);
+        assert_eq!(assemble("asl").unwrap().bytes, vec![0x0A]);
+        // STY has no absolute,Y form to fall back to
+        assert!(assemble("STY $10,Y").is_err());
+    }
+
+    #[test]
+    fn test_labels_and_directives() {
+        let program = assemble(
+            "
+            .org $C000
+            start:  LDA value    ; forward reference
+                    STA $0200
+                    BRK
+            value:  .byte $2A, 1
+            table:  .word start, $1234
+            .org $C010
+            end:    .byte 0",
+        )
+        .unwrap();
+
+        assert_eq!(program.origin, 0xC000);
+        assert_eq!(
+            &program.bytes[..14],
+            &[0xAD, 0x07, 0xC0, 0x8D, 0x00, 0x02, 0x00, 0x2A, 0x01, 0x00, 0xC0, 0x34, 0x12, 0x00]
+        );
+        assert_eq!(program.bytes.len(), 0x11);
+        assert_eq!(program.symbols.label(0xC007), Some("value"));
+        assert_eq!(program.symbols.address_of("end"), Some(0xC010));
+    }
+
+    #[test]
+    fn test_errors_report_the_line() {
+        let error = |source| assemble(source).unwrap_err();
+
+        assert_eq!(error("TAX\nFOO").line, 2);
+        assert_eq!(error("LDA missing").message, "unknown label 'missing'");
+        assert_eq!(error("a:\na:").line, 2);
+        assert!(error("LDA #$100").message.contains("fit in a byte"));
+        assert!(error(".org $9000\nTAX\n.org $8000")
+            .message
+            .contains("behind"));
+    }
+
+    #[test]
+    fn test_assembled_program_runs() {
+        let program = assemble(
+            "
+            LDA #$C0
+            TAX
+            INX
+            BRK",
+        )
+        .unwrap();
+        let mut cpu = Cpu::new();
+        cpu.load_and_run(program.bytes);
+        assert_eq!(cpu.registers().x, 0xC1);
+    }
+}
@@ -0,0 +1,354 @@
+use std::time::Duration;
+use std::{env, fs, io, process};
+
+use nes::cartridge::{load_nrom, load_raw, RawLayout, RomError};
+use nes::cpu::{Cpu, Status};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+const DISASSEMBLY_LINES: usize = 24;
+const MEMORY_ROWS: u16 = 16;
+// while continuing, how many instructions run between redraws
+const STEPS_PER_FRAME: usize = 10_000;
+const HISTORY_CAPACITY: usize = 1000;
+const USAGE: &str = "usage: nes-tui <rom.nes | program.bin>";
+const RESET_VECTOR: u16 = 0xFFFC;
+
+const HELP: &str =
+    "s step  u step back  c continue  p pause  b breakpoint  r reset  PgUp/PgDn memory  g memory at PC  q quit";
+
+struct App {
+    cpu: Cpu,
+    running: bool,
+    status: Option<Status>,
+    memory_base: u16,
+}
+
+impl App {
+    fn new(cpu: Cpu) -> Self {
+        Self {
+            cpu,
+            running: false,
+            status: None,
+            memory_base: 0x0000,
+        }
+    }
+
+    // a breakpoint at the current PC pauses before executing, so step past it when asked to move
+    fn step(&mut self) -> Status {
+        match self.cpu.step() {
+            Status::Breakpoint(_) => self.cpu.step(),
+            status => status,
+        }
+    }
+
+    fn run_frame(&mut self) {
+        for i in 0..STEPS_PER_FRAME {
+            let status = if i == 0 { self.step() } else { self.cpu.step() };
+            if status != Status::Running {
+                self.running = false;
+                self.status = Some(status);
+                return;
+            }
+        }
+    }
+
+    fn toggle_breakpoint(&mut self) {
+        let pc = self.cpu.registers().pc;
+        if !self.cpu.debugger_mut().remove_breakpoint(pc) {
+            self.cpu.debugger_mut().add_breakpoint(pc);
+        }
+    }
+
+    // returns false once the user quits
+    fn handle_key(&mut self, code: KeyCode) -> bool {
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Char('s') | KeyCode::Char(' ') if !self.running => {
+                self.status = Some(self.step());
+            }
+            KeyCode::Char('u') if !self.running => {
+                self.cpu.step_back();
+                self.status = None;
+            }
+            KeyCode::Char('c') => {
+                self.running = true;
+                self.status = None;
+            }
+            KeyCode::Char('p') => self.running = false,
+            KeyCode::Char('b') => self.toggle_breakpoint(),
+            KeyCode::Char('r') => {
+                self.cpu.reset();
+                self.running = false;
+                self.status = None;
+            }
+            KeyCode::Char('g') => self.memory_base = self.cpu.registers().pc & 0xFFF0,
+            KeyCode::PageUp => {
+                self.memory_base = self.memory_base.wrapping_sub(MEMORY_ROWS * 0x10);
+            }
+            KeyCode::PageDown => {
+                self.memory_base = self.memory_base.wrapping_add(MEMORY_ROWS * 0x10);
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [main, footer] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let [left, right] =
+            Layout::horizontal([Constraint::Length(44), Constraint::Min(0)]).areas(main);
+        let [registers, stack, breakpoints] = Layout::vertical([
+            Constraint::Length(5),
+            Constraint::Min(0),
+            Constraint::Length(8),
+        ])
+        .areas(left);
+        let [disassembly, memory] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(MEMORY_ROWS + 2)])
+                .areas(right);
+
+        self.draw_registers(frame, registers);
+        self.draw_stack(frame, stack);
+        self.draw_breakpoints(frame, breakpoints);
+        self.draw_disassembly(frame, disassembly);
+        self.draw_memory(frame, memory);
+        frame.render_widget(Paragraph::new(self.footer()), footer);
+    }
+
+    fn draw_registers(&self, frame: &mut Frame, area: Rect) {
+        let registers = self.cpu.registers();
+        let flags: Vec<Span> = "NV-BDIZC"
+            .chars()
+            .enumerate()
+            .map(|(i, name)| {
+                if registers.status & (0x80 >> i) != 0 {
+                    Span::styled(name.to_string(), Style::new().fg(Color::Green))
+                } else {
+                    Span::styled(name.to_string(), Style::new().fg(Color::DarkGray))
+                }
+            })
+            .collect();
+
+        let lines = vec![
+            Line::from(format!(
+                "A:{:02X}  X:{:02X}  Y:{:02X}  SP:{:02X}  PC:{:04X}",
+                registers.a, registers.x, registers.y, registers.sp, registers.pc
+            )),
+            Line::from(
+                [
+                    vec![Span::raw(format!("P:{:02X}  ", registers.status))],
+                    flags,
+                ]
+                .concat(),
+            ),
+            Line::from(format!("CYC:{}", self.cpu.cycles())),
+        ];
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title("Registers")),
+            area,
+        );
+    }
+
+    fn draw_stack(&self, frame: &mut Frame, area: Rect) {
+        let sp = self.cpu.registers().sp;
+        let lines: Vec<Line> = (sp as u16 + 1..=0xFF)
+            .take(area.height.saturating_sub(2) as usize)
+            .map(|offset| {
+                let addr = 0x0100 + offset;
+                Line::from(format!("{:04X}: {:02X}", addr, self.cpu.mem_peek(addr)))
+            })
+            .collect();
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title("Stack")),
+            area,
+        );
+    }
+
+    fn draw_breakpoints(&self, frame: &mut Frame, area: Rect) {
+        let lines: Vec<Line> = self
+            .cpu
+            .debugger()
+            .breakpoints()
+            .map(|breakpoint| {
+                let state = if breakpoint.enabled {
+                    ""
+                } else {
+                    " (disabled)"
+                };
+                let condition = breakpoint
+                    .condition
+                    .as_ref()
+                    .map(|condition| format!(" if {}", condition))
+                    .unwrap_or_default();
+                Line::from(format!("${:04X}{}{}", breakpoint.address, condition, state))
+            })
+            .collect();
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title("Breakpoints")),
+            area,
+        );
+    }
+
+    fn draw_disassembly(&self, frame: &mut Frame, area: Rect) {
+        let pc = self.cpu.registers().pc;
+        let debugger = self.cpu.debugger();
+        let mut addr = pc;
+        let mut lines = Vec::new();
+
+        for _ in 0..DISASSEMBLY_LINES.min(area.height.saturating_sub(2) as usize) {
+            let disassembled = self.cpu.disassemble_at(addr);
+            let bytes = disassembled
+                .bytes
+                .iter()
+                .map(|byte| format!("{:02X}", byte))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let marker = if debugger
+                .breakpoints()
+                .any(|b| b.address == addr && b.enabled)
+            {
+                '*'
+            } else {
+                ' '
+            };
+            let text = format!("{}{:04X}  {:<8}  {}", marker, addr, bytes, disassembled);
+
+            lines.push(if addr == pc {
+                Line::styled(text, Style::new().add_modifier(Modifier::REVERSED))
+            } else {
+                Line::from(text)
+            });
+            addr = addr.wrapping_add(disassembled.len() as u16);
+        }
+
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title("Disassembly")),
+            area,
+        );
+    }
+
+    fn draw_memory(&self, frame: &mut Frame, area: Rect) {
+        let lines: Vec<Line> = (0..MEMORY_ROWS)
+            .map(|row| {
+                let base = self.memory_base.wrapping_add(row * 0x10);
+                let bytes: Vec<u8> = (0..0x10)
+                    .map(|i| self.cpu.mem_peek(base.wrapping_add(i)))
+                    .collect();
+                let hex = bytes
+                    .iter()
+                    .map(|byte| format!("{:02X}", byte))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let ascii: String = bytes
+                    .iter()
+                    .map(|&byte| {
+                        if byte.is_ascii_graphic() {
+                            byte as char
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect();
+                Line::from(format!("{:04X}: {}  {}", base, hex, ascii))
+            })
+            .collect();
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title("Memory")),
+            area,
+        );
+    }
+
+    fn footer(&self) -> String {
+        let state = match self.status {
+            _ if self.running => "running".to_string(),
+            None | Some(Status::Running) => "paused".to_string(),
+            Some(Status::Halted) => "halted (BRK)".to_string(),
+            Some(Status::Breakpoint(addr)) => format!("breakpoint at ${:04X}", addr),
+            Some(Status::Watchpoint(hit)) => format!(
+                "watchpoint: {:?} ${:04X} = {:02X} by ${:04X}",
+                hit.kind, hit.address, hit.value, hit.pc
+            ),
+            Some(Status::UnknownOpcode(opcode)) => format!("unknown opcode ${:02X}", opcode),
+            Some(Status::UninitializedRead(read)) => format!(
+                "uninitialized read of ${:04X} by ${:04X}",
+                read.address, read.pc
+            ),
+        };
+        format!("[{}]  {}", state, HELP)
+    }
+}
+
+fn run(terminal: &mut DefaultTerminal, mut app: App) -> io::Result<()> {
+    loop {
+        terminal.draw(|frame| app.draw(frame))?;
+
+        let timeout = if app.running {
+            Duration::ZERO
+        } else {
+            Duration::from_millis(250)
+        };
+        if event::poll(timeout)? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && !app.handle_key(key.code) {
+                    return Ok(());
+                }
+            }
+        }
+
+        if app.running {
+            app.run_frame();
+        }
+    }
+}
+
+// iNES images go through the cartridge loader and run BRK like the hardware; anything else is
+// raw code at $8000, started from there unless it reaches the vectors itself
+fn load(cpu: &mut Cpu, image: &[u8]) -> Result<(), RomError> {
+    if image.starts_with(b"NES\x1A") {
+        load_nrom(cpu, image)?;
+        cpu.set_halt_on_brk(false);
+        return Ok(());
+    }
+
+    let layout = RawLayout::default();
+    load_raw(cpu, image, &layout)?;
+    if layout.load_address as usize + image.len() <= RESET_VECTOR as usize {
+        cpu.mem_write_u16(RESET_VECTOR, layout.load_address);
+    }
+    Ok(())
+}
+
+fn main() {
+    let Some(path) = env::args().nth(1) else {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    };
+    let program = match fs::read(&path) {
+        Ok(program) => program,
+        Err(err) => {
+            eprintln!("failed to read {}: {}", path, err);
+            process::exit(1);
+        }
+    };
+
+    let mut cpu = Cpu::new();
+    if let Err(err) = load(&mut cpu, &program) {
+        eprintln!("can't load {}: {}", path, err);
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    }
+    cpu.reset();
+    cpu.debugger_mut().set_history_capacity(HISTORY_CAPACITY);
+
+    let result = ratatui::run(|terminal| run(terminal, App::new(cpu)));
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
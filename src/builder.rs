@@ -0,0 +1,271 @@
+use crate::cartridge::{detect_region, load_nrom, RomError};
+use crate::config::{Config, ConfigError, RewindConfig};
+use crate::cpu::{Cpu, RamInit, MAX_PROGRAM_SIZE};
+use crate::debugger::Heatmap;
+use crate::pacing::Region;
+use crate::patch::{apply_patch, PatchError};
+use crate::rewind::Rewind;
+
+#[derive(Debug, thiserror::Error)]
+pub enum BuildError {
+    #[error("a cartridge and a bare program can't both be loaded")]
+    ConflictingImages,
+    #[error("a patch was given without a cartridge to apply it to")]
+    NothingToPatch,
+    #[error("a {size} byte program doesn't fit below the vectors")]
+    ProgramTooLarge { size: usize },
+    #[error(transparent)]
+    Rom(#[from] RomError),
+    #[error(transparent)]
+    Patch(#[from] PatchError),
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+}
+
+// a built Cpu with the host-side pieces the options describe, from CpuBuilder::build_machine
+pub struct Machine {
+    pub cpu: Cpu,
+    // what to pace frames by: the override, or else whatever the cartridge says, or else NTSC
+    pub region: Region,
+    pub rewind: Option<Rewind>,
+}
+
+// collects a machine's options and checks them together in build, instead of poking setters on
+// a Cpu that may already be half set up
+#[derive(Debug, Clone, Default)]
+pub struct CpuBuilder {
+    ram_init: RamInit,
+    region: Option<Region>,
+    rewind: RewindConfig,
+    cartridge: Option<Vec<u8>>,
+    patch: Option<Vec<u8>>,
+    program: Option<Vec<u8>>,
+    history_capacity: usize,
+    heatmap: Option<Heatmap>,
+}
+
+impl CpuBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // picks up the options a config file can set
+    pub fn config(mut self, config: &Config) -> Self {
+        self.ram_init = config.ram_init;
+        self.region = config.region;
+        self.rewind = config.rewind;
+        self
+    }
+
+    pub fn ram_init(mut self, ram_init: RamInit) -> Self {
+        self.ram_init = ram_init;
+        self
+    }
+
+    // forces a region instead of detecting one from the cartridge
+    pub fn region(mut self, region: Region) -> Self {
+        self.region = Some(region);
+        self
+    }
+
+    pub fn rewind(mut self, rewind: RewindConfig) -> Self {
+        self.rewind = rewind;
+        self
+    }
+
+    // an iNES image; only NROM for now
+    pub fn cartridge(mut self, rom: Vec<u8>) -> Self {
+        self.cartridge = Some(rom);
+        self
+    }
+
+    // an IPS or BPS patch applied to the cartridge image before its header is read
+    pub fn patch(mut self, patch: Vec<u8>) -> Self {
+        self.patch = Some(patch);
+        self
+    }
+
+    // raw code loaded the way Cpu::load does it
+    pub fn program(mut self, program: Vec<u8>) -> Self {
+        self.program = Some(program);
+        self
+    }
+
+    pub fn history_capacity(mut self, instructions: usize) -> Self {
+        self.history_capacity = instructions;
+        self
+    }
+
+    pub fn heatmap(mut self, heatmap: Heatmap) -> Self {
+        self.heatmap = Some(heatmap);
+        self
+    }
+
+    // the machine comes out reset if anything was loaded into it; region and rewind are checked
+    // here too, but only build_machine hands them back
+    pub fn build(self) -> Result<Cpu, BuildError> {
+        self.build_machine().map(|machine| machine.cpu)
+    }
+
+    pub fn build_machine(self) -> Result<Machine, BuildError> {
+        if self.cartridge.is_some() && self.program.is_some() {
+            return Err(BuildError::ConflictingImages);
+        }
+        let cartridge = match (self.cartridge, &self.patch) {
+            (Some(rom), Some(patch)) => Some(apply_patch(&rom, patch)?),
+            (None, Some(_)) => return Err(BuildError::NothingToPatch),
+            (rom, None) => rom,
+        };
+        if let Some(program) = &self.program {
+            if program.len() > MAX_PROGRAM_SIZE {
+                return Err(BuildError::ProgramTooLarge {
+                    size: program.len(),
+                });
+            }
+        }
+
+        let rewind = self.rewind.build()?;
+        let region = self
+            .region
+            .or_else(|| {
+                cartridge
+                    .as_deref()
+                    .and_then(|rom| detect_region(rom, None))
+            })
+            .unwrap_or_default();
+
+        let loaded = cartridge.is_some() || self.program.is_some();
+        let mut cpu = Cpu::with_ram_init(self.ram_init);
+        if let Some(rom) = &cartridge {
+            load_nrom(&mut cpu, rom)?;
+        }
+        if let Some(program) = self.program {
+            cpu.load(program);
+        }
+
+        let debugger = cpu.debugger_mut();
+        debugger.set_history_capacity(self.history_capacity);
+        debugger.set_heatmap(self.heatmap);
+
+        if loaded {
+            cpu.reset();
+        }
+        Ok(Machine {
+            cpu,
+            region,
+            rewind,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::Registers;
+
+    #[test]
+    fn test_build_with_program() {
+        let mut cpu = CpuBuilder::new()
+            .ram_init(RamInit::Ones)
+            .program(vec![0xA9, 0x05, 0x00])
+            .history_capacity(10)
+            .build()
+            .unwrap();
+
+        assert_eq!(cpu.registers().pc, 0x8000);
+        assert_eq!(cpu.mem_peek(0x0000), 0xFF);
+        assert_eq!(cpu.debugger().history_capacity(), 10);
+        cpu.run();
+        assert_eq!(cpu.registers().a, 0x05);
+    }
+
+    #[test]
+    fn test_cartridge_is_patched_before_loading() {
+        let mut rom = vec![0; 16 + 0x4000];
+        rom[..4].copy_from_slice(b"NES\x1A");
+        rom[4] = 1;
+        // IPS: LDA #$07 at $8000, BRK after it
+        let patch = b"PATCH\x00\x00\x10\x00\x02\xA9\x07EOF".to_vec();
+
+        let mut cpu = CpuBuilder::new()
+            .cartridge(rom.clone())
+            .patch(patch.clone())
+            .build()
+            .unwrap();
+        cpu.set_registers(Registers {
+            pc: 0x8000,
+            ..cpu.registers()
+        });
+        cpu.run();
+        assert_eq!(cpu.registers().a, 0x07);
+
+        assert!(matches!(
+            CpuBuilder::new().patch(patch).build(),
+            Err(BuildError::NothingToPatch)
+        ));
+        assert!(matches!(
+            CpuBuilder::new().cartridge(rom).patch(vec![1]).build(),
+            Err(BuildError::Patch(PatchError::NotAPatch))
+        ));
+    }
+
+    #[test]
+    fn test_conflicts_are_rejected() {
+        let builder = CpuBuilder::new().cartridge(Vec::new());
+        assert!(matches!(
+            builder.clone().program(vec![0x00]).build(),
+            Err(BuildError::ConflictingImages)
+        ));
+        assert!(matches!(
+            builder.build(),
+            Err(BuildError::Rom(RomError::NotINes))
+        ));
+    }
+
+    #[test]
+    fn test_oversized_program_is_rejected() {
+        assert!(matches!(
+            CpuBuilder::new().program(vec![0xEA; 0x9000]).build(),
+            Err(BuildError::ProgramTooLarge { size: 0x9000 })
+        ));
+        assert!(CpuBuilder::new()
+            .program(vec![0xEA; MAX_PROGRAM_SIZE])
+            .build()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_config_region_and_rewind_are_applied() {
+        let mut rom = vec![0; 16 + 0x4000];
+        rom[..4].copy_from_slice(b"NES\x1A");
+        rom[4] = 1;
+        // NES 2.0, PAL timing
+        rom[7] = 0x08;
+        rom[12] = 0x01;
+
+        let machine = CpuBuilder::new()
+            .cartridge(rom.clone())
+            .build_machine()
+            .unwrap();
+        assert_eq!(machine.region, Region::Pal);
+        assert!(machine.rewind.is_none());
+
+        let config = Config::from_toml("region = \"dendy\"\n[rewind]\nenabled = true").unwrap();
+        let machine = CpuBuilder::new()
+            .config(&config)
+            .cartridge(rom)
+            .build_machine()
+            .unwrap();
+        assert_eq!(machine.region, Region::Dendy);
+        assert!(machine.rewind.is_some());
+
+        let rewind = RewindConfig {
+            interval: 0,
+            ..RewindConfig::default()
+        };
+        assert!(matches!(
+            CpuBuilder::new().rewind(rewind).build(),
+            Err(BuildError::Config(ConfigError::Invalid(_)))
+        ));
+    }
+}
@@ -0,0 +1,388 @@
+use crate::cpu::Cpu;
+use crate::pacing::Region;
+
+pub(crate) const INES_MAGIC: &[u8; 4] = b"NES\x1A";
+pub(crate) const INES_HEADER_SIZE: usize = 16;
+const TRAINER_SIZE: usize = 512;
+const PRG_BANK_SIZE: usize = 0x4000;
+const CHR_BANK_SIZE: usize = 0x2000;
+
+// No-Intro and GoodNES region tags, which only ever appear in parentheses; bracketed GoodNES
+// flags like [a] and [f] mean alternate and fixed dumps, not Australia and France
+const PAL_TAGS: &[&str] = &[
+    "e",
+    "europe",
+    "pal",
+    "a",
+    "australia",
+    "g",
+    "germany",
+    "f",
+    "france",
+    "i",
+    "italy",
+    "s",
+    "spain",
+    "sw",
+    "sweden",
+    "nl",
+    "netherlands",
+    "uk",
+];
+const NTSC_TAGS: &[&str] = &["u", "usa", "j", "japan", "ntsc", "k", "korea"];
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum RomError {
+    #[error("not an iNES image")]
+    NotINes,
+    #[error("only NROM is supported, got mapper {mapper} with {prg_banks} PRG banks")]
+    UnsupportedMapper { mapper: u8, prg_banks: usize },
+    #[error("PRG ROM is truncated")]
+    Truncated,
+    #[error("{size} bytes don't fit in memory at ${address:04X}")]
+    DoesNotFit { address: u16, size: usize },
+}
+
+// nametable layout, for the PPU once there is one
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Mirroring {
+    #[default]
+    Horizontal,
+    Vertical,
+    FourScreen,
+}
+
+// describes a headerless image: what an iNES header would have said, plus where the PRG goes
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RawLayout {
+    pub mapper: u8,
+    pub load_address: u16,
+    // None takes everything up to the CHR data
+    pub prg_size: Option<usize>,
+    pub chr_size: usize,
+    pub mirroring: Mirroring,
+}
+
+impl Default for RawLayout {
+    fn default() -> Self {
+        Self {
+            mapper: 0,
+            load_address: 0x8000,
+            prg_size: None,
+            chr_size: 0,
+            mirroring: Mirroring::default(),
+        }
+    }
+}
+
+// what the loaders found out about an image beyond the PRG they put in memory
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Cartridge {
+    pub mapper: u8,
+    pub prg_size: usize,
+    pub chr_size: usize,
+    pub mirroring: Mirroring,
+}
+
+// maps an NROM image's PRG ROM at $8000, mirroring a single 16K bank into $C000 too
+pub fn load_nrom(cpu: &mut Cpu, rom: &[u8]) -> Result<Cartridge, RomError> {
+    if rom.len() < INES_HEADER_SIZE || &rom[..4] != INES_MAGIC {
+        log::warn!("rejecting ROM: no iNES header");
+        return Err(RomError::NotINes);
+    }
+
+    let banks = rom[4] as usize;
+    let mapper = (rom[6] >> 4) | (rom[7] & 0xF0);
+    if mapper != 0 || !(1..=2).contains(&banks) {
+        log::warn!("rejecting ROM: mapper {} with {} PRG banks", mapper, banks);
+        return Err(RomError::UnsupportedMapper {
+            mapper,
+            prg_banks: banks,
+        });
+    }
+
+    let trainer = rom[6] & 0x04 != 0;
+    let start = INES_HEADER_SIZE + if trainer { TRAINER_SIZE } else { 0 };
+    let prg = rom
+        .get(start..start + banks * PRG_BANK_SIZE)
+        .ok_or(RomError::Truncated)?;
+    log::info!(
+        "loading NROM image: {} PRG bank(s){}{}",
+        banks,
+        if banks == 1 {
+            ", mirrored at $C000"
+        } else {
+            ""
+        },
+        if trainer { ", skipping trainer" } else { "" }
+    );
+
+    cpu.write_image(0x8000, prg);
+    if banks == 1 {
+        cpu.write_image(0xC000, prg);
+    }
+
+    let mirroring = if rom[6] & 0x08 != 0 {
+        Mirroring::FourScreen
+    } else if rom[6] & 0x01 != 0 {
+        Mirroring::Vertical
+    } else {
+        Mirroring::Horizontal
+    };
+    Ok(Cartridge {
+        mapper,
+        prg_size: prg.len(),
+        chr_size: rom[5] as usize * CHR_BANK_SIZE,
+        mirroring,
+    })
+}
+
+// loads PRG data with no header, e.g. a Klaus Dormann test binary at $0000 or an EPROM dump at
+// $8000; a single 16K bank at $8000 is mirrored into $C000 as on NROM. CHR data comes after the
+// PRG and is skipped, as there's no PPU to put it in
+pub fn load_raw(cpu: &mut Cpu, data: &[u8], layout: &RawLayout) -> Result<Cartridge, RomError> {
+    let available = data
+        .len()
+        .checked_sub(layout.chr_size)
+        .ok_or(RomError::Truncated)?;
+    let prg_size = layout.prg_size.unwrap_or(available);
+    if prg_size > available {
+        return Err(RomError::Truncated);
+    }
+    if layout.mapper != 0 {
+        log::warn!("rejecting raw image: mapper {}", layout.mapper);
+        return Err(RomError::UnsupportedMapper {
+            mapper: layout.mapper,
+            prg_banks: prg_size.div_ceil(PRG_BANK_SIZE),
+        });
+    }
+    if layout.load_address as usize + prg_size > 0x10000 {
+        return Err(RomError::DoesNotFit {
+            address: layout.load_address,
+            size: prg_size,
+        });
+    }
+
+    let prg = &data[..prg_size];
+    let mirrored = layout.load_address == 0x8000 && prg_size == PRG_BANK_SIZE;
+    log::info!(
+        "loading raw image: {} bytes at ${:04X}{}",
+        prg_size,
+        layout.load_address,
+        if mirrored { ", mirrored at $C000" } else { "" }
+    );
+
+    cpu.write_image(layout.load_address, prg);
+    if mirrored {
+        cpu.write_image(0xC000, prg);
+    }
+    Ok(Cartridge {
+        mapper: layout.mapper,
+        prg_size,
+        chr_size: layout.chr_size,
+        mirroring: layout.mirroring,
+    })
+}
+
+// the NES 2.0 timing field first, then region tags in the file name; None when neither says.
+// There's no ROM database to consult between the two yet
+pub fn detect_region(rom: &[u8], file_name: Option<&str>) -> Option<Region> {
+    region_from_header(rom).or_else(|| file_name.and_then(region_from_file_name))
+}
+
+fn region_from_header(rom: &[u8]) -> Option<Region> {
+    if rom.len() < INES_HEADER_SIZE || &rom[..4] != INES_MAGIC || rom[7] & 0x0C != 0x08 {
+        return None;
+    }
+    match rom[12] & 0x03 {
+        0 => Some(Region::Ntsc),
+        1 => Some(Region::Pal),
+        3 => Some(Region::Dendy),
+        // multi-region carts run on either, so let the file name decide
+        _ => None,
+    }
+}
+
+fn region_from_file_name(name: &str) -> Option<Region> {
+    let tags = name
+        .split(['(', ')'])
+        .skip(1)
+        .step_by(2)
+        .flat_map(|group| group.split(','))
+        .map(|tag| tag.trim().to_ascii_lowercase());
+
+    for tag in tags {
+        if tag == "dendy" {
+            return Some(Region::Dendy);
+        }
+        if PAL_TAGS.contains(&tag.as_str()) {
+            return Some(Region::Pal);
+        }
+        if NTSC_TAGS.contains(&tag.as_str()) {
+            return Some(Region::Ntsc);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::debugger::Heatmap;
+
+    fn nes2_header(timing: u8) -> Vec<u8> {
+        let mut rom = vec![0; INES_HEADER_SIZE];
+        rom[..4].copy_from_slice(INES_MAGIC);
+        rom[7] = 0x08;
+        rom[12] = timing;
+        rom
+    }
+
+    #[test]
+    fn test_load_nrom_mirrors_a_single_bank() {
+        let mut rom = vec![0; INES_HEADER_SIZE + PRG_BANK_SIZE];
+        rom[..4].copy_from_slice(INES_MAGIC);
+        rom[4] = 1;
+        rom[INES_HEADER_SIZE] = 0x4C;
+        rom[INES_HEADER_SIZE + PRG_BANK_SIZE - 1] = 0xC0;
+
+        let mut cpu = Cpu::new();
+        let cartridge = load_nrom(&mut cpu, &rom).unwrap();
+        assert_eq!(cartridge.prg_size, PRG_BANK_SIZE);
+        assert_eq!(cartridge.mirroring, Mirroring::Horizontal);
+        assert_eq!(cpu.mem_read(0x8000), 0x4C);
+        assert_eq!(cpu.mem_read(0xC000), 0x4C);
+        assert_eq!(cpu.mem_read(0xFFFF), 0xC0);
+
+        rom[6] = 0x10;
+        assert_eq!(
+            load_nrom(&mut cpu, &rom),
+            Err(RomError::UnsupportedMapper {
+                mapper: 1,
+                prg_banks: 1
+            })
+        );
+        assert_eq!(load_nrom(&mut cpu, &rom[..4]), Err(RomError::NotINes));
+    }
+
+    #[test]
+    fn test_loading_bypasses_the_bus() {
+        let mut rom = vec![0; INES_HEADER_SIZE + PRG_BANK_SIZE];
+        rom[..4].copy_from_slice(INES_MAGIC);
+        rom[4] = 1;
+        rom[INES_HEADER_SIZE] = 0xEA;
+
+        let mut cpu = Cpu::new();
+        cpu.hooks_mut().on_write(0x0000..=0xFFFF, |_, _| Some(0xFF));
+        cpu.debugger_mut().set_heatmap(Some(Heatmap::new(1)));
+        load_nrom(&mut cpu, &rom).unwrap();
+        load_raw(&mut cpu, &[0xE8], &RawLayout::default()).unwrap();
+
+        assert_eq!(cpu.mem_peek(0x8000), 0xE8);
+        assert_eq!(cpu.mem_peek(0xC000), 0xEA);
+        assert_eq!(cpu.mem_peek(0xC001), 0x00);
+        assert_eq!(cpu.debugger().heatmap().unwrap().get(0x8000).writes, 0);
+    }
+
+    #[test]
+    fn test_load_raw() {
+        let mut cpu = Cpu::new();
+        let layout = RawLayout {
+            load_address: 0x0400,
+            ..RawLayout::default()
+        };
+        load_raw(&mut cpu, &[0xA9, 0x01, 0x00], &layout).unwrap();
+        assert_eq!(cpu.mem_peek(0x0401), 0x01);
+        assert_eq!(cpu.mem_peek(0x0403), 0x00);
+
+        // a single bank with 8K of CHR after it
+        let mut image = vec![0; PRG_BANK_SIZE + 0x2000];
+        image[0] = 0x4C;
+        image[PRG_BANK_SIZE] = 0xFF;
+        let layout = RawLayout {
+            chr_size: 0x2000,
+            mirroring: Mirroring::Vertical,
+            ..RawLayout::default()
+        };
+        let cartridge = load_raw(&mut cpu, &image, &layout).unwrap();
+        assert_eq!(cartridge.mirroring, Mirroring::Vertical);
+        assert_eq!(cartridge.chr_size, 0x2000);
+        assert_eq!(cpu.mem_peek(0xC000), 0x4C);
+        assert_eq!(cpu.mem_peek(0xC000 + 1), 0x00);
+
+        let layout = RawLayout {
+            load_address: 0xFFFF,
+            ..RawLayout::default()
+        };
+        assert_eq!(
+            load_raw(&mut cpu, &[0; 2], &layout),
+            Err(RomError::DoesNotFit {
+                address: 0xFFFF,
+                size: 2
+            })
+        );
+        let layout = RawLayout {
+            prg_size: Some(4),
+            chr_size: 1,
+            ..RawLayout::default()
+        };
+        assert_eq!(
+            load_raw(&mut cpu, &[0; 4], &layout),
+            Err(RomError::Truncated)
+        );
+        let layout = RawLayout {
+            mapper: 4,
+            ..RawLayout::default()
+        };
+        assert!(matches!(
+            load_raw(&mut cpu, &[0; 4], &layout),
+            Err(RomError::UnsupportedMapper { mapper: 4, .. })
+        ));
+    }
+
+    #[test]
+    fn test_region_detection() {
+        assert_eq!(detect_region(&nes2_header(1), None), Some(Region::Pal));
+        assert_eq!(detect_region(&nes2_header(3), None), Some(Region::Dendy));
+        // the header wins over the file name
+        assert_eq!(
+            detect_region(&nes2_header(0), Some("Game (Europe).nes")),
+            Some(Region::Ntsc)
+        );
+        assert_eq!(
+            detect_region(&nes2_header(2), Some("Game (Europe).nes")),
+            Some(Region::Pal)
+        );
+
+        // plain iNES has no reliable timing field
+        let mut ines = nes2_header(1);
+        ines[7] = 0;
+        assert_eq!(detect_region(&ines, None), None);
+        assert_eq!(
+            detect_region(&ines, Some("Game (E) [!].nes")),
+            Some(Region::Pal)
+        );
+        assert_eq!(
+            detect_region(&ines, Some("Game (USA, Europe) (Rev 1).nes")),
+            Some(Region::Ntsc)
+        );
+        assert_eq!(
+            detect_region(&ines, Some("Game (Dendy) [b1].nes")),
+            Some(Region::Dendy)
+        );
+        assert_eq!(detect_region(&ines, Some("Europe.nes")), None);
+    }
+
+    #[test]
+    fn test_dump_flags_are_not_regions() {
+        assert_eq!(
+            region_from_file_name("Game [f] (U).nes"),
+            Some(Region::Ntsc)
+        );
+        assert_eq!(region_from_file_name("Game [a] [!].nes"), None);
+        assert_eq!(
+            region_from_file_name("Game [a1] (J) [f2].nes"),
+            Some(Region::Ntsc)
+        );
+    }
+}
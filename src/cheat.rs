@@ -0,0 +1,201 @@
+pub mod game_genie;
+pub mod ram_search;
+
+pub use game_genie::{GameGenieCode, GameGenieError};
+pub use ram_search::RamSearch;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct CheatId(usize);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Cheat {
+    GameGenie(GameGenieCode),
+    Patch { address: u16, value: u8 },
+    Freeze { address: u16, value: u8 },
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    id: CheatId,
+    cheat: Cheat,
+    enabled: bool,
+    applied: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CheatManager {
+    next_id: usize,
+    entries: Vec<Entry>,
+}
+
+impl CheatManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, cheat: Cheat) -> CheatId {
+        let id = CheatId(self.next_id);
+        self.next_id += 1;
+
+        self.entries.push(Entry {
+            id,
+            cheat,
+            enabled: true,
+            applied: false,
+        });
+        id
+    }
+
+    pub fn add_game_genie(&mut self, code: &str) -> Result<CheatId, GameGenieError> {
+        let code = GameGenieCode::decode(code)?;
+        Ok(self.add(Cheat::GameGenie(code)))
+    }
+
+    pub fn remove(&mut self, id: CheatId) -> Option<Cheat> {
+        let index = self.entries.iter().position(|entry| entry.id == id)?;
+        Some(self.entries.remove(index).cheat)
+    }
+
+    pub fn set_enabled(&mut self, id: CheatId, enabled: bool) -> bool {
+        match self.entries.iter_mut().find(|entry| entry.id == id) {
+            Some(entry) => {
+                if enabled && !entry.enabled {
+                    entry.applied = false;
+                }
+                entry.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_enabled(&self, id: CheatId) -> Option<bool> {
+        self.entries
+            .iter()
+            .find(|entry| entry.id == id)
+            .map(|entry| entry.enabled)
+    }
+
+    pub fn get(&self, id: CheatId) -> Option<&Cheat> {
+        self.entries
+            .iter()
+            .find(|entry| entry.id == id)
+            .map(|entry| &entry.cheat)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // game genie codes patch reads of cartridge space ($8000-$FFFF)
+    pub fn apply_read(&self, addr: u16, value: u8) -> u8 {
+        if addr < 0x8000 {
+            return value;
+        }
+
+        self.entries
+            .iter()
+            .filter(|entry| entry.enabled)
+            .fold(value, |value, entry| match &entry.cheat {
+                Cheat::GameGenie(code) => code.apply(addr, value),
+                _ => value,
+            })
+    }
+
+    // frozen addresses ignore whatever the program tries to write to them
+    pub fn apply_write(&self, addr: u16, value: u8) -> u8 {
+        self.entries
+            .iter()
+            .filter(|entry| entry.enabled)
+            .fold(value, |value, entry| match entry.cheat {
+                Cheat::Freeze { address, value } if address == addr => value,
+                _ => value,
+            })
+    }
+
+    // memory writes still owed: every enabled freeze plus patches that were not written yet
+    pub fn take_pending_writes(&mut self) -> Vec<(u16, u8)> {
+        let mut writes = Vec::new();
+        for entry in self.entries.iter_mut().filter(|entry| entry.enabled) {
+            match entry.cheat {
+                Cheat::Patch { address, value } if !entry.applied => {
+                    entry.applied = true;
+                    writes.push((address, value));
+                }
+                Cheat::Freeze { address, value } => writes.push((address, value)),
+                _ => {}
+            }
+        }
+        writes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_and_remove() {
+        let mut cheats = CheatManager::new();
+        let id = cheats.add_game_genie("SXIOPO").unwrap();
+        assert_eq!(cheats.apply_read(0x91D9, 0xCE), 0xAD);
+
+        assert!(cheats.remove(id).is_some());
+        assert!(cheats.remove(id).is_none());
+        assert_eq!(cheats.apply_read(0x91D9, 0xCE), 0xCE);
+    }
+
+    #[test]
+    fn test_disabled_cheat_is_ignored() {
+        let mut cheats = CheatManager::new();
+        let id = cheats.add_game_genie("SXIOPO").unwrap();
+
+        assert!(cheats.set_enabled(id, false));
+        assert_eq!(cheats.is_enabled(id), Some(false));
+        assert_eq!(cheats.apply_read(0x91D9, 0xCE), 0xCE);
+    }
+
+    #[test]
+    fn test_patch_is_written_once() {
+        let mut cheats = CheatManager::new();
+        let id = cheats.add(Cheat::Patch {
+            address: 0x0010,
+            value: 0x09,
+        });
+
+        assert_eq!(cheats.take_pending_writes(), vec![(0x0010, 0x09)]);
+        assert!(cheats.take_pending_writes().is_empty());
+
+        cheats.set_enabled(id, false);
+        cheats.set_enabled(id, true);
+        assert_eq!(cheats.take_pending_writes(), vec![(0x0010, 0x09)]);
+    }
+
+    #[test]
+    fn test_freeze_overrides_writes() {
+        let mut cheats = CheatManager::new();
+        let id = cheats.add(Cheat::Freeze {
+            address: 0x0010,
+            value: 0x09,
+        });
+
+        assert_eq!(cheats.apply_write(0x0010, 0x00), 0x09);
+        assert_eq!(cheats.apply_write(0x0011, 0x00), 0x00);
+        assert_eq!(cheats.take_pending_writes(), vec![(0x0010, 0x09)]);
+        assert_eq!(cheats.take_pending_writes(), vec![(0x0010, 0x09)]);
+
+        cheats.set_enabled(id, false);
+        assert_eq!(cheats.apply_write(0x0010, 0x00), 0x00);
+    }
+
+    #[test]
+    fn test_invalid_code_is_rejected() {
+        let mut cheats = CheatManager::new();
+        assert!(cheats.add_game_genie("HELLO").is_err());
+        assert!(cheats.is_empty());
+    }
+}
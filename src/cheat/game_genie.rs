@@ -0,0 +1,122 @@
+const LETTERS: [char; 16] = [
+    'A', 'P', 'Z', 'L', 'G', 'I', 'T', 'Y', 'E', 'O', 'X', 'U', 'K', 'S', 'V', 'N',
+];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct GameGenieCode {
+    pub address: u16,
+    pub value: u8,
+    pub compare: Option<u8>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum GameGenieError {
+    #[error("game genie codes are 6 or 8 letters long, got {0}")]
+    InvalidLength(usize),
+    #[error("'{0}' is not a game genie letter")]
+    InvalidLetter(char),
+}
+
+impl GameGenieCode {
+    pub fn decode(code: &str) -> Result<Self, GameGenieError> {
+        let n = code
+            .chars()
+            .map(|letter| {
+                LETTERS
+                    .iter()
+                    .position(|&l| l == letter.to_ascii_uppercase())
+                    .map(|n| n as u16)
+                    .ok_or(GameGenieError::InvalidLetter(letter))
+            })
+            .collect::<Result<Vec<u16>, _>>()?;
+
+        if n.len() != 6 && n.len() != 8 {
+            return Err(GameGenieError::InvalidLength(n.len()));
+        }
+
+        let address = 0x8000
+            | ((n[3] & 7) << 12)
+            | ((n[5] & 7) << 8)
+            | ((n[4] & 8) << 8)
+            | ((n[2] & 7) << 4)
+            | ((n[1] & 8) << 4)
+            | (n[4] & 7)
+            | (n[3] & 8);
+
+        let value_low = if n.len() == 6 { n[5] } else { n[7] };
+        let value = ((n[1] & 7) << 4) | ((n[0] & 8) << 4) | (n[0] & 7) | (value_low & 8);
+
+        let compare =
+            (n.len() == 8).then(|| ((n[7] & 7) << 4) | ((n[6] & 8) << 4) | (n[6] & 7) | (n[5] & 8));
+
+        Ok(Self {
+            address,
+            value: value as u8,
+            compare: compare.map(|compare| compare as u8),
+        })
+    }
+
+    pub fn apply(&self, addr: u16, value: u8) -> u8 {
+        if addr != self.address {
+            return value;
+        }
+
+        match self.compare {
+            Some(compare) if compare != value => value,
+            _ => self.value,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_6_letter_code() {
+        assert_eq!(
+            GameGenieCode::decode("SXIOPO"),
+            Ok(GameGenieCode {
+                address: 0x91D9,
+                value: 0xAD,
+                compare: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_decode_8_letter_code() {
+        assert_eq!(
+            GameGenieCode::decode("aaaaaase"),
+            Ok(GameGenieCode {
+                address: 0x8000,
+                value: 0x08,
+                compare: Some(0x85),
+            })
+        );
+    }
+
+    #[test]
+    fn test_decode_invalid_code() {
+        assert_eq!(
+            GameGenieCode::decode("SXIOP"),
+            Err(GameGenieError::InvalidLength(5))
+        );
+        assert_eq!(
+            GameGenieCode::decode("SXIOPB"),
+            Err(GameGenieError::InvalidLetter('B'))
+        );
+    }
+
+    #[test]
+    fn test_apply_with_compare() {
+        let code = GameGenieCode {
+            address: 0x8000,
+            value: 0x08,
+            compare: Some(0x85),
+        };
+        assert_eq!(code.apply(0x8000, 0x85), 0x08);
+        assert_eq!(code.apply(0x8000, 0x94), 0x94);
+        assert_eq!(code.apply(0x8001, 0x85), 0x85);
+    }
+}
@@ -0,0 +1,210 @@
+use crate::cheat::Cheat;
+use crate::cpu::Cpu;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DataSize {
+    Byte,
+    Word,
+}
+
+impl DataSize {
+    fn bytes(&self) -> usize {
+        match self {
+            DataSize::Byte => 1,
+            DataSize::Word => 2,
+        }
+    }
+
+    // words are little-endian, like everything else the 6502 stores
+    fn read(&self, ram: &[u8], addr: u16) -> u16 {
+        let addr = addr as usize;
+        match self {
+            DataSize::Byte => ram[addr] as u16,
+            DataSize::Word => u16::from_le_bytes([ram[addr], ram[addr + 1]]),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Filter {
+    EqualToPrevious,
+    ChangedFromPrevious,
+    GreaterThanPrevious,
+    LessThanPrevious,
+    EqualTo(u16),
+}
+
+impl Filter {
+    fn matches(&self, previous: u16, current: u16) -> bool {
+        match self {
+            Filter::EqualToPrevious => current == previous,
+            Filter::ChangedFromPrevious => current != previous,
+            Filter::GreaterThanPrevious => current > previous,
+            Filter::LessThanPrevious => current < previous,
+            Filter::EqualTo(value) => current == *value,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    pub address: u16,
+    pub previous: u16,
+    pub value: u16,
+}
+
+pub struct RamSearch {
+    size: DataSize,
+    snapshot: Vec<u8>,
+    candidates: Vec<u16>,
+}
+
+impl RamSearch {
+    pub fn new(cpu: &Cpu, size: DataSize) -> Self {
+        let snapshot = cpu.ram().to_vec();
+        let last = snapshot.len() - size.bytes();
+        let candidates = (0..=last as u16).collect();
+
+        Self {
+            size,
+            snapshot,
+            candidates,
+        }
+    }
+
+    pub fn size(&self) -> DataSize {
+        self.size
+    }
+
+    pub fn len(&self) -> usize {
+        self.candidates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.candidates.is_empty()
+    }
+
+    // keeps only the candidates matching `filter`, then makes the current RAM the new "previous"
+    pub fn filter(&mut self, cpu: &Cpu, filter: Filter) {
+        let ram = cpu.ram();
+        let size = self.size;
+        let snapshot = &self.snapshot;
+
+        self.candidates
+            .retain(|&addr| filter.matches(size.read(snapshot, addr), size.read(ram, addr)));
+        self.snapshot.copy_from_slice(ram);
+    }
+
+    pub fn candidates(&self, cpu: &Cpu) -> Vec<Candidate> {
+        let ram = cpu.ram();
+        self.candidates
+            .iter()
+            .map(|&address| Candidate {
+                address,
+                previous: self.size.read(&self.snapshot, address),
+                value: self.size.read(ram, address),
+            })
+            .collect()
+    }
+
+    pub fn freeze_cheats(&self, address: u16, value: u16) -> Vec<Cheat> {
+        value.to_le_bytes()[..self.size.bytes()]
+            .iter()
+            .enumerate()
+            .map(|(offset, &value)| Cheat::Freeze {
+                address: address.wrapping_add(offset as u16),
+                value,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(cpu: &mut Cpu, program: Vec<u8>) {
+        cpu.load(program);
+        cpu.reset();
+        cpu.run();
+    }
+
+    #[test]
+    fn test_narrow_down_decreasing_counter() {
+        let mut cpu = Cpu::new();
+        run(&mut cpu, vec![0xA9, 0x03, 0x85, 0x42, 0x85, 0x43, 0x00]);
+
+        let mut search = RamSearch::new(&cpu, DataSize::Byte);
+        search.filter(&cpu, Filter::EqualTo(3));
+        assert_eq!(search.len(), 2);
+
+        run(&mut cpu, vec![0xC6, 0x42, 0x00]);
+        search.filter(&cpu, Filter::LessThanPrevious);
+
+        let candidates = search.candidates(&cpu);
+        assert_eq!(
+            candidates,
+            vec![Candidate {
+                address: 0x42,
+                previous: 2,
+                value: 2,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_word_search() {
+        let mut cpu = Cpu::new();
+        run(
+            &mut cpu,
+            vec![0xA9, 0x34, 0x85, 0x10, 0xA9, 0x12, 0x85, 0x11, 0x00],
+        );
+
+        let mut search = RamSearch::new(&cpu, DataSize::Word);
+        assert_eq!(search.len(), 0x7FF);
+        search.filter(&cpu, Filter::EqualTo(0x1234));
+        assert_eq!(search.candidates(&cpu)[0].address, 0x10);
+
+        assert_eq!(
+            search.freeze_cheats(0x10, 0x1234),
+            vec![
+                Cheat::Freeze {
+                    address: 0x10,
+                    value: 0x34,
+                },
+                Cheat::Freeze {
+                    address: 0x11,
+                    value: 0x12,
+                },
+            ]
+        );
+
+        // the last word in RAM starts at $07FE and ends on its top byte
+        cpu.mem_write(0x07FE, 0x78);
+        cpu.mem_write(0x07FF, 0x56);
+        let mut search = RamSearch::new(&cpu, DataSize::Word);
+        search.filter(&cpu, Filter::EqualTo(0x5678));
+        let candidate = search.candidates(&cpu)[0];
+        assert_eq!(candidate.address, 0x07FE);
+        assert_eq!(
+            search.freeze_cheats(candidate.address, 0x1234)[1],
+            Cheat::Freeze {
+                address: 0x07FF,
+                value: 0x12,
+            }
+        );
+    }
+
+    #[test]
+    fn test_changed_from_previous() {
+        let mut cpu = Cpu::new();
+        let mut search = RamSearch::new(&cpu, DataSize::Byte);
+
+        run(&mut cpu, vec![0xE6, 0x20, 0x00]);
+        search.filter(&cpu, Filter::ChangedFromPrevious);
+        assert_eq!(search.len(), 1);
+
+        search.filter(&cpu, Filter::ChangedFromPrevious);
+        assert!(search.is_empty());
+    }
+}
@@ -0,0 +1,188 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cartridge::detect_region;
+use crate::cpu::RamInit;
+use crate::pacing::Region;
+use crate::rewind::Rewind;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub ram_init: RamInit,
+    // forces a region instead of detecting one from the ROM
+    pub region: Option<Region>,
+    pub rewind: RewindConfig,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RewindConfig {
+    pub enabled: bool,
+    pub capacity: usize,
+    pub interval: usize,
+    pub delta_compression: bool,
+}
+
+impl Default for RewindConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            capacity: 64,
+            interval: 1000,
+            delta_compression: true,
+        }
+    }
+}
+
+impl RewindConfig {
+    // Rewind::new asserts on these, and they come from a file the user edits
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.capacity == 0 {
+            return Err(ConfigError::Invalid("rewind.capacity must be at least 1"));
+        }
+        if self.interval == 0 {
+            return Err(ConfigError::Invalid("rewind.interval must be at least 1"));
+        }
+        Ok(())
+    }
+
+    // checked again here since a RewindConfig can be put together in code as well as loaded
+    pub fn build(&self) -> Result<Option<Rewind>, ConfigError> {
+        self.validate()?;
+        Ok(self.enabled.then(|| {
+            Rewind::new(self.capacity, self.interval).with_delta_compression(self.delta_compression)
+        }))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("could not access config file: {0}")]
+    Io(#[source] io::Error),
+    #[error("invalid config: {0}")]
+    Parse(#[source] toml::de::Error),
+    #[error("could not serialize config: {0}")]
+    Serialize(#[source] toml::ser::Error),
+    #[error("invalid config: {0}")]
+    Invalid(&'static str),
+}
+
+impl Config {
+    pub fn from_toml(source: &str) -> Result<Self, ConfigError> {
+        let config: Self = toml::from_str(source).map_err(ConfigError::Parse)?;
+        config.rewind.validate()?;
+        Ok(config)
+    }
+
+    pub fn to_toml(&self) -> Result<String, ConfigError> {
+        toml::to_string_pretty(self).map_err(ConfigError::Serialize)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        log::debug!("loading config from {}", path.as_ref().display());
+        let source = fs::read_to_string(path).map_err(ConfigError::Io)?;
+        Self::from_toml(&source)
+    }
+
+    // the override if there is one, then whatever detect_region finds, then NTSC
+    pub fn region_for(&self, rom: &[u8], file_name: Option<&str>) -> Region {
+        self.region
+            .or_else(|| detect_region(rom, file_name))
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ConfigError> {
+        fs::write(path, self.to_toml()?).map_err(ConfigError::Io)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_fields_use_defaults() {
+        assert_eq!(Config::from_toml("").unwrap(), Config::default());
+
+        let config = Config::from_toml("[rewind]\nenabled = true").unwrap();
+        assert!(config.rewind.enabled);
+        assert_eq!(config.rewind.capacity, 64);
+    }
+
+    #[test]
+    fn test_zero_rewind_sizes_are_rejected() {
+        for source in [
+            "[rewind]\nenabled = true\ncapacity = 0",
+            "[rewind]\nenabled = true\ninterval = 0",
+        ] {
+            assert!(matches!(
+                Config::from_toml(source),
+                Err(ConfigError::Invalid(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_ram_init_variants() {
+        let config = Config::from_toml("ram_init = \"ones\"").unwrap();
+        assert_eq!(config.ram_init, RamInit::Ones);
+
+        let config = Config::from_toml("ram_init = { random = 42 }").unwrap();
+        assert_eq!(config.ram_init, RamInit::Random(42));
+
+        assert!(Config::from_toml("ram_init = \"sometimes\"").is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let config = Config {
+            ram_init: RamInit::Random(7),
+            region: Some(Region::Pal),
+            rewind: RewindConfig {
+                enabled: true,
+                capacity: 10,
+                interval: 5,
+                delta_compression: false,
+            },
+        };
+        let source = config.to_toml().unwrap();
+        assert_eq!(Config::from_toml(&source).unwrap(), config);
+    }
+
+    #[test]
+    fn test_region_override() {
+        let config = Config::from_toml("region = \"dendy\"").unwrap();
+        assert_eq!(
+            config.region_for(&[], Some("Game (USA).nes")),
+            Region::Dendy
+        );
+
+        let config = Config::default();
+        assert_eq!(
+            config.region_for(&[], Some("Game (Europe).nes")),
+            Region::Pal
+        );
+        assert_eq!(config.region_for(&[], None), Region::Ntsc);
+    }
+
+    #[test]
+    fn test_rewind_is_built_only_when_enabled() {
+        assert!(RewindConfig::default().build().unwrap().is_none());
+
+        let rewind = RewindConfig {
+            enabled: true,
+            ..RewindConfig::default()
+        };
+        assert!(rewind.build().unwrap().unwrap().is_empty());
+
+        let rewind = RewindConfig {
+            capacity: 0,
+            ..rewind
+        };
+        assert!(matches!(rewind.build(), Err(ConfigError::Invalid(_))));
+    }
+}
@@ -1,12 +1,31 @@
+mod block_cache;
 pub mod instructions;
-
+pub mod ram_init;
+
+use crate::cheat::CheatManager;
+use crate::debugger::{Debugger, UninitializedRead, WatchpointHit};
+use crate::disassembler::Disassembled;
+use crate::events::{Event, EventBus};
+use crate::hash::Fnv1a;
+use crate::hooks::Hooks;
+use crate::system_memory::SystemMemory;
 use bitflags::bitflags;
-use instructions::INSTRUCTION_MAP;
+use block_cache::BlockCache;
+use instructions::INSTRUCTION_TABLE;
+pub use ram_init::RamInit;
 
-const MEMORY_SIZE: usize = 2048;
+// the whole 16-bit address space
+const MEMORY_SIZE: usize = 0x10000;
+pub(crate) const RAM_SIZE: usize = 0x0800;
 
 const PROGRAM_START_ADDRESS: usize = 0x8000;
-const PROGRAM_COUNTER_RESET_ADDRESS: u16 = 0xFFFC;
+pub(crate) const PROGRAM_COUNTER_RESET_ADDRESS: u16 = 0xFFFC;
+// what load() can take without running into the vectors
+pub(crate) const MAX_PROGRAM_SIZE: usize =
+    PROGRAM_COUNTER_RESET_ADDRESS as usize - PROGRAM_START_ADDRESS;
+const IRQ_BRK_VECTOR_ADDRESS: u16 = 0xFFFE;
+// the stack grows down through $01FF-$0100, SP pointing at the next free slot
+pub(crate) const STACK_PAGE: u16 = 0x0100;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum AddressingMode {
@@ -25,6 +44,41 @@ pub enum AddressingMode {
     IndirectY,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Status {
+    Running,
+    Halted,
+    Breakpoint(u16),
+    Watchpoint(WatchpointHit),
+    // PC is left on the opcode
+    UnknownOpcode(u8),
+    // strict mode with StrictAction::Break saw a read of RAM that was never written
+    UninitializedRead(UninitializedRead),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Registers {
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub status: u8,
+    pub sp: u8,
+    pub pc: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CpuState {
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub status: u8,
+    pub sp: u8,
+    pub pc: u16,
+    pub cycles: u64,
+
+    pub memory: Box<[u8; MEMORY_SIZE]>,
+}
+
 pub struct Cpu {
     a: u8,
     x: u8,
@@ -32,8 +86,16 @@ pub struct Cpu {
     status: StatusFlags,
     sp: u8,
     pc: u16,
+    cycles: u64,
+
+    memory: [u8; MEMORY_SIZE],
+    halt_on_brk: bool,
+    block_cache: Option<BlockCache>,
 
-    memory: [u8; 0xFFFF],
+    cheats: CheatManager,
+    hooks: Hooks,
+    events: EventBus,
+    debugger: Debugger,
 }
 
 bitflags! {
@@ -43,13 +105,19 @@ bitflags! {
         const Zero             = 0b0000_0010;
         const InterruptDisable = 0b0000_0100;
         const Decimal          = 0b0000_1000;
-        //
-        //
+        const Break            = 0b0001_0000;
+        const Unused           = 0b0010_0000;
         const Overflow         = 0b0100_0000;
         const Negative         = 0b1000_0000;
     }
 }
 
+impl Default for Cpu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Cpu {
     pub fn new() -> Self {
         Self {
@@ -59,11 +127,25 @@ impl Cpu {
             status: StatusFlags::from_bits_retain(0b0010_0100),
             sp: 0,
             pc: 0,
+            cycles: 0,
+
+            memory: [0; MEMORY_SIZE],
+            halt_on_brk: true,
+            block_cache: None,
 
-            memory: [0; 0xFFFF],
+            cheats: CheatManager::new(),
+            hooks: Hooks::new(),
+            events: EventBus::new(),
+            debugger: Debugger::new(),
         }
     }
 
+    pub fn with_ram_init(ram_init: RamInit) -> Self {
+        let mut cpu = Self::new();
+        ram_init.fill(&mut cpu.memory[..RAM_SIZE]);
+        cpu
+    }
+
     pub fn load_and_run(&mut self, program: Vec<u8>) {
         self.load(program);
         self.reset();
@@ -73,6 +155,7 @@ impl Cpu {
     pub fn load(&mut self, program: Vec<u8>) {
         self.memory[PROGRAM_START_ADDRESS..(PROGRAM_START_ADDRESS + program.len())]
             .copy_from_slice(&program);
+        self.code_replaced();
         self.mem_write_u16(PROGRAM_COUNTER_RESET_ADDRESS, PROGRAM_START_ADDRESS as u16);
     }
 
@@ -82,74 +165,387 @@ impl Cpu {
         self.y = 0;
         self.status |= StatusFlags::from_bits_retain(0b0010_0100);
 
-        // TODO: self.stack -= 3;
+        // reset goes through the motions of an interrupt without the writes
+        self.sp = self.sp.wrapping_sub(3);
 
         self.pc = self.mem_read_u16(PROGRAM_COUNTER_RESET_ADDRESS);
+        self.cycles += 7;
+        log::debug!("reset, starting at ${:04X}", self.pc);
+        self.debugger.call_stack_mut().clear();
+        self.debugger.clear_history();
+
+        self.events.publish(Event::Reset);
+    }
+
+    pub fn run(&mut self) -> Status {
+        self.run_with_callback(|_| {})
     }
 
-    pub fn run(&mut self) {
+    pub fn run_with_callback<F>(&mut self, mut callback: F) -> Status
+    where
+        F: FnMut(&mut Cpu),
+    {
         loop {
-            let opcode = self.mem_read(self.pc);
-            self.pc += 1;
+            callback(self);
 
-            let instruction = INSTRUCTION_MAP.get(&opcode).unwrap();
+            let status = self.step();
+            if status != Status::Running {
+                return status;
+            }
+        }
+    }
 
-            match opcode {
-                // Access
-                0xA9 | 0xA5 | 0xB5 | 0xAD | 0xBD | 0xB9 | 0xA1 | 0xB1 => {
-                    self.lda(&instruction.addressing_mode)
-                }
-                0x85 | 0x95 | 0x8D | 0x9D | 0x99 | 0x81 | 0x91 => {
-                    self.sta(&instruction.addressing_mode)
-                }
-                0xA2 | 0xA6 | 0xB6 | 0xAE | 0xBE => self.ldx(&instruction.addressing_mode),
-                0x86 | 0x96 | 0x8E => self.stx(&instruction.addressing_mode),
-                0xA0 | 0xA4 | 0xB4 | 0xAC | 0xBC => self.ldy(&instruction.addressing_mode),
-                0x84 | 0x94 | 0x8C => self.sty(&instruction.addressing_mode),
-
-                // Transfer
-                0xAA => self.tax(),
-                0x8A => self.txa(),
-                0xA8 => self.tay(),
-                0x98 => self.tya(),
-
-                // Arithmetic
-                0x69 | 0x65 | 0x75 | 0x6D | 0x7D | 0x79 | 0x61 | 0x71 => {
-                    self.adc(&instruction.addressing_mode);
-                }
-                0xE9 | 0xE5 | 0xF5 | 0xED | 0xFD | 0xF9 | 0xE1 | 0xF1 => {
-                    self.sbc(&instruction.addressing_mode);
-                }
-                0xE6 | 0xF6 | 0xEE | 0xFE => self.inc(&instruction.addressing_mode),
-                0xC6 | 0xD6 | 0xCE | 0xDE => self.dec(&instruction.addressing_mode),
-                0xCA => self.dex(),
-                0xE8 => self.inx(),
-                0xC8 => self.iny(),
-                0x88 => self.dey(),
-
-                // Shift
-                0x0A | 0x06 | 0x16 | 0x0E | 0x1E => self.asl(&instruction.addressing_mode),
-                0x4A | 0x46 | 0x56 | 0x4E | 0x5E => self.lsr(&instruction.addressing_mode),
-                0x2A | 0x26 | 0x36 | 0x2E | 0x3E => self.rol(&instruction.addressing_mode),
-                0x6A | 0x66 | 0x76 | 0x6E | 0x7E => self.ror(&instruction.addressing_mode),
-
-                // Bitwise
-                0x29 | 0x25 | 0x35 | 0x2D | 0x3D | 0x39 | 0x21 | 0x31 => {
-                    self.and(&instruction.addressing_mode)
-                }
-                0x09 | 0x05 | 0x15 | 0x0D | 0x1D | 0x19 | 0x01 | 0x11 => {
-                    self.ora(&instruction.addressing_mode)
-                }
-                0x49 | 0x45 | 0x55 | 0x4D | 0x5D | 0x59 | 0x41 | 0x51 => {
-                    self.eor(&instruction.addressing_mode)
-                }
-                0x24 | 0x2C => self.bit(&instruction.addressing_mode),
+    pub fn step(&mut self) -> Status {
+        // conditions need to see the whole machine, so the debugger is lifted out while they run
+        let mut debugger = std::mem::take(&mut self.debugger);
+        let paused = debugger.check_breakpoint(self.pc, |condition| condition.evaluate(self));
+        self.debugger = debugger;
+        if paused {
+            return Status::Breakpoint(self.pc);
+        }
+        self.debugger
+            .begin_instruction(self.registers(), self.cycles);
+
+        if self.hooks.has_execute() {
+            let mut registers = self.registers();
+            self.hooks.execute(self.pc, &mut registers);
+            self.set_registers(registers);
+        }
 
-                // Jump
-                0x00 => return,
-                _ => panic!("opcode '{:X}' not recognised", opcode),
+        let instruction_pc = self.pc;
+        let sp_before = self.sp;
+        let cached = match &mut self.block_cache {
+            // cheats and read hooks can change what an opcode reads as
+            Some(cache) if self.cheats.is_empty() && !self.hooks.has_read() => {
+                cache.next(self.pc, &self.memory)
+            }
+            _ => None,
+        };
+        let instruction = match cached {
+            Some(instruction) => {
+                // skipping the fetch mustn't hide it from watchpoints, the heatmap or strict mode
+                self.debugger.check_read(self.pc, instruction.opcode);
+                instruction
+            }
+            None => {
+                let opcode = self.mem_read(self.pc);
+                let Some(instruction) = INSTRUCTION_TABLE[opcode as usize] else {
+                    log::warn!("unknown opcode ${:02X} at ${:04X}", opcode, self.pc);
+                    return Status::UnknownOpcode(opcode);
+                };
+                instruction
+            }
+        };
+        let opcode = instruction.opcode;
+        self.pc = self.pc.wrapping_add(1);
+        self.cycles += instruction.cycles as u64;
+
+        // set by instructions that leave PC where execution continues
+        let mut jumped = false;
+        match opcode {
+            // Access
+            0xA9 | 0xA5 | 0xB5 | 0xAD | 0xBD | 0xB9 | 0xA1 | 0xB1 => {
+                self.lda(&instruction.addressing_mode)
+            }
+            0x85 | 0x95 | 0x8D | 0x9D | 0x99 | 0x81 | 0x91 => {
+                self.sta(&instruction.addressing_mode)
+            }
+            0xA2 | 0xA6 | 0xB6 | 0xAE | 0xBE => self.ldx(&instruction.addressing_mode),
+            0x86 | 0x96 | 0x8E => self.stx(&instruction.addressing_mode),
+            0xA0 | 0xA4 | 0xB4 | 0xAC | 0xBC => self.ldy(&instruction.addressing_mode),
+            0x84 | 0x94 | 0x8C => self.sty(&instruction.addressing_mode),
+
+            // Transfer
+            0xAA => self.tax(),
+            0x8A => self.txa(),
+            0xA8 => self.tay(),
+            0x98 => self.tya(),
+            0xBA => self.tsx(),
+            0x9A => self.txs(),
+
+            // Arithmetic
+            0x69 | 0x65 | 0x75 | 0x6D | 0x7D | 0x79 | 0x61 | 0x71 => {
+                self.adc(&instruction.addressing_mode);
+            }
+            0xE9 | 0xE5 | 0xF5 | 0xED | 0xFD | 0xF9 | 0xE1 | 0xF1 => {
+                self.sbc(&instruction.addressing_mode);
+            }
+            0xE6 | 0xF6 | 0xEE | 0xFE => self.inc(&instruction.addressing_mode),
+            0xC6 | 0xD6 | 0xCE | 0xDE => self.dec(&instruction.addressing_mode),
+            0xCA => self.dex(),
+            0xE8 => self.inx(),
+            0xC8 => self.iny(),
+            0x88 => self.dey(),
+
+            // Shift
+            0x0A | 0x06 | 0x16 | 0x0E | 0x1E => self.asl(&instruction.addressing_mode),
+            0x4A | 0x46 | 0x56 | 0x4E | 0x5E => self.lsr(&instruction.addressing_mode),
+            0x2A | 0x26 | 0x36 | 0x2E | 0x3E => self.rol(&instruction.addressing_mode),
+            0x6A | 0x66 | 0x76 | 0x6E | 0x7E => self.ror(&instruction.addressing_mode),
+
+            // Bitwise
+            0x29 | 0x25 | 0x35 | 0x2D | 0x3D | 0x39 | 0x21 | 0x31 => {
+                self.and(&instruction.addressing_mode)
+            }
+            0x09 | 0x05 | 0x15 | 0x0D | 0x1D | 0x19 | 0x01 | 0x11 => {
+                self.ora(&instruction.addressing_mode)
+            }
+            0x49 | 0x45 | 0x55 | 0x4D | 0x5D | 0x59 | 0x41 | 0x51 => {
+                self.eor(&instruction.addressing_mode)
             }
-            self.pc += (instruction.bytes - 1) as u16;
+            0x24 | 0x2C => self.bit(&instruction.addressing_mode),
+
+            // Compare
+            0xC9 | 0xC5 | 0xD5 | 0xCD | 0xDD | 0xD9 | 0xC1 | 0xD1 => {
+                self.compare(&instruction.addressing_mode, self.a)
+            }
+            0xE0 | 0xE4 | 0xEC => self.compare(&instruction.addressing_mode, self.x),
+            0xC0 | 0xC4 | 0xCC => self.compare(&instruction.addressing_mode, self.y),
+
+            // Flags
+            0x18 => self.status.remove(StatusFlags::Carry),
+            0x38 => self.status.insert(StatusFlags::Carry),
+            0x58 => self.status.remove(StatusFlags::InterruptDisable),
+            0x78 => self.status.insert(StatusFlags::InterruptDisable),
+            0xD8 => self.status.remove(StatusFlags::Decimal),
+            0xF8 => self.status.insert(StatusFlags::Decimal),
+            0xB8 => self.status.remove(StatusFlags::Overflow),
+
+            // Stack
+            0x48 => self.pha(),
+            0x68 => self.pla(),
+            0x08 => self.php(),
+            0x28 => self.plp(),
+
+            // Branch
+            0x90 => jumped = self.branch(!self.status.contains(StatusFlags::Carry)),
+            0xB0 => jumped = self.branch(self.status.contains(StatusFlags::Carry)),
+            0xF0 => jumped = self.branch(self.status.contains(StatusFlags::Zero)),
+            0xD0 => jumped = self.branch(!self.status.contains(StatusFlags::Zero)),
+            0x30 => jumped = self.branch(self.status.contains(StatusFlags::Negative)),
+            0x10 => jumped = self.branch(!self.status.contains(StatusFlags::Negative)),
+            0x50 => jumped = self.branch(!self.status.contains(StatusFlags::Overflow)),
+            0x70 => jumped = self.branch(self.status.contains(StatusFlags::Overflow)),
+
+            // Jump
+            0x4C | 0x6C => jumped = self.jmp(&instruction.addressing_mode),
+            0x20 => jumped = self.jsr(),
+            0x60 => jumped = self.rts(),
+            0x00 if self.halt_on_brk => return Status::Halted,
+            0x00 => jumped = self.brk(instruction_pc),
+            0x40 => jumped = self.rti(),
+
+            // Other
+            0xEA => {}
+
+            // Unofficial
+            0x1A | 0x3A | 0x5A | 0x7A | 0xDA | 0xFA | 0x80 | 0x82 | 0x89 | 0xC2 | 0xE2 => {}
+            0x04 | 0x44 | 0x64 | 0x14 | 0x34 | 0x54 | 0x74 | 0xD4 | 0xF4 | 0x0C | 0x1C | 0x3C
+            | 0x5C | 0x7C | 0xDC | 0xFC => self.nop_read(&instruction.addressing_mode),
+            _ => panic!("opcode '{:X}' not recognised", opcode),
+        }
+        if !jumped {
+            self.pc = self.pc.wrapping_add((instruction.bytes - 1) as u16);
+        }
+
+        self.debugger
+            .call_stack_mut()
+            .update(opcode, instruction_pc, self.pc, sp_before, self.sp);
+
+        let mut debugger = std::mem::take(&mut self.debugger);
+        let hit = debugger.take_watchpoint_hit(|condition| condition.evaluate(self));
+        self.debugger = debugger;
+        if let Some(hit) = hit {
+            return Status::Watchpoint(hit);
+        }
+        match self.debugger.take_strict_break() {
+            Some(read) => Status::UninitializedRead(read),
+            None => Status::Running,
+        }
+    }
+
+    // undoes the last instruction recorded by the debugger's history, see
+    // Debugger::set_history_capacity; false once there is nothing left to undo
+    pub fn step_back(&mut self) -> bool {
+        let Some(entry) = self.debugger.pop_history() else {
+            return false;
+        };
+
+        for &(addr, old) in entry.writes.iter().rev() {
+            self.memory[addr as usize] = old;
+            self.code_modified(addr);
+        }
+        self.set_registers(entry.registers);
+        self.cycles = entry.cycles;
+        *self.debugger.call_stack_mut() = entry.call_stack;
+        true
+    }
+
+    pub fn save_state(&self) -> CpuState {
+        log::trace!("saving state at cycle {}", self.cycles);
+        CpuState {
+            a: self.a,
+            x: self.x,
+            y: self.y,
+            status: self.status.bits(),
+            sp: self.sp,
+            pc: self.pc,
+            cycles: self.cycles,
+
+            memory: Box::new(self.memory),
+        }
+    }
+
+    pub fn load_state(&mut self, state: &CpuState) {
+        log::debug!(
+            "loading state from cycle {} (was at cycle {})",
+            state.cycles,
+            self.cycles
+        );
+        self.a = state.a;
+        self.x = state.x;
+        self.y = state.y;
+        self.status = StatusFlags::from_bits_retain(state.status);
+        self.sp = state.sp;
+        self.pc = state.pc;
+        self.cycles = state.cycles;
+
+        self.memory = *state.memory;
+        self.code_replaced();
+        self.debugger.call_stack_mut().clear();
+        self.debugger.clear_history();
+
+        self.events.publish(Event::StateLoaded);
+    }
+
+    // covers everything that affects emulation; cheats, hooks and subscribers are host-side
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = Fnv1a::new();
+        hasher.write(&[self.a, self.x, self.y, self.status.bits(), self.sp]);
+        hasher.write(&self.pc.to_le_bytes());
+        hasher.write(&self.cycles.to_le_bytes());
+        hasher.write(&self.memory);
+        hasher.finish()
+    }
+
+    pub fn registers(&self) -> Registers {
+        Registers {
+            a: self.a,
+            x: self.x,
+            y: self.y,
+            status: self.status.bits(),
+            sp: self.sp,
+            pc: self.pc,
+        }
+    }
+
+    pub fn set_registers(&mut self, registers: Registers) {
+        self.a = registers.a;
+        self.x = registers.x;
+        self.y = registers.y;
+        self.status = StatusFlags::from_bits_retain(registers.status);
+        self.sp = registers.sp;
+        self.pc = registers.pc;
+    }
+
+    // programs given to load() end in BRK, so by default it stops the CPU with Status::Halted;
+    // turned off, BRK runs the interrupt sequence through $FFFE like the hardware
+    pub fn halt_on_brk(&self) -> bool {
+        self.halt_on_brk
+    }
+
+    pub fn set_halt_on_brk(&mut self, halt: bool) {
+        self.halt_on_brk = halt;
+    }
+
+    // decodes straight-line runs of code once and reuses them until they are written over, for
+    // hot loops; execution, cycle counts and everything the debugger sees stay the same
+    pub fn block_cache_enabled(&self) -> bool {
+        self.block_cache.is_some()
+    }
+
+    pub fn set_block_cache(&mut self, enabled: bool) {
+        self.block_cache = enabled.then(BlockCache::new);
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    pub fn ram(&self) -> &[u8] {
+        &self.memory[..RAM_SIZE]
+    }
+
+    pub fn system_memory(&self) -> SystemMemory<'_> {
+        SystemMemory::new(self)
+    }
+
+    pub(crate) fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    pub fn cheats(&self) -> &CheatManager {
+        &self.cheats
+    }
+
+    pub fn cheats_mut(&mut self) -> &mut CheatManager {
+        &mut self.cheats
+    }
+
+    pub fn hooks(&self) -> &Hooks {
+        &self.hooks
+    }
+
+    pub fn hooks_mut(&mut self) -> &mut Hooks {
+        &mut self.hooks
+    }
+
+    pub fn debugger(&self) -> &Debugger {
+        &self.debugger
+    }
+
+    pub fn debugger_mut(&mut self) -> &mut Debugger {
+        &mut self.debugger
+    }
+
+    pub fn events_mut(&mut self) -> &mut EventBus {
+        &mut self.events
+    }
+
+    pub fn disassemble_at(&self, addr: u16) -> Disassembled {
+        let bytes: Vec<u8> = (0..3)
+            .map(|offset| self.mem_peek(addr.wrapping_add(offset)))
+            .collect();
+        Disassembled::decode(addr, &bytes)
+    }
+
+    // meant to be called once per frame so frozen values win over the program
+    pub fn apply_cheats(&mut self) {
+        for (addr, value) in self.cheats.take_pending_writes() {
+            self.memory[addr as usize] = value;
+            self.code_modified(addr);
+        }
+    }
+
+    // copies an image straight into memory, wrapping at $FFFF; loading a ROM isn't something
+    // hooks, cheats or the debugger should see
+    pub(crate) fn write_image(&mut self, addr: u16, image: &[u8]) {
+        let start = addr as usize;
+        let (head, tail) = image.split_at(image.len().min(MEMORY_SIZE - start));
+        self.memory[start..start + head.len()].copy_from_slice(head);
+        self.memory[..tail.len()].copy_from_slice(tail);
+        self.code_replaced();
+    }
+
+    fn code_modified(&mut self, addr: u16) {
+        if let Some(cache) = &mut self.block_cache {
+            cache.invalidate(addr);
+        }
+    }
+
+    fn code_replaced(&mut self) {
+        if let Some(cache) = &mut self.block_cache {
+            cache.clear();
         }
     }
 
@@ -201,6 +597,16 @@ impl Cpu {
         self.update_zero_and_negative_flags(self.x);
     }
 
+    fn tsx(&mut self) {
+        self.x = self.sp;
+        self.update_zero_and_negative_flags(self.x);
+    }
+
+    // the only transfer that leaves the flags alone
+    fn txs(&mut self) {
+        self.sp = self.x;
+    }
+
     fn txa(&mut self) {
         self.a = self.x;
         self.update_zero_and_negative_flags(self.a);
@@ -391,14 +797,110 @@ impl Cpu {
         let addr = self.get_address(mode);
         let value = self.mem_read(addr);
 
-        let result = self.a & value;
-
-        self.update_zero_and_negative_flags(result);
+        // only Z depends on A; N and V are copied straight from the operand
+        self.update_zero_flag(self.a & value);
+        self.update_negative_flag(value);
         self.set_overflow_flag((value & 0x40) != 0);
     }
 
+    // Compare
+
+    // CMP, CPX and CPY: flags as for register - value, with carry meaning no borrow
+    fn compare(&mut self, mode: &AddressingMode, register: u8) {
+        let addr = self.get_address(mode);
+        let value = self.mem_read(addr);
+
+        self.set_carry_flag(register >= value);
+        self.update_zero_and_negative_flags(register.wrapping_sub(value));
+    }
+
+    // Stack
+
+    fn pha(&mut self) {
+        self.push(self.a);
+    }
+
+    fn pla(&mut self) {
+        self.a = self.pull();
+        self.update_zero_and_negative_flags(self.a);
+    }
+
+    // B only exists in the pushed copy: set by PHP and BRK, clear for interrupts
+    fn php(&mut self) {
+        self.push((self.status | StatusFlags::Break | StatusFlags::Unused).bits());
+    }
+
+    fn plp(&mut self) {
+        self.status = StatusFlags::from_bits_retain(self.pull());
+        self.status.remove(StatusFlags::Break);
+        self.status.insert(StatusFlags::Unused);
+    }
+
+    // Branch
+
+    // true if taken, leaving PC at the target
+    fn branch(&mut self, condition: bool) -> bool {
+        if !condition {
+            return false;
+        }
+
+        let target = self.get_address(&AddressingMode::Relative);
+        let next = self.pc.wrapping_add(1);
+        self.cycles += if next & 0xFF00 == target & 0xFF00 {
+            1
+        } else {
+            2
+        };
+        self.pc = target;
+        true
+    }
+
+    // Jump
+
+    fn jmp(&mut self, mode: &AddressingMode) -> bool {
+        self.pc = self.get_address(mode);
+        true
+    }
+
+    // pushes the address of the operand's last byte, which RTS steps past
+    fn jsr(&mut self) -> bool {
+        let target = self.get_address(&AddressingMode::Absolute);
+        self.push_u16(self.pc.wrapping_add(1));
+        self.pc = target;
+        true
+    }
+
+    fn rts(&mut self) -> bool {
+        self.pc = self.pull_u16().wrapping_add(1);
+        true
+    }
+
+    // skips the padding byte after the opcode, so the handler returns past it
+    fn brk(&mut self, pc: u16) -> bool {
+        self.push_u16(self.pc.wrapping_add(1));
+        self.php();
+        self.status.insert(StatusFlags::InterruptDisable);
+        self.pc = self.mem_read_u16(IRQ_BRK_VECTOR_ADDRESS);
+        self.debugger
+            .call_stack_mut()
+            .enter_interrupt(pc, self.pc, self.sp);
+        true
+    }
+
+    fn rti(&mut self) -> bool {
+        self.plp();
+        self.pc = self.pull_u16();
+        true
+    }
+
     // Other
 
+    // the read still happens, which matters for registers with read side effects
+    fn nop_read(&mut self, mode: &AddressingMode) {
+        let addr = self.get_address(mode);
+        self.mem_read(addr);
+    }
+
     fn add_to_accumulator(&mut self, value: u8) {
         let (result, overflow) = {
             let (res, ovf1) = self.a.overflowing_add(value);
@@ -415,26 +917,62 @@ impl Cpu {
         self.update_zero_and_negative_flags(self.a);
     }
 
-    fn mem_read(&self, addr: u16) -> u8 {
-        self.memory[addr as usize]
+    // reads what the CPU would see, without firing hooks
+    pub fn mem_peek(&self, addr: u16) -> u8 {
+        let value = self.memory[addr as usize];
+        self.cheats.apply_read(addr, value)
+    }
+
+    pub fn mem_read(&mut self, addr: u16) -> u8 {
+        let value = self.memory[addr as usize];
+        let value = self.cheats.apply_read(addr, value);
+        let value = self.hooks.read(addr, value);
+        self.debugger.check_read(addr, value);
+        value
     }
 
-    fn mem_write(&mut self, addr: u16, data: u8) {
+    pub fn mem_write(&mut self, addr: u16, data: u8) {
+        let data = self.hooks.write(addr, data);
+        let data = self.cheats.apply_write(addr, data);
+        self.debugger
+            .check_write(addr, self.memory[addr as usize], data);
         self.memory[addr as usize] = data;
+        self.code_modified(addr);
     }
 
-    fn mem_read_u16(&self, addr: u16) -> u16 {
+    pub fn mem_read_u16(&mut self, addr: u16) -> u16 {
         let lo = self.mem_read(addr) as u16;
-        let hi = self.mem_read(addr + 1) as u16;
+        let hi = self.mem_read(addr.wrapping_add(1)) as u16;
 
         (hi << 8) | lo
     }
 
-    fn mem_write_u16(&mut self, addr: u16, data: u16) {
+    pub fn mem_write_u16(&mut self, addr: u16, data: u16) {
         let hi = (data >> 8) as u8;
         let lo = (data & 0xFF) as u8;
         self.mem_write(addr, lo);
-        self.mem_write(addr + 1, hi);
+        self.mem_write(addr.wrapping_add(1), hi);
+    }
+
+    fn push(&mut self, value: u8) {
+        self.mem_write(STACK_PAGE | self.sp as u16, value);
+        self.sp = self.sp.wrapping_sub(1);
+    }
+
+    fn pull(&mut self) -> u8 {
+        self.sp = self.sp.wrapping_add(1);
+        self.mem_read(STACK_PAGE | self.sp as u16)
+    }
+
+    fn push_u16(&mut self, value: u16) {
+        self.push((value >> 8) as u8);
+        self.push((value & 0xFF) as u8);
+    }
+
+    fn pull_u16(&mut self) -> u16 {
+        let lo = self.pull() as u16;
+        let hi = self.pull() as u16;
+        (hi << 8) | lo
     }
 
     fn update_zero_and_negative_flags(&mut self, result: u8) {
@@ -446,7 +984,7 @@ impl Cpu {
         if result == 0 {
             self.status |= StatusFlags::Zero;
         } else {
-            self.status &= !StatusFlags::Zero;
+            self.status.remove(StatusFlags::Zero);
         }
     }
 
@@ -454,7 +992,7 @@ impl Cpu {
         if result & StatusFlags::Negative.bits() != 0 {
             self.status |= StatusFlags::Negative;
         } else {
-            self.status &= !StatusFlags::Negative;
+            self.status.remove(StatusFlags::Negative);
         }
     }
 
@@ -463,7 +1001,7 @@ impl Cpu {
         if value {
             self.status |= StatusFlags::Overflow;
         } else {
-            self.status &= !StatusFlags::Overflow;
+            self.status.remove(StatusFlags::Overflow);
         }
     }
 
@@ -471,27 +1009,27 @@ impl Cpu {
         if value {
             self.status |= StatusFlags::Carry;
         } else {
-            self.status &= !StatusFlags::Carry;
+            self.status.remove(StatusFlags::Carry);
         }
     }
 
-    fn get_zero_flag(&self) -> u8 {
+    pub fn get_zero_flag(&self) -> u8 {
         (self.status & StatusFlags::Zero).bits() >> 1
     }
 
-    fn get_negative_flag(&self) -> u8 {
+    pub fn get_negative_flag(&self) -> u8 {
         (self.status & StatusFlags::Negative).bits() >> 7
     }
 
-    fn get_overflow_flag(&self) -> u8 {
+    pub fn get_overflow_flag(&self) -> u8 {
         (self.status & StatusFlags::Overflow).bits() >> 6
     }
 
-    fn get_carry_flag(&self) -> u8 {
+    pub fn get_carry_flag(&self) -> u8 {
         (self.status & StatusFlags::Carry).bits()
     }
 
-    fn get_address(&self, mode: &AddressingMode) -> u16 {
+    fn get_address(&mut self, mode: &AddressingMode) -> u16 {
         match mode {
             AddressingMode::Implicit => todo!(),
             AddressingMode::Accumulator => todo!(),
@@ -499,34 +1037,33 @@ impl Cpu {
             AddressingMode::ZeroPage => self.mem_read(self.pc) as u16,
             AddressingMode::ZeroPageX => {
                 let arg = self.mem_read(self.pc);
-                let addr = arg.wrapping_add(self.x) as u16;
-                addr
+                arg.wrapping_add(self.x) as u16
             }
             AddressingMode::ZeroPageY => {
                 let arg = self.mem_read(self.pc);
-                let addr = arg.wrapping_add(self.y) as u16;
-                addr
+                arg.wrapping_add(self.y) as u16
             }
             AddressingMode::Absolute => self.mem_read_u16(self.pc),
             AddressingMode::AbsoluteX => {
                 let arg = self.mem_read_u16(self.pc);
-                let addr = arg.wrapping_add(self.x as u16);
-                addr
+                arg.wrapping_add(self.x as u16)
             }
             AddressingMode::AbsoluteY => {
                 let arg = self.mem_read_u16(self.pc);
-                let addr = arg.wrapping_add(self.y as u16);
-                addr
+                arg.wrapping_add(self.y as u16)
             }
+            // signed, from the instruction after the branch
             AddressingMode::Relative => {
-                // TODO: test
-                let offset = self.mem_read(self.pc) as u16;
-                self.pc + offset
+                let offset = self.mem_read(self.pc) as i8;
+                self.pc.wrapping_add(1).wrapping_add(offset as u16)
             }
+            // the high byte comes from the same page, so a pointer at $xxFF wraps to $xx00
             AddressingMode::Indirect => {
-                // TODO: test
-                let addr = self.mem_read_u16(self.pc);
-                addr
+                let pointer = self.mem_read_u16(self.pc);
+                let lo = self.mem_read(pointer);
+                let hi = self.mem_read((pointer & 0xFF00) | (pointer.wrapping_add(1) & 0x00FF));
+
+                (hi as u16) << 8 | lo as u16
             }
             AddressingMode::IndirectX => {
                 let addr = self.mem_read(self.pc).wrapping_add(self.x);
@@ -540,8 +1077,7 @@ impl Cpu {
                 let lo = self.mem_read(addr as u16);
                 let hi = self.mem_read(addr.wrapping_add(1) as u16);
                 let deref = (hi as u16) << 8 | lo as u16;
-                let result = deref.wrapping_add(self.y as u16);
-                result
+                deref.wrapping_add(self.y as u16)
             }
         }
     }
@@ -550,6 +1086,9 @@ impl Cpu {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cheat::Cheat;
+    use crate::debugger::{Condition, FrameKind, Heatmap, StrictAction, StrictMode, WatchKind};
+    use std::sync::{Arc, Mutex};
 
     mod instructions {
         use super::*;
@@ -656,6 +1195,26 @@ mod tests {
                 cpu.load_and_run(vec![0xA0, 0xAD, 0x98, 0x00]);
                 assert_eq!(cpu.a, 0xAD);
             }
+
+            #[test]
+            fn test_0xba_tsx() {
+                let mut cpu = Cpu::new();
+                cpu.load_and_run(vec![0xBA, 0x00]);
+                assert_eq!(cpu.x, 0xFD);
+                assert_eq!(cpu.get_negative_flag(), 1);
+                assert_eq!(cpu.get_zero_flag(), 0);
+            }
+
+            #[test]
+            fn test_0x9a_txs_leaves_flags() {
+                let mut cpu = Cpu::new();
+                // Z from LDX #$00 survives the TXS, and PHA then pushes to $0100
+                cpu.load_and_run(vec![0xA9, 0x80, 0xA2, 0x00, 0x9A, 0x48, 0x00]);
+                assert_eq!(cpu.sp, 0xFF);
+                assert_eq!(cpu.mem_read(0x0100), 0x80);
+                assert_eq!(cpu.get_zero_flag(), 1);
+                assert_eq!(cpu.get_negative_flag(), 0);
+            }
         }
 
         mod arithmetic {
@@ -869,6 +1428,584 @@ mod tests {
                 assert_eq!(cpu.get_overflow_flag(), 1);
                 assert_eq!(cpu.get_negative_flag(), 1);
             }
+
+            #[test]
+            fn test_0x24_bit_copies_operand_bits() {
+                let mut cpu = Cpu::new();
+                cpu.mem_write(0x10, 0xC0);
+                cpu.load_and_run(vec![0xA9, 0x01, 0x24, 0x10, 0x00]);
+                assert_eq!(cpu.get_zero_flag(), 1);
+                assert_eq!(cpu.get_overflow_flag(), 1);
+                assert_eq!(cpu.get_negative_flag(), 1);
+                assert_eq!(cpu.a, 0x01);
+            }
+
+            #[test]
+            fn test_0x2c_bit_absolute() {
+                let mut cpu = Cpu::new();
+                cpu.mem_write(0x0200, 0x3F);
+                cpu.load_and_run(vec![0xA9, 0xFF, 0x2C, 0x00, 0x02, 0x00]);
+                assert_eq!(cpu.get_zero_flag(), 0);
+                assert_eq!(cpu.get_overflow_flag(), 0);
+                assert_eq!(cpu.get_negative_flag(), 0);
+            }
+        }
+
+        mod compare {
+            use super::*;
+
+            #[test]
+            fn test_0xc9_cmp() {
+                let mut cpu = Cpu::new();
+                cpu.load_and_run(vec![0xA9, 0x42, 0xC9, 0x42, 0x00]);
+                assert_eq!(cpu.get_zero_flag(), 1);
+                assert_eq!(cpu.get_carry_flag(), 1);
+                assert_eq!(cpu.get_negative_flag(), 0);
+
+                let mut cpu = Cpu::new();
+                cpu.load_and_run(vec![0xA9, 0x01, 0xC9, 0x02, 0x00]);
+                assert_eq!(cpu.get_zero_flag(), 0);
+                assert_eq!(cpu.get_carry_flag(), 0);
+                assert_eq!(cpu.get_negative_flag(), 1);
+                assert_eq!(cpu.a, 0x01);
+            }
+
+            #[test]
+            fn test_0xdd_cmp_absolute_x() {
+                let mut cpu = Cpu::new();
+                cpu.mem_write(0x0205, 0x7F);
+                cpu.load_and_run(vec![0xA9, 0x80, 0xA2, 0x05, 0xDD, 0x00, 0x02, 0x00]);
+                assert_eq!(cpu.get_zero_flag(), 0);
+                assert_eq!(cpu.get_carry_flag(), 1);
+                assert_eq!(cpu.get_negative_flag(), 0);
+            }
+
+            #[test]
+            fn test_0xe0_cpx_counts_a_loop() {
+                let mut cpu = Cpu::new();
+                cpu.load_and_run(vec![0xA2, 0x00, 0xE8, 0xE0, 0x05, 0xD0, 0xFB, 0x00]);
+                assert_eq!(cpu.x, 5);
+                assert_eq!(cpu.get_zero_flag(), 1);
+                assert_eq!(cpu.get_carry_flag(), 1);
+            }
+
+            #[test]
+            fn test_0xc4_cpy_zero_page() {
+                let mut cpu = Cpu::new();
+                cpu.mem_write(0x10, 0x30);
+                cpu.load_and_run(vec![0xA0, 0x20, 0xC4, 0x10, 0x00]);
+                assert_eq!(cpu.get_carry_flag(), 0);
+                assert_eq!(cpu.get_negative_flag(), 1);
+                assert_eq!(cpu.y, 0x20);
+            }
+        }
+
+        mod flags {
+            use super::*;
+
+            #[test]
+            fn test_set_and_clear() {
+                // (set, clear, flag)
+                let pairs = [
+                    (0x38, 0x18, StatusFlags::Carry),
+                    (0x78, 0x58, StatusFlags::InterruptDisable),
+                    (0xF8, 0xD8, StatusFlags::Decimal),
+                ];
+                for (set, clear, flag) in pairs {
+                    let mut cpu = Cpu::new();
+                    cpu.load(vec![set, set, clear, clear, 0x00]);
+                    cpu.reset();
+                    cpu.status = StatusFlags::Unused;
+
+                    cpu.step();
+                    assert_eq!(cpu.status, StatusFlags::Unused | flag, "{set:#04X}");
+                    cpu.step();
+                    assert_eq!(cpu.status, StatusFlags::Unused | flag, "{set:#04X} again");
+                    cpu.step();
+                    assert_eq!(cpu.status, StatusFlags::Unused, "{clear:#04X}");
+                    cpu.step();
+                    assert_eq!(cpu.status, StatusFlags::Unused, "{clear:#04X} again");
+                }
+            }
+
+            #[test]
+            fn test_0xb8_clv() {
+                // 0x50 + 0x50 overflows
+                let mut cpu = Cpu::new();
+                cpu.load(vec![0xA9, 0x50, 0x69, 0x50, 0xB8, 0x00]);
+                cpu.reset();
+                cpu.step();
+                cpu.step();
+                assert_eq!(cpu.get_overflow_flag(), 1);
+                cpu.step();
+                assert_eq!(cpu.get_overflow_flag(), 0);
+                assert_eq!(cpu.get_negative_flag(), 1);
+            }
+
+            #[test]
+            fn test_0x38_sec_feeds_sbc() {
+                let mut cpu = Cpu::new();
+                cpu.load_and_run(vec![0xA9, 0x05, 0x38, 0xE9, 0x02, 0x00]);
+                assert_eq!(cpu.a, 0x03);
+            }
+        }
+
+        mod branch {
+            use super::*;
+
+            #[test]
+            fn test_0xd0_bne_loops_backwards() {
+                let mut cpu = Cpu::new();
+                cpu.load_and_run(vec![0xA2, 0x03, 0xA0, 0x00, 0xC8, 0xCA, 0xD0, 0xFC, 0x00]);
+                assert_eq!(cpu.x, 0);
+                assert_eq!(cpu.y, 3);
+            }
+
+            #[test]
+            fn test_0xf0_beq_skips_forward() {
+                let mut cpu = Cpu::new();
+                cpu.load_and_run(vec![0xA9, 0x00, 0xF0, 0x02, 0xA9, 0x05, 0x00]);
+                assert_eq!(cpu.a, 0);
+            }
+
+            #[test]
+            fn test_branches_not_taken_fall_through() {
+                let mut cpu = Cpu::new();
+                cpu.load_and_run(vec![0xA9, 0x01, 0xF0, 0x02, 0xA2, 0x05, 0x00]);
+                assert_eq!(cpu.x, 5);
+
+                let mut cpu = Cpu::new();
+                cpu.load_and_run(vec![0xA9, 0x80, 0x10, 0x02, 0xA2, 0x05, 0x00]);
+                assert_eq!(cpu.x, 5);
+            }
+
+            #[test]
+            fn test_each_branch_condition() {
+                // (opcode, flag it tests, whether it branches when the flag is set)
+                let branches = [
+                    (0x90, StatusFlags::Carry, false),
+                    (0xB0, StatusFlags::Carry, true),
+                    (0xF0, StatusFlags::Zero, true),
+                    (0xD0, StatusFlags::Zero, false),
+                    (0x30, StatusFlags::Negative, true),
+                    (0x10, StatusFlags::Negative, false),
+                    (0x50, StatusFlags::Overflow, false),
+                    (0x70, StatusFlags::Overflow, true),
+                ];
+                for (opcode, flag, when_set) in branches {
+                    for set in [false, true] {
+                        let mut cpu = Cpu::new();
+                        cpu.load(vec![opcode, 0x10, 0x00]);
+                        cpu.reset();
+                        cpu.status.set(flag, set);
+                        cpu.step();
+                        let expected = if set == when_set { 0x8012 } else { 0x8002 };
+                        assert_eq!(cpu.pc, expected, "opcode {opcode:#04X}, flag set: {set}");
+                    }
+                }
+            }
+
+            #[test]
+            fn test_branch_cycles() {
+                let mut cpu = Cpu::new();
+                cpu.mem_write(0x8000, 0xD0);
+                cpu.mem_write(0x8001, 0x02);
+                cpu.mem_write(0x80FD, 0xD0);
+                cpu.mem_write(0x80FE, 0x02);
+
+                // not taken
+                cpu.pc = 0x8000;
+                cpu.status.insert(StatusFlags::Zero);
+                let start = cpu.cycles();
+                cpu.step();
+                assert_eq!(cpu.cycles() - start, 2);
+
+                // taken, same page
+                cpu.pc = 0x8000;
+                cpu.status.remove(StatusFlags::Zero);
+                let start = cpu.cycles();
+                cpu.step();
+                assert_eq!(cpu.pc, 0x8004);
+                assert_eq!(cpu.cycles() - start, 3);
+
+                // taken, onto the next page
+                cpu.pc = 0x80FD;
+                let start = cpu.cycles();
+                cpu.step();
+                assert_eq!(cpu.pc, 0x8101);
+                assert_eq!(cpu.cycles() - start, 4);
+            }
+        }
+
+        mod stack {
+            use super::*;
+
+            #[test]
+            fn test_reset_sets_sp() {
+                let mut cpu = Cpu::new();
+                cpu.load_and_run(vec![0x00]);
+                assert_eq!(cpu.sp, 0xFD);
+            }
+
+            #[test]
+            fn test_0x48_pha_0x68_pla() {
+                let mut cpu = Cpu::new();
+                cpu.load_and_run(vec![
+                    0xA9, 0x11, 0x48, 0xA9, 0x80, 0x48, 0xA9, 0x00, 0x68, 0xAA, 0x68, 0x00,
+                ]);
+                assert_eq!(cpu.mem_read(0x01FD), 0x11);
+                assert_eq!(cpu.mem_read(0x01FC), 0x80);
+                assert_eq!(cpu.x, 0x80);
+                assert_eq!(cpu.a, 0x11);
+                assert_eq!(cpu.get_negative_flag(), 0);
+                assert_eq!(cpu.sp, 0xFD);
+            }
+
+            #[test]
+            fn test_0x68_pla_sets_flags() {
+                let mut cpu = Cpu::new();
+                cpu.load_and_run(vec![0xA9, 0x00, 0x48, 0xA9, 0x01, 0x68, 0x00]);
+                assert_eq!(cpu.a, 0);
+                assert_eq!(cpu.get_zero_flag(), 1);
+            }
+
+            #[test]
+            fn test_0x08_php_sets_break() {
+                let mut cpu = Cpu::new();
+                cpu.load(vec![0x08, 0x00]);
+                cpu.reset();
+                cpu.status = StatusFlags::Carry;
+                cpu.run();
+                assert_eq!(cpu.mem_read(0x01FD), 0b0011_0001);
+                assert_eq!(cpu.status, StatusFlags::Carry);
+            }
+
+            #[test]
+            fn test_0x28_plp_ignores_break() {
+                let mut cpu = Cpu::new();
+                cpu.load_and_run(vec![0xA9, 0xDF, 0x48, 0x28, 0x00]);
+                assert_eq!(cpu.status.bits(), 0b1110_1111);
+            }
+
+            #[test]
+            fn test_stack_wraps() {
+                let mut cpu = Cpu::new();
+                cpu.load(vec![0xA9, 0x42, 0x48, 0x68, 0x00]);
+                cpu.reset();
+                cpu.sp = 0x00;
+                cpu.run();
+                assert_eq!(cpu.mem_read(0x0100), 0x42);
+                assert_eq!(cpu.sp, 0x00);
+            }
+        }
+
+        mod jump {
+            use super::*;
+
+            #[test]
+            fn test_0x4c_jmp_absolute() {
+                let mut cpu = Cpu::new();
+                cpu.load_and_run(vec![0x4C, 0x05, 0x80, 0xA2, 0x01, 0xA0, 0x02, 0x00]);
+                assert_eq!(cpu.x, 0);
+                assert_eq!(cpu.y, 2);
+            }
+
+            #[test]
+            fn test_0x6c_jmp_indirect() {
+                let mut cpu = Cpu::new();
+                cpu.mem_write_u16(0x0120, 0x9000);
+                cpu.load(vec![0x6C, 0x20, 0x01]);
+                cpu.reset();
+                let start = cpu.cycles();
+                cpu.step();
+                assert_eq!(cpu.pc, 0x9000);
+                assert_eq!(cpu.cycles() - start, 5);
+            }
+
+            #[test]
+            fn test_0x6c_jmp_indirect_wraps_within_page() {
+                let mut cpu = Cpu::new();
+                cpu.mem_write(0x02FF, 0x34);
+                cpu.mem_write(0x0300, 0x56);
+                cpu.mem_write(0x0200, 0x12);
+                cpu.load(vec![0x6C, 0xFF, 0x02]);
+                cpu.reset();
+                cpu.step();
+                assert_eq!(cpu.pc, 0x1234);
+            }
+
+            #[test]
+            fn test_0x20_jsr_0x60_rts() {
+                let mut cpu = Cpu::new();
+                cpu.load_and_run(vec![
+                    0x20, 0x09, 0x80, 0xA2, 0x05, 0x00, 0x00, 0x00, 0x00, 0xA9, 0x42, 0x60,
+                ]);
+                assert_eq!(cpu.a, 0x42);
+                assert_eq!(cpu.x, 0x05);
+                assert_eq!(cpu.sp, 0xFD);
+            }
+
+            #[test]
+            fn test_0x20_jsr_pushes_last_operand_byte() {
+                let mut cpu = Cpu::new();
+                cpu.load(vec![0x20, 0x34, 0x92]);
+                cpu.reset();
+                let start = cpu.cycles();
+                cpu.step();
+                assert_eq!(cpu.pc, 0x9234);
+                assert_eq!(cpu.sp, 0xFB);
+                assert_eq!(cpu.mem_read_u16(0x01FC), 0x8002);
+                assert_eq!(cpu.cycles() - start, 6);
+                assert_eq!(cpu.debugger().call_stack().depth(), 1);
+            }
+
+            #[test]
+            fn test_nested_subroutines() {
+                // main calls $8010, which calls $8020 twice
+                let mut program = vec![0; 0x30];
+                program[..4].copy_from_slice(&[0x20, 0x10, 0x80, 0x00]);
+                program[0x10..0x17].copy_from_slice(&[0x20, 0x20, 0x80, 0x20, 0x20, 0x80, 0x60]);
+                program[0x20..0x22].copy_from_slice(&[0xE8, 0x60]);
+
+                let mut cpu = Cpu::new();
+                cpu.load_and_run(program);
+                assert_eq!(cpu.x, 2);
+                assert_eq!(cpu.sp, 0xFD);
+                assert_eq!(cpu.debugger().call_stack().depth(), 0);
+                assert_eq!(cpu.debugger().call_stack().mismatches(), 0);
+            }
+
+            #[test]
+            fn test_0x00_brk_halts_by_default() {
+                let mut cpu = Cpu::new();
+                cpu.load(vec![0xA9, 0x01, 0x00]);
+                cpu.reset();
+                assert!(cpu.halt_on_brk());
+                assert_eq!(cpu.run(), Status::Halted);
+                assert_eq!(cpu.sp, 0xFD);
+            }
+
+            #[test]
+            fn test_0x00_brk_interrupt_sequence() {
+                let mut cpu = Cpu::new();
+                cpu.set_halt_on_brk(false);
+                cpu.mem_write_u16(0xFFFE, 0x9000);
+                cpu.load(vec![0x00, 0xFF]);
+                cpu.reset();
+                cpu.status = StatusFlags::Carry | StatusFlags::Unused;
+                let start = cpu.cycles();
+
+                assert_eq!(cpu.step(), Status::Running);
+                assert_eq!(cpu.pc, 0x9000);
+                assert_eq!(cpu.sp, 0xFA);
+                assert_eq!(cpu.mem_read_u16(0x01FC), 0x8002);
+                assert_eq!(cpu.mem_read(0x01FB), 0b0011_0001);
+                assert!(cpu.status.contains(StatusFlags::InterruptDisable));
+                assert_eq!(cpu.cycles() - start, 7);
+                assert_eq!(
+                    cpu.debugger().call_stack().frames()[0].kind,
+                    FrameKind::Interrupt
+                );
+            }
+
+            #[test]
+            fn test_0x40_rti_returns_from_brk() {
+                let mut cpu = Cpu::new();
+                cpu.set_halt_on_brk(false);
+                cpu.mem_write_u16(0xFFFE, 0x9000);
+                cpu.mem_write(0x9000, 0xA2);
+                cpu.mem_write(0x9001, 0x07);
+                cpu.mem_write(0x9002, 0x40);
+                // ends on an opcode the CPU doesn't know
+                cpu.load(vec![0x00, 0xFF, 0xA0, 0x01, 0x02]);
+                cpu.reset();
+                cpu.status = StatusFlags::Carry | StatusFlags::Unused;
+
+                assert_eq!(cpu.run(), Status::UnknownOpcode(0x02));
+                assert_eq!(cpu.x, 0x07);
+                assert_eq!(cpu.y, 0x01);
+                assert_eq!(cpu.status, StatusFlags::Carry | StatusFlags::Unused);
+                assert_eq!(cpu.sp, 0xFD);
+                assert_eq!(cpu.debugger().call_stack().depth(), 0);
+                assert_eq!(cpu.debugger().call_stack().mismatches(), 0);
+            }
+        }
+
+        mod other {
+            use super::*;
+
+            #[test]
+            fn test_0xea_nop() {
+                let mut cpu = Cpu::new();
+                cpu.load(vec![0xA9, 0x80, 0xEA, 0x00]);
+                cpu.reset();
+                cpu.step();
+                let registers = cpu.registers();
+                let start = cpu.cycles();
+                cpu.step();
+                assert_eq!(
+                    cpu.registers(),
+                    Registers {
+                        pc: 0x8003,
+                        ..registers
+                    }
+                );
+                assert_eq!(cpu.cycles() - start, 2);
+            }
+
+            #[test]
+            fn test_unofficial_nops() {
+                // (opcode, bytes, cycles)
+                let nops = [
+                    (0x1A, 1, 2),
+                    (0x80, 2, 2),
+                    (0x04, 2, 3),
+                    (0x14, 2, 4),
+                    (0x0C, 3, 4),
+                    (0x1C, 3, 4),
+                ];
+                for (opcode, bytes, cycles) in nops {
+                    let mut cpu = Cpu::new();
+                    cpu.load(vec![opcode, 0x10, 0x02, 0x00]);
+                    cpu.reset();
+                    let registers = cpu.registers();
+                    let start = cpu.cycles();
+                    assert_eq!(cpu.step(), Status::Running);
+                    assert_eq!(
+                        cpu.registers(),
+                        Registers {
+                            pc: 0x8000 + bytes,
+                            ..registers
+                        },
+                        "{opcode:#04X}"
+                    );
+                    assert_eq!(cpu.cycles() - start, cycles, "{opcode:#04X}");
+                }
+            }
+
+            #[test]
+            fn test_unofficial_nops_read_their_operand() {
+                let reads = Arc::new(Mutex::new(Vec::new()));
+                let mut cpu = Cpu::new();
+                let log = Arc::clone(&reads);
+                cpu.hooks_mut().on_read(0x0200..=0x02FF, move |addr, _| {
+                    log.lock().unwrap().push(addr);
+                    None
+                });
+                cpu.load_and_run(vec![0xA2, 0x01, 0x0C, 0x00, 0x02, 0x1C, 0x10, 0x02, 0x00]);
+                assert_eq!(*reads.lock().unwrap(), vec![0x0200, 0x0211]);
+            }
+        }
+
+        // algebraic properties over every operand, checked against plain integer math
+        mod properties {
+            use super::*;
+            use proptest::prelude::*;
+
+            const C: u8 = 0b0000_0001;
+            const Z: u8 = 0b0000_0010;
+            const V: u8 = 0b0100_0000;
+            const N: u8 = 0b1000_0000;
+
+            fn run_with(program: Vec<u8>, a: u8, carry: bool) -> Cpu {
+                let mut cpu = Cpu::new();
+                cpu.load(program);
+                cpu.reset();
+                cpu.a = a;
+                cpu.set_carry_flag(carry);
+                cpu.run();
+                cpu
+            }
+
+            fn flags(cpu: &Cpu) -> u8 {
+                cpu.status.bits() & (C | Z | V | N)
+            }
+
+            fn zn(result: u8) -> u8 {
+                (if result == 0 { Z } else { 0 }) | (result & N)
+            }
+
+            proptest! {
+                #[test]
+                fn test_adc_matches_reference(a: u8, m: u8, carry: bool) {
+                    let cpu = run_with(vec![0x69, m, 0x00], a, carry);
+
+                    let sum = a as u16 + m as u16 + carry as u16;
+                    let signed = a as i8 as i16 + m as i8 as i16 + carry as i16;
+                    let result = sum as u8;
+                    let expected = zn(result)
+                        | if sum > 0xFF { C } else { 0 }
+                        | if !(-128..=127).contains(&signed) { V } else { 0 };
+
+                    prop_assert_eq!(cpu.a, result);
+                    prop_assert_eq!(flags(&cpu), expected);
+                }
+
+                #[test]
+                fn test_sbc_matches_reference(a: u8, m: u8, carry: bool) {
+                    let cpu = run_with(vec![0xE9, m, 0x00], a, carry);
+
+                    let borrow = !carry as i16;
+                    let difference = a as i16 - m as i16 - borrow;
+                    let signed = a as i8 as i16 - m as i8 as i16 - borrow;
+                    let result = difference as u8;
+                    let expected = zn(result)
+                        | if difference >= 0 { C } else { 0 }
+                        | if !(-128..=127).contains(&signed) { V } else { 0 };
+
+                    prop_assert_eq!(cpu.a, result);
+                    prop_assert_eq!(flags(&cpu), expected);
+                }
+
+                #[test]
+                fn test_sbc_is_adc_of_the_complement(a: u8, m: u8, carry: bool) {
+                    let sbc = run_with(vec![0xE9, m, 0x00], a, carry);
+                    let adc = run_with(vec![0x69, !m, 0x00], a, carry);
+
+                    prop_assert_eq!(sbc.a, adc.a);
+                    prop_assert_eq!(flags(&sbc), flags(&adc));
+                }
+
+                #[test]
+                fn test_cmp_matches_reference(a: u8, m: u8, carry: bool) {
+                    let cpu = run_with(vec![0xC9, m, 0x00], a, carry);
+
+                    let expected = zn(a.wrapping_sub(m)) | if a >= m { C } else { 0 };
+
+                    prop_assert_eq!(cpu.a, a);
+                    prop_assert_eq!(flags(&cpu), expected);
+                }
+
+                #[test]
+                fn test_rol_then_ror_round_trips(a: u8, carry: bool) {
+                    let cpu = run_with(vec![0x2A, 0x6A, 0x00], a, carry);
+
+                    prop_assert_eq!(cpu.a, a);
+                    prop_assert_eq!(cpu.get_carry_flag() == 1, carry);
+                    prop_assert_eq!(flags(&cpu) & (Z | N), zn(a));
+                }
+
+                #[test]
+                fn test_asl_then_lsr_clears_the_top_bit(a: u8, carry: bool) {
+                    let asl = run_with(vec![0x0A, 0x00], a, carry);
+                    prop_assert_eq!(asl.a, a << 1);
+                    prop_assert_eq!(asl.get_carry_flag(), a >> 7);
+
+                    let cpu = run_with(vec![0x0A, 0x4A, 0x00], a, carry);
+                    prop_assert_eq!(cpu.a, a & 0x7F);
+                    prop_assert_eq!(cpu.get_carry_flag(), 0);
+                }
+
+                #[test]
+                fn test_inc_then_dec_round_trips(m: u8) {
+                    let mut cpu = Cpu::new();
+                    cpu.mem_write(0x10, m);
+                    cpu.load_and_run(vec![0xE6, 0x10, 0xC6, 0x10, 0x00]);
+
+                    prop_assert_eq!(cpu.mem_peek(0x10), m);
+                    prop_assert_eq!(flags(&cpu) & (Z | N), zn(m));
+                }
+            }
         }
 
         #[test]
@@ -879,4 +2016,324 @@ mod tests {
             assert_eq!(cpu.x, 0xC1);
         }
     }
+
+    #[test]
+    fn test_game_genie_patches_program_reads() {
+        let mut cpu = Cpu::new();
+        // LDA #$01 becomes LDA #$FF at $8001
+        cpu.cheats_mut().add_game_genie("NYAAPE").unwrap();
+        cpu.load_and_run(vec![0xA9, 0x01, 0x00]);
+        assert_eq!(cpu.a, 0xFF);
+    }
+
+    #[test]
+    fn test_frozen_address_ignores_program_writes() {
+        let mut cpu = Cpu::new();
+        cpu.cheats_mut().add(Cheat::Freeze {
+            address: 0x10,
+            value: 0x63,
+        });
+        cpu.apply_cheats();
+        assert_eq!(cpu.mem_read(0x10), 0x63);
+
+        cpu.load_and_run(vec![0xA9, 0x01, 0x85, 0x10, 0x00]);
+        assert_eq!(cpu.mem_read(0x10), 0x63);
+    }
+
+    #[test]
+    fn test_read_hook_overrides_value() {
+        let mut cpu = Cpu::new();
+        cpu.mem_write(0x10, 0x01);
+        cpu.hooks_mut()
+            .on_read(0x10..=0x10, |_, value| Some(value + 0x10));
+        cpu.load_and_run(vec![0xA5, 0x10, 0x00]);
+        assert_eq!(cpu.a, 0x11);
+    }
+
+    #[test]
+    fn test_write_hook_sees_stores() {
+        let writes = Arc::new(Mutex::new(Vec::new()));
+        let mut cpu = Cpu::new();
+        let log = Arc::clone(&writes);
+        cpu.hooks_mut().on_write(0x00..=0xFF, move |addr, value| {
+            log.lock().unwrap().push((addr, value));
+            None
+        });
+        cpu.load_and_run(vec![0xA9, 0x07, 0x85, 0x20, 0xE6, 0x20, 0x00]);
+        assert_eq!(*writes.lock().unwrap(), vec![(0x20, 0x07), (0x20, 0x08)]);
+    }
+
+    #[test]
+    fn test_execute_hook_can_change_registers() {
+        let mut cpu = Cpu::new();
+        cpu.hooks_mut()
+            .on_execute(0x8002..=0x8002, |_, registers| registers.x = 0x40);
+        cpu.load_and_run(vec![0xA2, 0x01, 0xE8, 0x00]);
+        assert_eq!(cpu.x, 0x41);
+    }
+
+    #[test]
+    fn test_reset_and_state_load_events() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut cpu = Cpu::new();
+        let log = Arc::clone(&events);
+        cpu.events_mut()
+            .subscribe(move |event| log.lock().unwrap().push(*event));
+
+        cpu.load_and_run(vec![0x00]);
+        let state = cpu.save_state();
+        cpu.load_state(&state);
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![Event::Reset, Event::StateLoaded]
+        );
+    }
+
+    #[test]
+    fn test_state_hash() {
+        let mut first = Cpu::new();
+        let mut second = Cpu::new();
+        first.load_and_run(vec![0xA9, 0x01, 0x85, 0x10, 0x00]);
+        second.load_and_run(vec![0xA9, 0x01, 0x85, 0x10, 0x00]);
+        assert_eq!(first.state_hash(), second.state_hash());
+
+        second.cheats_mut().add_game_genie("SXIOPO").unwrap();
+        assert_eq!(first.state_hash(), second.state_hash());
+
+        second.x = 1;
+        assert_ne!(first.state_hash(), second.state_hash());
+        second.x = 0;
+        second.mem_write(0x0700, 0x01);
+        assert_ne!(first.state_hash(), second.state_hash());
+    }
+
+    #[test]
+    fn test_disassemble_at() {
+        let mut cpu = Cpu::new();
+        cpu.load(vec![0xA9, 0x05, 0x8D, 0x00, 0x02, 0x00]);
+        assert_eq!(cpu.disassemble_at(0x8000).to_string(), "LDA #$05");
+        assert_eq!(cpu.disassemble_at(0x8002).to_string(), "STA $0200");
+
+        cpu.cheats_mut().add_game_genie("NYAAPE").unwrap();
+        assert_eq!(cpu.disassemble_at(0x8000).to_string(), "LDA #$FF");
+    }
+
+    #[test]
+    fn test_breakpoint_pauses_before_instruction() {
+        let mut cpu = Cpu::new();
+        cpu.load(vec![0xA2, 0x01, 0xE8, 0xE8, 0x00]);
+        cpu.reset();
+        cpu.debugger_mut().add_breakpoint(0x8003);
+
+        assert_eq!(cpu.run(), Status::Breakpoint(0x8003));
+        assert_eq!(cpu.pc, 0x8003);
+        assert_eq!(cpu.x, 0x02);
+
+        assert_eq!(cpu.run(), Status::Halted);
+        assert_eq!(cpu.x, 0x03);
+    }
+
+    #[test]
+    fn test_conditional_breakpoints_and_watchpoints() {
+        let program = vec![0xA9, 0x05, 0x85, 0x10, 0xE8, 0x00];
+        let condition = |source| Some(Condition::parse(source).unwrap());
+
+        let mut cpu = Cpu::new();
+        cpu.load(program.clone());
+        cpu.reset();
+        cpu.debugger_mut().add_breakpoint(0x8004);
+        cpu.debugger_mut()
+            .set_breakpoint_condition(0x8004, condition("[$10] == 6"));
+        let id = cpu
+            .debugger_mut()
+            .add_watchpoint(0x10..=0x10, WatchKind::Write);
+        cpu.debugger_mut()
+            .set_watchpoint_condition(id, condition("A != 5"));
+        assert_eq!(cpu.run(), Status::Halted);
+
+        let mut cpu = Cpu::new();
+        cpu.load(program);
+        cpu.reset();
+        cpu.debugger_mut().add_breakpoint(0x8004);
+        cpu.debugger_mut()
+            .set_breakpoint_condition(0x8004, condition("[$10] == 5 && A == 5"));
+        assert_eq!(cpu.run(), Status::Breakpoint(0x8004));
+    }
+
+    #[test]
+    fn test_step_back() {
+        let mut cpu = Cpu::new();
+        // LDA #$05; STA $10; INC $10; BRK
+        cpu.load(vec![0xA9, 0x05, 0x85, 0x10, 0xE6, 0x10, 0x00]);
+        cpu.reset();
+        cpu.debugger_mut().set_history_capacity(3);
+        let start = cpu.state_hash();
+
+        assert_eq!(cpu.run(), Status::Halted);
+        let end = cpu.state_hash();
+        assert_eq!(cpu.debugger().history_len(), 3);
+
+        assert!(cpu.step_back());
+        assert_eq!(cpu.pc, 0x8006);
+        assert!(cpu.step_back());
+        assert_eq!(cpu.mem_read(0x10), 0x05);
+        assert!(cpu.step_back());
+        assert_eq!(cpu.mem_read(0x10), 0x00);
+        assert_eq!(cpu.pc, 0x8002);
+        // the LDA fell out of the history
+        assert!(!cpu.step_back());
+
+        cpu.step();
+        cpu.step();
+        cpu.step();
+        assert_eq!(cpu.state_hash(), end);
+
+        cpu.reset();
+        assert_eq!(cpu.debugger().history_len(), 0);
+        assert_ne!(cpu.state_hash(), start);
+    }
+
+    #[test]
+    fn test_heatmap() {
+        let mut cpu = Cpu::new();
+        // LDA $10; STA $11; STA $11; BRK
+        cpu.load(vec![0xA5, 0x10, 0x85, 0x11, 0x85, 0x11, 0x00]);
+        cpu.reset();
+        cpu.debugger_mut().set_heatmap(Some(Heatmap::new(1)));
+        cpu.run();
+
+        let heatmap = cpu.debugger().heatmap().unwrap();
+        assert_eq!(heatmap.get(0x10).reads, 1);
+        assert_eq!(heatmap.get(0x11).writes, 2);
+        assert_eq!(heatmap.get(0x8002).executes, 1);
+        assert_eq!(heatmap.get(0x8002).reads, 1);
+    }
+
+    #[test]
+    fn test_watchpoint_reports_triggering_instruction() {
+        let mut cpu = Cpu::new();
+        cpu.load(vec![0xA9, 0x07, 0x85, 0x10, 0xE8, 0x00]);
+        cpu.reset();
+        let id = cpu
+            .debugger_mut()
+            .add_watchpoint(0x10..=0x10, WatchKind::Write);
+
+        assert_eq!(
+            cpu.run(),
+            Status::Watchpoint(WatchpointHit {
+                id,
+                address: 0x10,
+                value: 0x07,
+                kind: WatchKind::Write,
+                pc: 0x8002,
+            })
+        );
+        assert_eq!(cpu.mem_read(0x10), 0x07);
+        assert_eq!(cpu.pc, 0x8004);
+
+        assert_eq!(cpu.run(), Status::Halted);
+        assert_eq!(cpu.x, 0x01);
+    }
+
+    #[test]
+    fn test_strict_mode_breaks_on_uninitialized_read() {
+        let mut cpu = Cpu::new();
+        // STA $10; LDA $10; LDX $11; INX; BRK
+        cpu.load(vec![0x85, 0x10, 0xA5, 0x10, 0xA6, 0x11, 0xE8, 0x00]);
+        cpu.reset();
+        cpu.debugger_mut()
+            .set_strict_mode(Some(StrictMode::new(StrictAction::Break)));
+
+        assert_eq!(
+            cpu.run(),
+            Status::UninitializedRead(UninitializedRead {
+                address: 0x11,
+                pc: 0x8004
+            })
+        );
+        assert_eq!(cpu.pc, 0x8006);
+        assert_eq!(cpu.run(), Status::Halted);
+        assert_eq!(cpu.debugger().strict_mode().unwrap().reads().len(), 1);
+    }
+
+    #[test]
+    fn test_clearing_flags_keeps_unused_bit() {
+        let mut cpu = Cpu::new();
+        cpu.load_and_run(vec![0xA9, 0x01, 0x00]);
+        assert_eq!(cpu.status.bits(), 0b0010_0100);
+    }
+
+    #[test]
+    fn test_ram_init_only_fills_internal_ram() {
+        let mut cpu = Cpu::with_ram_init(RamInit::Ones);
+        assert_eq!(cpu.mem_read(0x0000), 0xFF);
+        assert_eq!(cpu.mem_read(0x07FF), 0xFF);
+        assert_eq!(cpu.mem_read(0x0800), 0x00);
+    }
+
+    #[test]
+    fn test_cpus_run_on_separate_threads() {
+        let handles: Vec<_> = (0..4u8)
+            .map(|value| {
+                let mut cpu = Cpu::new();
+                cpu.hooks_mut()
+                    .on_read(0x10..=0x10, move |_, _| Some(value));
+                std::thread::spawn(move || {
+                    cpu.load_and_run(vec![0xA5, 0x10, 0x00]);
+                    cpu.a
+                })
+            })
+            .collect();
+
+        let results: Vec<u8> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert_eq!(results, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_unknown_opcode_stops_without_panicking() {
+        let mut cpu = Cpu::new();
+        cpu.load(vec![0xE8, 0x02]);
+        cpu.reset();
+        assert_eq!(cpu.run(), Status::UnknownOpcode(0x02));
+        assert_eq!(cpu.pc, 0x8001);
+        assert_eq!(cpu.x, 1);
+    }
+
+    #[test]
+    fn test_block_cache_runs_the_same() {
+        // LDX #$00; loop: INX; STX $10; LDA ($10),Y; CPX #$40; BNE loop; BRK
+        let program = vec![
+            0xA2, 0x00, 0xE8, 0x86, 0x10, 0xB1, 0x10, 0xE0, 0x40, 0xD0, 0xF7, 0x00,
+        ];
+        let mut interpreted = Cpu::new();
+        interpreted.load_and_run(program.clone());
+
+        let mut cached = Cpu::new();
+        cached.set_block_cache(true);
+        cached.debugger_mut().set_heatmap(Some(Heatmap::new(1)));
+        cached.load_and_run(program);
+
+        assert_eq!(cached.state_hash(), interpreted.state_hash());
+        assert_eq!(cached.debugger().heatmap().unwrap().get(0x8002).reads, 0x40);
+    }
+
+    #[test]
+    fn test_block_cache_sees_self_modifying_code() {
+        // LDA #$E8; STA $8007; NOP; NOP; NOP; BRK, where the STA turns the last NOP into INX
+        let mut cpu = Cpu::new();
+        cpu.set_block_cache(true);
+        cpu.load_and_run(vec![0xA9, 0xE8, 0x8D, 0x07, 0x80, 0xEA, 0xEA, 0xEA, 0x00]);
+        assert_eq!(cpu.x, 0x01);
+    }
+
+    #[test]
+    fn test_pc_wraps_at_end_of_memory() {
+        let mut cpu = Cpu::new();
+        cpu.mem_write(0xFFFF, 0xA9);
+        cpu.mem_write(0x0000, 0x42);
+        cpu.pc = 0xFFFF;
+        assert_eq!(cpu.step(), Status::Running);
+        assert_eq!((cpu.a, cpu.pc), (0x42, 0x0001));
+    }
 }
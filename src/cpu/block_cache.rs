@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::instructions::{lookup, Instruction};
+use super::{AddressingMode, MEMORY_SIZE};
+
+// long enough for any loop body; a longer run just carries on in the next block
+const MAX_BLOCK_LEN: usize = 32;
+
+type Block = Arc<[&'static Instruction]>;
+
+// straight-line runs of decoded instructions, keyed by the address of the first one, so hot code
+// is only decoded once. Only opcodes are cached (operands are read as the instruction runs), and
+// a write over any cached opcode throws the whole cache away
+pub(crate) struct BlockCache {
+    blocks: HashMap<u16, Block>,
+    // one bit per address holding a cached opcode
+    code: Vec<u64>,
+    // the block being run, the index of its next instruction and the address that should be at
+    current: Option<(Block, usize, u16)>,
+}
+
+impl BlockCache {
+    pub(crate) fn new() -> Self {
+        Self {
+            blocks: HashMap::new(),
+            code: vec![0; MEMORY_SIZE / 64],
+            current: None,
+        }
+    }
+
+    // the instruction at `pc`, from the current block while execution runs straight through it;
+    // None for an opcode that doesn't decode
+    pub(crate) fn next(&mut self, pc: u16, memory: &[u8]) -> Option<&'static Instruction> {
+        if let Some((block, index, next_pc)) = &mut self.current {
+            if *next_pc == pc {
+                if let Some(&instruction) = block.get(*index) {
+                    *index += 1;
+                    *next_pc = pc.wrapping_add(instruction.bytes as u16);
+                    return Some(instruction);
+                }
+            }
+        }
+
+        let block = match self.blocks.get(&pc) {
+            Some(block) => block.clone(),
+            None => self.decode(pc, memory)?,
+        };
+        let instruction = block[0];
+        self.current = Some((block, 1, pc.wrapping_add(instruction.bytes as u16)));
+        Some(instruction)
+    }
+
+    // called for every write, which only costs anything when it lands on a cached opcode
+    pub(crate) fn invalidate(&mut self, addr: u16) {
+        if self.code[addr as usize / 64] & (1 << (addr % 64)) != 0 {
+            log::trace!("write to ${:04X} invalidated the block cache", addr);
+            self.clear();
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.blocks.clear();
+        self.code.fill(0);
+        self.current = None;
+    }
+
+    fn decode(&mut self, start: u16, memory: &[u8]) -> Option<Block> {
+        let mut instructions = Vec::new();
+        let mut pc = start;
+        while instructions.len() < MAX_BLOCK_LEN {
+            let Some(instruction) = lookup(memory[pc as usize]) else {
+                break;
+            };
+            self.code[pc as usize / 64] |= 1 << (pc % 64);
+            instructions.push(instruction);
+            if ends_block(instruction) {
+                break;
+            }
+            pc = pc.wrapping_add(instruction.bytes as u16);
+        }
+        if instructions.is_empty() {
+            return None;
+        }
+
+        let block: Block = instructions.into();
+        self.blocks.insert(start, block.clone());
+        Some(block)
+    }
+}
+
+// anything that can leave PC somewhere other than the next instruction
+fn ends_block(instruction: &Instruction) -> bool {
+    instruction.addressing_mode == AddressingMode::Relative
+        || matches!(instruction.mnemonic, "JMP" | "JSR" | "RTS" | "RTI" | "BRK")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory_with(program: &[u8]) -> Vec<u8> {
+        let mut memory = vec![0; MEMORY_SIZE];
+        memory[0x8000..0x8000 + program.len()].copy_from_slice(program);
+        memory
+    }
+
+    #[test]
+    fn test_block_runs_to_the_branch() {
+        // INX; CPX #$05; BNE -5; NOP
+        let memory = memory_with(&[0xE8, 0xE0, 0x05, 0xD0, 0xFB, 0xEA]);
+        let mut cache = BlockCache::new();
+
+        let opcodes: Vec<u8> = [0x8000, 0x8001, 0x8003]
+            .into_iter()
+            .map(|pc| cache.next(pc, &memory).unwrap().opcode)
+            .collect();
+        assert_eq!(opcodes, vec![0xE8, 0xE0, 0xD0]);
+        assert_eq!(cache.blocks.len(), 1);
+
+        // falling through the branch starts a new block, going round again reuses the first
+        assert_eq!(cache.next(0x8005, &memory).unwrap().opcode, 0xEA);
+        assert_eq!(cache.next(0x8000, &memory).unwrap().opcode, 0xE8);
+        assert_eq!(cache.blocks.len(), 2);
+    }
+
+    #[test]
+    fn test_writes_over_opcodes_invalidate() {
+        let mut memory = memory_with(&[0xE8, 0xE8, 0x00]);
+        let mut cache = BlockCache::new();
+        cache.next(0x8000, &memory);
+
+        // $8003 is past the end of the block
+        cache.invalidate(0x8003);
+        assert_eq!(cache.blocks.len(), 1);
+
+        memory[0x8001] = 0xC8;
+        cache.invalidate(0x8001);
+        assert_eq!(cache.blocks.len(), 0);
+        assert_eq!(cache.next(0x8001, &memory).unwrap().mnemonic, "INY");
+    }
+
+    #[test]
+    fn test_unknown_opcode_is_not_cached() {
+        let memory = memory_with(&[0x02]);
+        let mut cache = BlockCache::new();
+        assert_eq!(cache.next(0x8000, &memory), None);
+        assert_eq!(cache.blocks.len(), 0);
+    }
+}
@@ -1,5 +1,3 @@
-use std::collections::HashMap;
-
 use crate::cpu::AddressingMode;
 use lazy_static::lazy_static;
 
@@ -10,6 +8,8 @@ pub struct Instruction {
     pub bytes: u8,
     pub cycles: u8,
     pub addressing_mode: AddressingMode,
+    // false for the undocumented opcodes
+    pub official: bool,
 }
 
 impl Instruction {
@@ -26,6 +26,20 @@ impl Instruction {
             bytes,
             cycles,
             addressing_mode,
+            official: true,
+        }
+    }
+
+    pub fn unofficial(
+        opcode: u8,
+        mnemonic: &'static str,
+        bytes: u8,
+        cycles: u8,
+        addressing_mode: AddressingMode,
+    ) -> Self {
+        Self {
+            official: false,
+            ..Self::new(opcode, mnemonic, bytes, cycles, addressing_mode)
         }
     }
 }
@@ -78,6 +92,8 @@ lazy_static! {
         Instruction::new(0xA8, "TAY", 1, 2, AddressingMode::Implicit),
 
         Instruction::new(0x98, "TYA", 1, 2, AddressingMode::Implicit),
+        Instruction::new(0xBA, "TSX", 1, 2, AddressingMode::Implicit),
+        Instruction::new(0x9A, "TXS", 1, 2, AddressingMode::Implicit),
 
         // Arithmetic
         Instruction::new(0x69, "ADC", 2, 2, AddressingMode::Immediate),
@@ -172,29 +188,166 @@ lazy_static! {
         Instruction::new(0x24, "BIT", 2, 3, AddressingMode::ZeroPage),
         Instruction::new(0x2C, "BIT", 3, 4, AddressingMode::Absolute),
 
+        // Compare
+        Instruction::new(0xC9, "CMP", 2, 2, AddressingMode::Immediate),
+        Instruction::new(0xC5, "CMP", 2, 3, AddressingMode::ZeroPage),
+        Instruction::new(0xD5, "CMP", 2, 4, AddressingMode::ZeroPageX),
+        Instruction::new(0xCD, "CMP", 3, 4, AddressingMode::Absolute),
+        Instruction::new(0xDD, "CMP", 3, 4 /* 5 if page crossed */, AddressingMode::AbsoluteX),
+        Instruction::new(0xD9, "CMP", 3, 4 /* 5 if page crossed */, AddressingMode::AbsoluteY),
+        Instruction::new(0xC1, "CMP", 2, 6, AddressingMode::IndirectX),
+        Instruction::new(0xD1, "CMP", 2, 5 /* 6 if page crossed */, AddressingMode::IndirectY),
+
+        Instruction::new(0xE0, "CPX", 2, 2, AddressingMode::Immediate),
+        Instruction::new(0xE4, "CPX", 2, 3, AddressingMode::ZeroPage),
+        Instruction::new(0xEC, "CPX", 3, 4, AddressingMode::Absolute),
+
+        Instruction::new(0xC0, "CPY", 2, 2, AddressingMode::Immediate),
+        Instruction::new(0xC4, "CPY", 2, 3, AddressingMode::ZeroPage),
+        Instruction::new(0xCC, "CPY", 3, 4, AddressingMode::Absolute),
+
+        // Flags
+        Instruction::new(0x18, "CLC", 1, 2, AddressingMode::Implicit),
+        Instruction::new(0x38, "SEC", 1, 2, AddressingMode::Implicit),
+        Instruction::new(0x58, "CLI", 1, 2, AddressingMode::Implicit),
+        Instruction::new(0x78, "SEI", 1, 2, AddressingMode::Implicit),
+        Instruction::new(0xD8, "CLD", 1, 2, AddressingMode::Implicit),
+        Instruction::new(0xF8, "SED", 1, 2, AddressingMode::Implicit),
+        Instruction::new(0xB8, "CLV", 1, 2, AddressingMode::Implicit),
+
+        // Stack
+        Instruction::new(0x48, "PHA", 1, 3, AddressingMode::Implicit),
+        Instruction::new(0x68, "PLA", 1, 4, AddressingMode::Implicit),
+        Instruction::new(0x08, "PHP", 1, 3, AddressingMode::Implicit),
+        Instruction::new(0x28, "PLP", 1, 4, AddressingMode::Implicit),
+
+        // Branch
+        Instruction::new(0x90, "BCC", 2, 2 /* 3 if taken, 4 if to a new page */, AddressingMode::Relative),
+        Instruction::new(0xB0, "BCS", 2, 2 /* 3 if taken, 4 if to a new page */, AddressingMode::Relative),
+        Instruction::new(0xF0, "BEQ", 2, 2 /* 3 if taken, 4 if to a new page */, AddressingMode::Relative),
+        Instruction::new(0xD0, "BNE", 2, 2 /* 3 if taken, 4 if to a new page */, AddressingMode::Relative),
+        Instruction::new(0x30, "BMI", 2, 2 /* 3 if taken, 4 if to a new page */, AddressingMode::Relative),
+        Instruction::new(0x10, "BPL", 2, 2 /* 3 if taken, 4 if to a new page */, AddressingMode::Relative),
+        Instruction::new(0x50, "BVC", 2, 2 /* 3 if taken, 4 if to a new page */, AddressingMode::Relative),
+        Instruction::new(0x70, "BVS", 2, 2 /* 3 if taken, 4 if to a new page */, AddressingMode::Relative),
+
         // Jump
+        Instruction::new(0x4C, "JMP", 3, 3, AddressingMode::Absolute),
+        Instruction::new(0x6C, "JMP", 3, 5, AddressingMode::Indirect),
+        Instruction::new(0x20, "JSR", 3, 6, AddressingMode::Absolute),
+        Instruction::new(0x60, "RTS", 1, 6, AddressingMode::Implicit),
         Instruction::new(0x00, "BRK", 1, 7, AddressingMode::Implicit),
+        Instruction::new(0x40, "RTI", 1, 6, AddressingMode::Implicit),
         // Instruction::new(0x00, "BRK", 2, 7, AddressingMode::Immediate),
+
+        // Other
+        Instruction::new(0xEA, "NOP", 1, 2, AddressingMode::Implicit),
+
+        // Unofficial
+        // NOPs; the ones with a memory operand still read it
+        Instruction::unofficial(0x1A, "NOP", 1, 2, AddressingMode::Implicit),
+        Instruction::unofficial(0x3A, "NOP", 1, 2, AddressingMode::Implicit),
+        Instruction::unofficial(0x5A, "NOP", 1, 2, AddressingMode::Implicit),
+        Instruction::unofficial(0x7A, "NOP", 1, 2, AddressingMode::Implicit),
+        Instruction::unofficial(0xDA, "NOP", 1, 2, AddressingMode::Implicit),
+        Instruction::unofficial(0xFA, "NOP", 1, 2, AddressingMode::Implicit),
+        Instruction::unofficial(0x80, "NOP", 2, 2, AddressingMode::Immediate),
+        Instruction::unofficial(0x82, "NOP", 2, 2, AddressingMode::Immediate),
+        Instruction::unofficial(0x89, "NOP", 2, 2, AddressingMode::Immediate),
+        Instruction::unofficial(0xC2, "NOP", 2, 2, AddressingMode::Immediate),
+        Instruction::unofficial(0xE2, "NOP", 2, 2, AddressingMode::Immediate),
+        Instruction::unofficial(0x04, "NOP", 2, 3, AddressingMode::ZeroPage),
+        Instruction::unofficial(0x44, "NOP", 2, 3, AddressingMode::ZeroPage),
+        Instruction::unofficial(0x64, "NOP", 2, 3, AddressingMode::ZeroPage),
+        Instruction::unofficial(0x14, "NOP", 2, 4, AddressingMode::ZeroPageX),
+        Instruction::unofficial(0x34, "NOP", 2, 4, AddressingMode::ZeroPageX),
+        Instruction::unofficial(0x54, "NOP", 2, 4, AddressingMode::ZeroPageX),
+        Instruction::unofficial(0x74, "NOP", 2, 4, AddressingMode::ZeroPageX),
+        Instruction::unofficial(0xD4, "NOP", 2, 4, AddressingMode::ZeroPageX),
+        Instruction::unofficial(0xF4, "NOP", 2, 4, AddressingMode::ZeroPageX),
+        Instruction::unofficial(0x0C, "NOP", 3, 4, AddressingMode::Absolute),
+        Instruction::unofficial(0x1C, "NOP", 3, 4 /* 5 if page crossed */, AddressingMode::AbsoluteX),
+        Instruction::unofficial(0x3C, "NOP", 3, 4 /* 5 if page crossed */, AddressingMode::AbsoluteX),
+        Instruction::unofficial(0x5C, "NOP", 3, 4 /* 5 if page crossed */, AddressingMode::AbsoluteX),
+        Instruction::unofficial(0x7C, "NOP", 3, 4 /* 5 if page crossed */, AddressingMode::AbsoluteX),
+        Instruction::unofficial(0xDC, "NOP", 3, 4 /* 5 if page crossed */, AddressingMode::AbsoluteX),
+        Instruction::unofficial(0xFC, "NOP", 3, 4 /* 5 if page crossed */, AddressingMode::AbsoluteX),
     ];
 
-    pub static ref INSTRUCTION_MAP: HashMap<u8, &'static Instruction> = {
-        let mut instruction_map = HashMap::new();
+    // indexed by opcode, so the hot path in Cpu::step decodes without hashing
+    pub static ref INSTRUCTION_TABLE: [Option<&'static Instruction>; 256] = {
+        let mut table = [None; 256];
         for instruction in CPU_INSTRUCTIONS.iter() {
-            instruction_map.insert(instruction.opcode, instruction);
+            table[instruction.opcode as usize] = Some(instruction);
         }
-        instruction_map
+        table
     };
 }
 
+// the public face of the tables above, for assemblers, disassemblers and analysis tools; cycle
+// counts are the base cost, without page-crossing or branch penalties
+
+pub fn lookup(opcode: u8) -> Option<&'static Instruction> {
+    INSTRUCTION_TABLE[opcode as usize]
+}
+
+// every opcode of a mnemonic, in any case, one per addressing mode
+pub fn opcodes_for(mnemonic: &str) -> impl Iterator<Item = &'static Instruction> + '_ {
+    instructions().filter(move |instruction| instruction.mnemonic.eq_ignore_ascii_case(mnemonic))
+}
+
+// the whole table in opcode order
+pub fn instructions() -> impl Iterator<Item = &'static Instruction> {
+    INSTRUCTION_TABLE.iter().flatten().copied()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_table_holds_every_instruction_once() {
+        for instruction in CPU_INSTRUCTIONS.iter() {
+            assert_eq!(lookup(instruction.opcode), Some(instruction));
+        }
+        assert_eq!(instructions().count(), CPU_INSTRUCTIONS.len());
+    }
+
+    #[test]
+    fn test_query_api() {
+        assert_eq!(lookup(0xA9).unwrap().mnemonic, "LDA");
+        assert_eq!(lookup(0x02), None);
+
+        let modes: Vec<_> = opcodes_for("stx")
+            .map(|instruction| (instruction.opcode, instruction.addressing_mode))
+            .collect();
+        assert_eq!(
+            modes,
+            vec![
+                (0x86, AddressingMode::ZeroPage),
+                (0x8E, AddressingMode::Absolute),
+                (0x96, AddressingMode::ZeroPageY),
+            ]
+        );
+
+        let all: Vec<_> = instructions().collect();
+        assert_eq!(all.len(), CPU_INSTRUCTIONS.len());
+        assert!(all.windows(2).all(|pair| pair[0].opcode < pair[1].opcode));
+        assert!(lookup(0xEA).unwrap().official);
+        assert!(!lookup(0x1C).unwrap().official);
+        assert_eq!(
+            all.iter()
+                .filter(|instruction| !instruction.official)
+                .count(),
+            27
+        );
+    }
+
     #[test]
     fn test_get_instruction() {
         assert_eq!(
-            INSTRUCTION_MAP.get(&0xA9),
-            Some(&&Instruction::new(
+            lookup(0xA9),
+            Some(&Instruction::new(
                 0xA9,
                 "LDA",
                 2,
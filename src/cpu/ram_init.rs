@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RamInit {
+    #[default]
+    Zero,
+    Ones,
+    AlternatingPages,
+    Random(u64),
+}
+
+impl RamInit {
+    pub fn fill(&self, ram: &mut [u8]) {
+        match self {
+            RamInit::Zero => ram.fill(0x00),
+            RamInit::Ones => ram.fill(0xFF),
+            RamInit::AlternatingPages => {
+                for (page, bytes) in ram.chunks_mut(0x100).enumerate() {
+                    bytes.fill(if page % 2 == 0 { 0x00 } else { 0xFF });
+                }
+            }
+            RamInit::Random(seed) => {
+                let mut state = *seed;
+                for chunk in ram.chunks_mut(8) {
+                    let bytes = splitmix64(&mut state).to_le_bytes();
+                    chunk.copy_from_slice(&bytes[..chunk.len()]);
+                }
+            }
+        }
+    }
+}
+
+// splitmix64, chosen because it is tiny, fast and well distributed for any seed (including 0)
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alternating_pages() {
+        let mut ram = [0x55; 0x800];
+        RamInit::AlternatingPages.fill(&mut ram);
+        assert!(ram[0x000..0x100].iter().all(|&b| b == 0x00));
+        assert!(ram[0x100..0x200].iter().all(|&b| b == 0xFF));
+        assert!(ram[0x700..0x800].iter().all(|&b| b == 0xFF));
+    }
+
+    #[test]
+    fn test_random_is_deterministic_per_seed() {
+        let mut first = [0; 0x800];
+        let mut second = [0; 0x800];
+        let mut other = [0; 0x800];
+        RamInit::Random(42).fill(&mut first);
+        RamInit::Random(42).fill(&mut second);
+        RamInit::Random(43).fill(&mut other);
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+    }
+}
@@ -0,0 +1,416 @@
+pub mod call_stack;
+pub mod condition;
+pub mod heatmap;
+mod history;
+pub mod stack;
+pub mod strict;
+
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
+
+use crate::cpu::Registers;
+use history::{Entry, History};
+
+pub use call_stack::{CallStack, Frame, FrameKind, StackMismatch};
+pub use condition::{Condition, ConditionError};
+pub use heatmap::{HeatCounts, Heatmap};
+pub use stack::{inspect_stack, StackEntry};
+pub use strict::{StrictAction, StrictMode, UninitializedRead};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub address: u16,
+    pub enabled: bool,
+    pub condition: Option<Condition>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    // a write that actually changes the stored value
+    Change,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct WatchpointId(usize);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub id: WatchpointId,
+    pub range: RangeInclusive<u16>,
+    pub kind: WatchKind,
+    pub enabled: bool,
+    pub condition: Option<Condition>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct WatchpointHit {
+    pub id: WatchpointId,
+    pub address: u16,
+    pub value: u8,
+    pub kind: WatchKind,
+    // address of the instruction that made the access
+    pub pc: u16,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Debugger {
+    breakpoints: BTreeMap<u16, Breakpoint>,
+    // the breakpoint we just paused on, so resuming doesn't immediately pause again
+    resume_from: Option<u16>,
+
+    next_watchpoint_id: usize,
+    watchpoints: Vec<Watchpoint>,
+    instruction_pc: u16,
+    // every matching access of the current instruction; conditions are checked once it finishes
+    watchpoint_hits: Vec<WatchpointHit>,
+
+    call_stack: CallStack,
+    history: History,
+    heatmap: Option<Heatmap>,
+    strict: Option<StrictMode>,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints
+            .insert(
+                address,
+                Breakpoint {
+                    address,
+                    enabled: true,
+                    condition: None,
+                },
+            )
+            .is_none()
+    }
+
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.remove(&address).is_some()
+    }
+
+    pub fn set_breakpoint_enabled(&mut self, address: u16, enabled: bool) -> bool {
+        match self.breakpoints.get_mut(&address) {
+            Some(breakpoint) => {
+                breakpoint.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // the breakpoint only pauses when the condition holds; None makes it unconditional
+    pub fn set_breakpoint_condition(&mut self, address: u16, condition: Option<Condition>) -> bool {
+        match self.breakpoints.get_mut(&address) {
+            Some(breakpoint) => {
+                breakpoint.condition = condition;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = &Breakpoint> {
+        self.breakpoints.values()
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    pub fn add_watchpoint(&mut self, range: RangeInclusive<u16>, kind: WatchKind) -> WatchpointId {
+        let id = WatchpointId(self.next_watchpoint_id);
+        self.next_watchpoint_id += 1;
+
+        self.watchpoints.push(Watchpoint {
+            id,
+            range,
+            kind,
+            enabled: true,
+            condition: None,
+        });
+        id
+    }
+
+    pub fn remove_watchpoint(&mut self, id: WatchpointId) -> bool {
+        let before = self.watchpoints.len();
+        self.watchpoints.retain(|watchpoint| watchpoint.id != id);
+        self.watchpoints.len() != before
+    }
+
+    pub fn set_watchpoint_enabled(&mut self, id: WatchpointId, enabled: bool) -> bool {
+        match self
+            .watchpoints
+            .iter_mut()
+            .find(|watchpoint| watchpoint.id == id)
+        {
+            Some(watchpoint) => {
+                watchpoint.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn set_watchpoint_condition(
+        &mut self,
+        id: WatchpointId,
+        condition: Option<Condition>,
+    ) -> bool {
+        match self
+            .watchpoints
+            .iter_mut()
+            .find(|watchpoint| watchpoint.id == id)
+        {
+            Some(watchpoint) => {
+                watchpoint.condition = condition;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn watchpoints(&self) -> impl Iterator<Item = &Watchpoint> {
+        self.watchpoints.iter()
+    }
+
+    pub fn clear_watchpoints(&mut self) {
+        self.watchpoints.clear();
+    }
+
+    pub fn call_stack(&self) -> &CallStack {
+        &self.call_stack
+    }
+
+    pub fn call_stack_mut(&mut self) -> &mut CallStack {
+        &mut self.call_stack
+    }
+
+    // keep undo information for the last `capacity` instructions so Cpu::step_back can
+    // reverse them; 0 turns it off
+    pub fn set_history_capacity(&mut self, capacity: usize) {
+        self.history.set_capacity(capacity);
+    }
+
+    pub fn history_capacity(&self) -> usize {
+        self.history.capacity()
+    }
+
+    // how many instructions can currently be stepped back
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+    }
+
+    // collect access counts into `heatmap`, or stop collecting with None
+    pub fn set_heatmap(&mut self, heatmap: Option<Heatmap>) {
+        self.heatmap = heatmap;
+    }
+
+    pub fn heatmap(&self) -> Option<&Heatmap> {
+        self.heatmap.as_ref()
+    }
+
+    pub fn heatmap_mut(&mut self) -> Option<&mut Heatmap> {
+        self.heatmap.as_mut()
+    }
+
+    // track which RAM has been written and report reads of the rest, or stop tracking with None
+    pub fn set_strict_mode(&mut self, strict: Option<StrictMode>) {
+        self.strict = strict;
+    }
+
+    pub fn strict_mode(&self) -> Option<&StrictMode> {
+        self.strict.as_ref()
+    }
+
+    pub fn strict_mode_mut(&mut self) -> Option<&mut StrictMode> {
+        self.strict.as_mut()
+    }
+
+    pub(crate) fn take_strict_break(&mut self) -> Option<UninitializedRead> {
+        self.strict.as_mut().and_then(StrictMode::take_break)
+    }
+
+    pub(crate) fn pop_history(&mut self) -> Option<Entry> {
+        self.history.pop()
+    }
+
+    pub(crate) fn begin_instruction(&mut self, registers: Registers, cycles: u64) {
+        self.instruction_pc = registers.pc;
+        self.watchpoint_hits.clear();
+        self.history.begin(registers, cycles, &self.call_stack);
+        if let Some(heatmap) = &mut self.heatmap {
+            heatmap.record_execute(registers.pc);
+        }
+    }
+
+    // the first hit of the instruction whose watchpoint condition holds
+    pub(crate) fn take_watchpoint_hit<F>(&mut self, evaluate: F) -> Option<WatchpointHit>
+    where
+        F: Fn(&Condition) -> bool,
+    {
+        let hits = std::mem::take(&mut self.watchpoint_hits);
+        hits.into_iter().find(|hit| {
+            self.watchpoints
+                .iter()
+                .find(|watchpoint| watchpoint.id == hit.id)
+                .and_then(|watchpoint| watchpoint.condition.as_ref())
+                .is_none_or(&evaluate)
+        })
+    }
+
+    pub(crate) fn check_read(&mut self, address: u16, value: u8) {
+        if let Some(heatmap) = &mut self.heatmap {
+            heatmap.record_read(address);
+        }
+        if let Some(strict) = &mut self.strict {
+            strict.record_read(address, self.instruction_pc);
+        }
+        self.check_access(address, value, |kind| kind == WatchKind::Read);
+    }
+
+    pub(crate) fn check_write(&mut self, address: u16, old: u8, new: u8) {
+        self.history.record_write(address, old);
+        if let Some(heatmap) = &mut self.heatmap {
+            heatmap.record_write(address);
+        }
+        if let Some(strict) = &mut self.strict {
+            strict.record_write(address);
+        }
+        self.check_access(address, new, |kind| {
+            kind == WatchKind::Write || (kind == WatchKind::Change && old != new)
+        });
+    }
+
+    fn check_access<F>(&mut self, address: u16, value: u8, matches: F)
+    where
+        F: Fn(WatchKind) -> bool,
+    {
+        if self.watchpoints.is_empty() {
+            return;
+        }
+
+        let pc = self.instruction_pc;
+        self.watchpoint_hits.extend(
+            self.watchpoints
+                .iter()
+                .filter(|watchpoint| {
+                    watchpoint.enabled
+                        && watchpoint.range.contains(&address)
+                        && matches(watchpoint.kind)
+                })
+                .map(|watchpoint| WatchpointHit {
+                    id: watchpoint.id,
+                    address,
+                    value,
+                    kind: watchpoint.kind,
+                    pc,
+                }),
+        );
+    }
+
+    // true when execution should pause before the instruction at `pc`
+    pub(crate) fn check_breakpoint<F>(&mut self, pc: u16, evaluate: F) -> bool
+    where
+        F: Fn(&Condition) -> bool,
+    {
+        if self.resume_from.take() == Some(pc) {
+            return false;
+        }
+
+        let hit = self.breakpoints.get(&pc).is_some_and(|breakpoint| {
+            breakpoint.enabled && breakpoint.condition.as_ref().is_none_or(&evaluate)
+        });
+        if hit {
+            self.resume_from = Some(pc);
+        }
+        hit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_breakpoint_management() {
+        let mut debugger = Debugger::new();
+        assert!(debugger.add_breakpoint(0x8000));
+        assert!(!debugger.add_breakpoint(0x8000));
+        assert!(debugger.set_breakpoint_enabled(0x8000, false));
+        assert!(!debugger.set_breakpoint_enabled(0x9000, false));
+        assert_eq!(
+            debugger.breakpoints().collect::<Vec<_>>(),
+            vec![&Breakpoint {
+                address: 0x8000,
+                enabled: false,
+                condition: None,
+            }]
+        );
+        assert!(debugger.remove_breakpoint(0x8000));
+        assert!(!debugger.remove_breakpoint(0x8000));
+    }
+
+    #[test]
+    fn test_watchpoint_kinds() {
+        let mut debugger = Debugger::new();
+        let read = debugger.add_watchpoint(0x10..=0x1F, WatchKind::Read);
+        let change = debugger.add_watchpoint(0x20..=0x20, WatchKind::Change);
+
+        debugger.begin_instruction(
+            Registers {
+                pc: 0x8000,
+                ..Registers::default()
+            },
+            0,
+        );
+        debugger.check_write(0x10, 0x00, 0x01);
+        debugger.check_read(0x20, 0x01);
+        debugger.check_write(0x20, 0x01, 0x01);
+        assert_eq!(debugger.take_watchpoint_hit(|_| true), None);
+
+        debugger.check_read(0x1F, 0x42);
+        assert_eq!(
+            debugger.take_watchpoint_hit(|_| true),
+            Some(WatchpointHit {
+                id: read,
+                address: 0x1F,
+                value: 0x42,
+                kind: WatchKind::Read,
+                pc: 0x8000,
+            })
+        );
+
+        debugger.set_watchpoint_enabled(read, false);
+        debugger.check_read(0x1F, 0x42);
+        debugger.check_write(0x20, 0x01, 0x02);
+        assert_eq!(
+            debugger.take_watchpoint_hit(|_| true).map(|hit| hit.id),
+            Some(change)
+        );
+    }
+
+    #[test]
+    fn test_check_breakpoint_resumes_once() {
+        let mut debugger = Debugger::new();
+        debugger.add_breakpoint(0x8000);
+
+        assert!(debugger.check_breakpoint(0x8000, |_| true));
+        assert!(!debugger.check_breakpoint(0x8000, |_| true));
+        assert!(!debugger.check_breakpoint(0x8002, |_| true));
+        assert!(debugger.check_breakpoint(0x8000, |_| true));
+
+        debugger.set_breakpoint_enabled(0x8000, false);
+        debugger.check_breakpoint(0x8000, |_| true);
+        assert!(!debugger.check_breakpoint(0x8000, |_| true));
+    }
+}
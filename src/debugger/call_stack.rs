@@ -0,0 +1,176 @@
+const JSR: u8 = 0x20;
+const RTS: u8 = 0x60;
+const RTI: u8 = 0x40;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FrameKind {
+    Subroutine,
+    Interrupt,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub kind: FrameKind,
+    // the JSR, or the instruction that was interrupted
+    pub caller: u16,
+    pub target: u16,
+    // SP once the return address (and status, for interrupts) has been pushed
+    pub sp: u8,
+}
+
+// the program moved the stack in a way the shadow stack didn't expect, e.g. popping a return
+// address with PLA or using RTS as a computed jump
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct StackMismatch {
+    pub pc: u16,
+    pub sp: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CallStack {
+    frames: Vec<Frame>,
+    mismatches: u64,
+    last_mismatch: Option<StackMismatch>,
+}
+
+impl CallStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // outermost call first
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn mismatches(&self) -> u64 {
+        self.mismatches
+    }
+
+    pub fn last_mismatch(&self) -> Option<StackMismatch> {
+        self.last_mismatch
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    // for whatever raises NMI/IRQ; JSR, RTS and RTI are picked up from the executed opcode
+    pub fn enter_interrupt(&mut self, caller: u16, target: u16, sp: u8) {
+        self.frames.push(Frame {
+            kind: FrameKind::Interrupt,
+            caller,
+            target,
+            sp,
+        });
+    }
+
+    // called after each instruction with the SP from before and after it
+    pub(crate) fn update(
+        &mut self,
+        opcode: u8,
+        pc: u16,
+        next_pc: u16,
+        sp_before: u8,
+        sp_after: u8,
+    ) {
+        match opcode {
+            JSR => self.frames.push(Frame {
+                kind: FrameKind::Subroutine,
+                caller: pc,
+                target: next_pc,
+                sp: sp_after,
+            }),
+            RTS => self.leave(FrameKind::Subroutine, pc, sp_before),
+            RTI => self.leave(FrameKind::Interrupt, pc, sp_before),
+            _ => {
+                // SP above a frame means its return address was popped without returning
+                while self.frames.last().is_some_and(|frame| sp_after > frame.sp) {
+                    self.frames.pop();
+                    self.mismatch(pc, sp_after);
+                }
+            }
+        }
+    }
+
+    fn leave(&mut self, kind: FrameKind, pc: u16, sp: u8) {
+        match self.frames.iter().rposition(|frame| frame.sp == sp) {
+            Some(i) => {
+                if i != self.frames.len() - 1 || self.frames[i].kind != kind {
+                    self.mismatch(pc, sp);
+                }
+                self.frames.truncate(i);
+            }
+            // a return with no matching call, like an RTS jump table
+            None => self.mismatch(pc, sp),
+        }
+    }
+
+    fn mismatch(&mut self, pc: u16, sp: u8) {
+        self.mismatches += 1;
+        self.last_mismatch = Some(StackMismatch { pc, sp });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nested_calls() {
+        let mut stack = CallStack::new();
+        stack.update(JSR, 0x8000, 0x9000, 0xFD, 0xFB);
+        stack.update(JSR, 0x9000, 0xA000, 0xFB, 0xF9);
+        assert_eq!(stack.depth(), 2);
+        assert_eq!(stack.frames()[1].caller, 0x9000);
+        assert_eq!(stack.frames()[1].target, 0xA000);
+
+        stack.update(0x48, 0xA000, 0xA001, 0xF9, 0xF8);
+        stack.update(0x68, 0xA001, 0xA002, 0xF8, 0xF9);
+        stack.update(RTS, 0xA002, 0x9003, 0xF9, 0xFB);
+        stack.update(RTS, 0x9003, 0x8003, 0xFB, 0xFD);
+        assert_eq!(stack.depth(), 0);
+        assert_eq!(stack.mismatches(), 0);
+    }
+
+    #[test]
+    fn test_interrupt_frames() {
+        let mut stack = CallStack::new();
+        stack.update(JSR, 0x8000, 0x9000, 0xFD, 0xFB);
+        stack.enter_interrupt(0x9000, 0xC000, 0xF8);
+        assert_eq!(stack.frames()[1].kind, FrameKind::Interrupt);
+
+        stack.update(RTI, 0xC000, 0x9000, 0xF8, 0xFB);
+        assert_eq!(stack.depth(), 1);
+        assert_eq!(stack.mismatches(), 0);
+    }
+
+    #[test]
+    fn test_manual_stack_manipulation() {
+        // PLA PLA drops the return address of the inner call
+        let mut stack = CallStack::new();
+        stack.update(JSR, 0x8000, 0x9000, 0xFD, 0xFB);
+        stack.update(JSR, 0x9000, 0xA000, 0xFB, 0xF9);
+        stack.update(0x68, 0xA000, 0xA001, 0xF9, 0xFA);
+        assert_eq!(stack.depth(), 1);
+        assert_eq!(
+            stack.last_mismatch(),
+            Some(StackMismatch {
+                pc: 0xA000,
+                sp: 0xFA
+            })
+        );
+
+        // RTS used as a jump after pushing an address by hand
+        let mut stack = CallStack::new();
+        stack.update(0x48, 0x8000, 0x8001, 0xFD, 0xFC);
+        stack.update(0x48, 0x8001, 0x8002, 0xFC, 0xFB);
+        stack.update(RTS, 0x8002, 0x9000, 0xFB, 0xFD);
+        assert_eq!(stack.depth(), 0);
+        assert_eq!(stack.mismatches(), 1);
+    }
+}
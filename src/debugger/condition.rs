@@ -0,0 +1,459 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::cpu::Cpu;
+use crate::trace::ppu_position;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("at {position}: {message}")]
+pub struct ConditionError {
+    // byte offset into the expression
+    pub position: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Variable {
+    A,
+    X,
+    Y,
+    P,
+    Sp,
+    Pc,
+    Cycles,
+    Scanline,
+    Dot,
+    Flag(u8),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum UnaryOp {
+    Not,
+    Negate,
+    Complement,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum BinaryOp {
+    Or,
+    And,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    ShiftLeft,
+    ShiftRight,
+    Add,
+    Subtract,
+    Multiply,
+}
+
+impl BinaryOp {
+    // higher binds tighter, as in C
+    fn precedence(self) -> u8 {
+        match self {
+            BinaryOp::Or => 1,
+            BinaryOp::And => 2,
+            BinaryOp::BitOr => 3,
+            BinaryOp::BitXor => 4,
+            BinaryOp::BitAnd => 5,
+            BinaryOp::Equal | BinaryOp::NotEqual => 6,
+            BinaryOp::Less | BinaryOp::LessEqual | BinaryOp::Greater | BinaryOp::GreaterEqual => 7,
+            BinaryOp::ShiftLeft | BinaryOp::ShiftRight => 8,
+            BinaryOp::Add | BinaryOp::Subtract => 9,
+            BinaryOp::Multiply => 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Number(i64),
+    Variable(Variable),
+    // [addr] reads a byte, {addr} a little-endian word
+    Byte(Box<Expr>),
+    Word(Box<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+// e.g. `A == 0x20 && scanline > 200 || [$00FE] != 0`; true when the result is non-zero
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    source: String,
+    expr: Expr,
+}
+
+impl Condition {
+    pub fn parse(source: &str) -> Result<Self, ConditionError> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens: &tokens,
+            index: 0,
+            end: source.len(),
+        };
+        let expr = parser.expression(0)?;
+        if let Some((position, token)) = parser.peek() {
+            return Err(ConditionError {
+                position,
+                message: format!("unexpected '{}'", token),
+            });
+        }
+
+        Ok(Self {
+            source: source.to_string(),
+            expr,
+        })
+    }
+
+    pub fn evaluate(&self, cpu: &Cpu) -> bool {
+        evaluate(&self.expr, cpu) != 0
+    }
+}
+
+impl FromStr for Condition {
+    type Err = ConditionError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        Self::parse(source)
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+fn evaluate(expr: &Expr, cpu: &Cpu) -> i64 {
+    let registers = cpu.registers();
+    let peek = |addr: i64| cpu.mem_peek(addr as u16) as i64;
+
+    match expr {
+        Expr::Number(n) => *n,
+        Expr::Variable(variable) => match variable {
+            Variable::A => registers.a as i64,
+            Variable::X => registers.x as i64,
+            Variable::Y => registers.y as i64,
+            Variable::P => registers.status as i64,
+            Variable::Sp => registers.sp as i64,
+            Variable::Pc => registers.pc as i64,
+            Variable::Cycles => cpu.cycles() as i64,
+            Variable::Scanline => ppu_position(cpu.cycles()).0 as i64,
+            Variable::Dot => ppu_position(cpu.cycles()).1 as i64,
+            Variable::Flag(mask) => (registers.status & mask != 0) as i64,
+        },
+        Expr::Byte(addr) => peek(evaluate(addr, cpu)),
+        Expr::Word(addr) => {
+            let addr = evaluate(addr, cpu);
+            peek(addr) | peek((addr as u16).wrapping_add(1) as i64) << 8
+        }
+        Expr::Unary(op, operand) => {
+            let value = evaluate(operand, cpu);
+            match op {
+                UnaryOp::Not => (value == 0) as i64,
+                UnaryOp::Negate => value.wrapping_neg(),
+                UnaryOp::Complement => !value,
+            }
+        }
+        Expr::Binary(BinaryOp::Or, lhs, rhs) => {
+            (evaluate(lhs, cpu) != 0 || evaluate(rhs, cpu) != 0) as i64
+        }
+        Expr::Binary(BinaryOp::And, lhs, rhs) => {
+            (evaluate(lhs, cpu) != 0 && evaluate(rhs, cpu) != 0) as i64
+        }
+        Expr::Binary(op, lhs, rhs) => {
+            let (lhs, rhs) = (evaluate(lhs, cpu), evaluate(rhs, cpu));
+            match op {
+                BinaryOp::BitOr => lhs | rhs,
+                BinaryOp::BitXor => lhs ^ rhs,
+                BinaryOp::BitAnd => lhs & rhs,
+                BinaryOp::Equal => (lhs == rhs) as i64,
+                BinaryOp::NotEqual => (lhs != rhs) as i64,
+                BinaryOp::Less => (lhs < rhs) as i64,
+                BinaryOp::LessEqual => (lhs <= rhs) as i64,
+                BinaryOp::Greater => (lhs > rhs) as i64,
+                BinaryOp::GreaterEqual => (lhs >= rhs) as i64,
+                BinaryOp::ShiftLeft => lhs.wrapping_shl(rhs as u32),
+                BinaryOp::ShiftRight => lhs.wrapping_shr(rhs as u32),
+                BinaryOp::Add => lhs.wrapping_add(rhs),
+                BinaryOp::Subtract => lhs.wrapping_sub(rhs),
+                BinaryOp::Multiply => lhs.wrapping_mul(rhs),
+                BinaryOp::Or | BinaryOp::And => unreachable!(),
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(i64),
+    Identifier(String),
+    Symbol(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Identifier(name) => write!(f, "{}", name),
+            Token::Symbol(symbol) => write!(f, "{}", symbol),
+        }
+    }
+}
+
+// longest first so `<=` isn't read as `<`
+const SYMBOLS: [&str; 24] = [
+    "||", "&&", "==", "!=", "<=", ">=", "<<", ">>", "<", ">", "|", "^", "&", "+", "-", "*", "!",
+    "~", "(", ")", "[", "]", "{", "}",
+];
+
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, ConditionError> {
+    let mut tokens = Vec::new();
+    let mut rest = source;
+
+    while let Some(start) = rest.find(|c: char| !c.is_whitespace()) {
+        rest = &rest[start..];
+        let position = source.len() - rest.len();
+        let error = |message: String| ConditionError { position, message };
+
+        let (token, len) = if let Some(symbol) = SYMBOLS.iter().find(|s| rest.starts_with(**s)) {
+            (Token::Symbol(symbol), symbol.len())
+        } else if rest.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '$' || c == '_') {
+            let len = rest[1..]
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .map_or(rest.len(), |len| len + 1);
+            let word = &rest[..len];
+
+            let number = if let Some(hex) = word.strip_prefix('$') {
+                Some(i64::from_str_radix(hex, 16))
+            } else if let Some(hex) = word.strip_prefix("0x").or(word.strip_prefix("0X")) {
+                Some(i64::from_str_radix(hex, 16))
+            } else if word.starts_with(|c: char| c.is_ascii_digit()) {
+                Some(word.parse())
+            } else {
+                None
+            };
+
+            match number {
+                Some(Ok(n)) => (Token::Number(n), len),
+                Some(Err(_)) => return Err(error(format!("invalid number '{}'", word))),
+                None => (Token::Identifier(word.to_ascii_lowercase()), len),
+            }
+        } else {
+            let c = rest.chars().next().unwrap_or_default();
+            return Err(error(format!("unexpected character '{}'", c)));
+        };
+
+        tokens.push((position, token));
+        rest = &rest[len..];
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [(usize, Token)],
+    index: usize,
+    end: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<(usize, &Token)> {
+        self.tokens
+            .get(self.index)
+            .map(|(position, token)| (*position, token))
+    }
+
+    fn next(&mut self) -> Result<(usize, &Token), ConditionError> {
+        let token = self.tokens.get(self.index).ok_or(ConditionError {
+            position: self.end,
+            message: "unexpected end of expression".to_string(),
+        })?;
+        self.index += 1;
+        Ok((token.0, &token.1))
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), ConditionError> {
+        match self.next()? {
+            (_, Token::Symbol(found)) if *found == symbol => Ok(()),
+            (position, token) => Err(ConditionError {
+                position,
+                message: format!("expected '{}', found '{}'", symbol, token),
+            }),
+        }
+    }
+
+    fn expression(&mut self, min_precedence: u8) -> Result<Expr, ConditionError> {
+        let mut lhs = self.unary()?;
+
+        while let Some(op) = self.peek().and_then(|(_, token)| binary_op(token)) {
+            if op.precedence() <= min_precedence {
+                break;
+            }
+            self.index += 1;
+            let rhs = self.expression(op.precedence())?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, ConditionError> {
+        let (position, token) = self.next()?;
+        let expr = match token {
+            Token::Number(n) => Expr::Number(*n),
+            Token::Identifier(name) => Expr::Variable(variable(name).ok_or(ConditionError {
+                position,
+                message: format!("unknown variable '{}'", name),
+            })?),
+            Token::Symbol("!") => Expr::Unary(UnaryOp::Not, Box::new(self.unary()?)),
+            Token::Symbol("-") => Expr::Unary(UnaryOp::Negate, Box::new(self.unary()?)),
+            Token::Symbol("~") => Expr::Unary(UnaryOp::Complement, Box::new(self.unary()?)),
+            Token::Symbol("(") => {
+                let expr = self.expression(0)?;
+                self.expect(")")?;
+                expr
+            }
+            Token::Symbol("[") => {
+                let expr = Expr::Byte(Box::new(self.expression(0)?));
+                self.expect("]")?;
+                expr
+            }
+            Token::Symbol("{") => {
+                let expr = Expr::Word(Box::new(self.expression(0)?));
+                self.expect("}")?;
+                expr
+            }
+            token => {
+                return Err(ConditionError {
+                    position,
+                    message: format!("unexpected '{}'", token),
+                })
+            }
+        };
+        Ok(expr)
+    }
+}
+
+fn binary_op(token: &Token) -> Option<BinaryOp> {
+    let Token::Symbol(symbol) = token else {
+        return None;
+    };
+    let op = match *symbol {
+        "||" => BinaryOp::Or,
+        "&&" => BinaryOp::And,
+        "|" => BinaryOp::BitOr,
+        "^" => BinaryOp::BitXor,
+        "&" => BinaryOp::BitAnd,
+        "==" => BinaryOp::Equal,
+        "!=" => BinaryOp::NotEqual,
+        "<" => BinaryOp::Less,
+        "<=" => BinaryOp::LessEqual,
+        ">" => BinaryOp::Greater,
+        ">=" => BinaryOp::GreaterEqual,
+        "<<" => BinaryOp::ShiftLeft,
+        ">>" => BinaryOp::ShiftRight,
+        "+" => BinaryOp::Add,
+        "-" => BinaryOp::Subtract,
+        "*" => BinaryOp::Multiply,
+        _ => return None,
+    };
+    Some(op)
+}
+
+fn variable(name: &str) -> Option<Variable> {
+    let variable = match name {
+        "a" => Variable::A,
+        "x" => Variable::X,
+        "y" => Variable::Y,
+        "p" | "status" => Variable::P,
+        "sp" => Variable::Sp,
+        "pc" => Variable::Pc,
+        "cycles" => Variable::Cycles,
+        "scanline" => Variable::Scanline,
+        "dot" | "cycle" => Variable::Dot,
+        "n" => Variable::Flag(0x80),
+        "v" => Variable::Flag(0x40),
+        "d" => Variable::Flag(0x08),
+        "i" => Variable::Flag(0x04),
+        "z" => Variable::Flag(0x02),
+        "c" => Variable::Flag(0x01),
+        _ => return None,
+    };
+    Some(variable)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::Registers;
+
+    fn cpu() -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.set_registers(Registers {
+            a: 0x20,
+            x: 0x03,
+            status: 0x81,
+            pc: 0x8000,
+            ..Registers::default()
+        });
+        cpu.mem_write(0x00FE, 0x01);
+        cpu.mem_write(0x0010, 0x34);
+        cpu.mem_write(0x0011, 0x12);
+        cpu
+    }
+
+    fn eval(source: &str) -> bool {
+        Condition::parse(source).unwrap().evaluate(&cpu())
+    }
+
+    #[test]
+    fn test_registers_memory_and_flags() {
+        assert!(eval("A == 0x20"));
+        assert!(eval("a == $20 && x < 4"));
+        assert!(eval("[0x00FE] != 0"));
+        assert!(eval("[$FB + X] == 1"));
+        assert!(eval("{$10} == $1234"));
+        assert!(eval("n && c && !z"));
+        assert!(eval("pc >= $8000"));
+        assert!(!eval("A == 0x21"));
+    }
+
+    #[test]
+    fn test_precedence() {
+        assert!(eval("A == 0x20 && scanline > 200 || [0x00FE] != 0"));
+        assert!(!eval("A == 0x21 && (scanline > 200 || [0x00FE] != 0)"));
+        assert!(eval("1 + 2 * 3 == 7"));
+        assert!(eval("(P & 0x80) == 0x80"));
+        assert!(eval("-1 < 0 && ~0 == -1 && 1 << 4 == 16"));
+    }
+
+    #[test]
+    fn test_scanline_follows_cycles() {
+        let mut cpu = cpu();
+        let mut state = cpu.save_state();
+        // 114 cycles is 342 dots: the first dot of the second scanline
+        state.cycles = 114;
+        cpu.load_state(&state);
+
+        let condition = Condition::parse("scanline == 1 && dot == 1").unwrap();
+        assert!(condition.evaluate(&cpu));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |source| Condition::parse(source).unwrap_err();
+
+        assert_eq!(error("A ==").position, 4);
+        assert_eq!(error("A == q").message, "unknown variable 'q'");
+        assert_eq!(error("[A").message, "unexpected end of expression");
+        assert_eq!(error("A 1").position, 2);
+        assert_eq!(error("A = 1").position, 2);
+    }
+}
@@ -0,0 +1,141 @@
+const ADDRESS_SPACE: usize = 0x10000;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct HeatCounts {
+    pub reads: u64,
+    pub writes: u64,
+    pub executes: u64,
+}
+
+impl HeatCounts {
+    pub fn total(&self) -> u64 {
+        self.reads + self.writes + self.executes
+    }
+}
+
+// per-bucket access counts over the whole address space; opcode fetches count as both a read
+// and an execute
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heatmap {
+    bucket_shift: u32,
+    // instructions per window; None keeps counting forever
+    window: Option<u64>,
+    instructions: u64,
+    current: Vec<HeatCounts>,
+    completed: Option<Vec<HeatCounts>>,
+}
+
+impl Heatmap {
+    // `bucket_size` addresses share a counter; it must be a power of two
+    pub fn new(bucket_size: usize) -> Self {
+        assert!(
+            bucket_size.is_power_of_two() && bucket_size <= ADDRESS_SPACE,
+            "bucket size must be a power of two no larger than the address space"
+        );
+
+        Self {
+            bucket_shift: bucket_size.trailing_zeros(),
+            window: None,
+            instructions: 0,
+            current: vec![HeatCounts::default(); ADDRESS_SPACE / bucket_size],
+            completed: None,
+        }
+    }
+
+    // every `instructions` instructions the counts move to completed_window and start over
+    pub fn with_window(mut self, instructions: u64) -> Self {
+        self.window = Some(instructions).filter(|&instructions| instructions > 0);
+        self
+    }
+
+    pub fn bucket_size(&self) -> usize {
+        1 << self.bucket_shift
+    }
+
+    pub fn bucket_of(&self, address: u16) -> usize {
+        address as usize >> self.bucket_shift
+    }
+
+    pub fn get(&self, address: u16) -> HeatCounts {
+        self.current[self.bucket_of(address)]
+    }
+
+    // bucket i covers addresses i * bucket_size up to the next bucket
+    pub fn buckets(&self) -> &[HeatCounts] {
+        &self.current
+    }
+
+    pub fn completed_window(&self) -> Option<&[HeatCounts]> {
+        self.completed.as_deref()
+    }
+
+    pub fn clear(&mut self) {
+        self.current.fill(HeatCounts::default());
+        self.completed = None;
+        self.instructions = 0;
+    }
+
+    pub(crate) fn record_read(&mut self, address: u16) {
+        let bucket = self.bucket_of(address);
+        self.current[bucket].reads += 1;
+    }
+
+    pub(crate) fn record_write(&mut self, address: u16) {
+        let bucket = self.bucket_of(address);
+        self.current[bucket].writes += 1;
+    }
+
+    pub(crate) fn record_execute(&mut self, address: u16) {
+        if self.window == Some(self.instructions) {
+            let fresh = vec![HeatCounts::default(); self.current.len()];
+            self.completed = Some(std::mem::replace(&mut self.current, fresh));
+            self.instructions = 0;
+        }
+
+        let bucket = self.bucket_of(address);
+        self.current[bucket].executes += 1;
+        self.instructions += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buckets() {
+        let mut heatmap = Heatmap::new(0x100);
+        assert_eq!(heatmap.buckets().len(), 0x100);
+
+        heatmap.record_read(0x0010);
+        heatmap.record_read(0x00FF);
+        heatmap.record_write(0x0100);
+        heatmap.record_execute(0x8000);
+
+        assert_eq!(heatmap.get(0x0000).reads, 2);
+        assert_eq!(heatmap.get(0x01FF).writes, 1);
+        assert_eq!(heatmap.buckets()[0x80].executes, 1);
+        assert_eq!(heatmap.get(0x0000).total(), 2);
+    }
+
+    #[test]
+    fn test_window_rolls_over() {
+        let mut heatmap = Heatmap::new(1).with_window(2);
+        heatmap.record_execute(0x8000);
+        heatmap.record_execute(0x8001);
+        assert!(heatmap.completed_window().is_none());
+
+        heatmap.record_execute(0x8002);
+        let completed = heatmap.completed_window().unwrap();
+        assert_eq!(completed[0x8000].executes, 1);
+        assert_eq!(completed[0x8002].executes, 0);
+        assert_eq!(heatmap.get(0x8000).executes, 0);
+        assert_eq!(heatmap.get(0x8002).executes, 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_bucket_size_must_be_a_power_of_two() {
+        Heatmap::new(3);
+    }
+}
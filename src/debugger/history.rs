@@ -0,0 +1,89 @@
+use std::collections::VecDeque;
+
+use crate::cpu::Registers;
+use crate::debugger::CallStack;
+
+// enough to undo one instruction: the state before it and the old value of every byte it wrote
+#[derive(Debug, Clone)]
+pub(crate) struct Entry {
+    pub registers: Registers,
+    pub cycles: u64,
+    pub call_stack: CallStack,
+    pub writes: Vec<(u16, u8)>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct History {
+    capacity: usize,
+    entries: VecDeque<Entry>,
+}
+
+impl History {
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.entries.len() > capacity {
+            self.entries.pop_front();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn begin(&mut self, registers: Registers, cycles: u64, call_stack: &CallStack) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(Entry {
+            registers,
+            cycles,
+            call_stack: call_stack.clone(),
+            writes: Vec::new(),
+        });
+    }
+
+    pub fn record_write(&mut self, address: u16, old: u8) {
+        if let Some(entry) = self.entries.back_mut() {
+            entry.writes.push((address, old));
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<Entry> {
+        self.entries.pop_back()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_is_bounded() {
+        let mut history = History::default();
+        history.begin(Registers::default(), 0, &CallStack::new());
+        assert_eq!(history.len(), 0);
+
+        history.set_capacity(2);
+        for cycles in 0..3 {
+            history.begin(Registers::default(), cycles, &CallStack::new());
+            history.record_write(0x10, cycles as u8);
+        }
+        assert_eq!(history.len(), 2);
+
+        let entry = history.pop().unwrap();
+        assert_eq!((entry.cycles, entry.writes), (2, vec![(0x10, 2)]));
+        assert_eq!(history.pop().unwrap().cycles, 1);
+        assert!(history.pop().is_none());
+    }
+}
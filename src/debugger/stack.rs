@@ -0,0 +1,191 @@
+use std::fmt;
+
+use super::call_stack::FrameKind;
+use crate::cpu::{Cpu, STACK_PAGE};
+
+// one slot or group of slots on the live part of the stack, from SP+1 up to $01FF
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StackEntry {
+    // the two bytes a JSR or interrupt pushed; `address` is where the low byte is
+    ReturnAddress {
+        address: u16,
+        kind: FrameKind,
+        pushed: u16,
+        // JSR pushes its own last byte, so RTS adds one
+        returns_to: u16,
+    },
+    // pushed by an interrupt, below its return address
+    Status {
+        address: u16,
+        flags: u8,
+    },
+    // anything the shadow call stack can't account for, e.g. PHA
+    Byte {
+        address: u16,
+        value: u8,
+    },
+}
+
+impl StackEntry {
+    pub fn address(&self) -> u16 {
+        match *self {
+            StackEntry::ReturnAddress { address, .. }
+            | StackEntry::Status { address, .. }
+            | StackEntry::Byte { address, .. } => address,
+        }
+    }
+}
+
+impl fmt::Display for StackEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            StackEntry::ReturnAddress {
+                address,
+                kind,
+                returns_to,
+                ..
+            } => {
+                let via = match kind {
+                    FrameKind::Subroutine => "RTS",
+                    FrameKind::Interrupt => "RTI",
+                };
+                write!(
+                    f,
+                    "${:04X}  return to ${:04X} ({})",
+                    address, returns_to, via
+                )
+            }
+            StackEntry::Status { address, flags } => {
+                write!(f, "${:04X}  status {:02X}", address, flags)
+            }
+            StackEntry::Byte { address, value } => write!(f, "${:04X}  {:02X}", address, value),
+        }
+    }
+}
+
+// the live stack, top first; return addresses and pushed status bytes are recognised using the
+// debugger's call stack, so only calls it saw being made are decoded
+pub fn inspect_stack(cpu: &Cpu) -> Vec<StackEntry> {
+    let sp = cpu.registers().sp;
+    let frames = cpu.debugger().call_stack().frames();
+    let peek = |slot: u8| cpu.mem_peek(STACK_PAGE | slot as u16);
+
+    let mut entries = Vec::new();
+    let mut slot = sp as u16 + 1;
+    while slot <= 0xFF {
+        let frame = frames
+            .iter()
+            .rev()
+            .find(|frame| frame.sp as u16 + 1 == slot && frame.sp >= sp);
+        let (status, bytes) = match frame.map(|frame| frame.kind) {
+            Some(FrameKind::Subroutine) => (0, 2),
+            Some(FrameKind::Interrupt) => (1, 3),
+            None => (0, 0),
+        };
+
+        // a frame that would run off the top of the page is left as raw bytes
+        if let Some(frame) = frame.filter(|_| slot + bytes - 1 <= 0xFF) {
+            if status == 1 {
+                entries.push(StackEntry::Status {
+                    address: STACK_PAGE | slot,
+                    flags: peek(slot as u8),
+                });
+            }
+            let low = slot + status;
+            let pushed = u16::from_le_bytes([peek(low as u8), peek(low as u8 + 1)]);
+            entries.push(StackEntry::ReturnAddress {
+                address: STACK_PAGE | low,
+                kind: frame.kind,
+                pushed,
+                returns_to: match frame.kind {
+                    FrameKind::Subroutine => pushed.wrapping_add(1),
+                    FrameKind::Interrupt => pushed,
+                },
+            });
+            slot += bytes;
+        } else {
+            entries.push(StackEntry::Byte {
+                address: STACK_PAGE | slot,
+                value: peek(slot as u8),
+            });
+            slot += 1;
+        }
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::Registers;
+
+    const JSR: u8 = 0x20;
+
+    fn cpu_with_stack(sp: u8, bytes: &[u8]) -> Cpu {
+        let mut cpu = Cpu::new();
+        for (i, &byte) in bytes.iter().enumerate() {
+            cpu.mem_write(STACK_PAGE + sp as u16 + 1 + i as u16, byte);
+        }
+        cpu.set_registers(Registers {
+            sp,
+            ..cpu.registers()
+        });
+        cpu
+    }
+
+    #[test]
+    fn test_frames_are_decoded() {
+        // from the top: a byte pushed with PHA, an interrupt's status and return address, then
+        // the return address of a JSR at $8000
+        let mut cpu = cpu_with_stack(0xF8, &[0x42, 0x30, 0x03, 0x90, 0x02, 0x80, 0x77]);
+        let call_stack = cpu.debugger_mut().call_stack_mut();
+        call_stack.update(JSR, 0x8000, 0x9000, 0xFE, 0xFC);
+        call_stack.enter_interrupt(0x9003, 0xC000, 0xF9);
+
+        let entries = inspect_stack(&cpu);
+        assert_eq!(entries[3].to_string(), "$01FD  return to $8003 (RTS)");
+        assert_eq!(
+            entries,
+            vec![
+                StackEntry::Byte {
+                    address: 0x01F9,
+                    value: 0x42
+                },
+                StackEntry::Status {
+                    address: 0x01FA,
+                    flags: 0x30
+                },
+                StackEntry::ReturnAddress {
+                    address: 0x01FB,
+                    kind: FrameKind::Interrupt,
+                    pushed: 0x9003,
+                    returns_to: 0x9003
+                },
+                StackEntry::ReturnAddress {
+                    address: 0x01FD,
+                    kind: FrameKind::Subroutine,
+                    pushed: 0x8002,
+                    returns_to: 0x8003
+                },
+                StackEntry::Byte {
+                    address: 0x01FF,
+                    value: 0x77
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_untracked_stack_is_raw_bytes() {
+        let cpu = cpu_with_stack(0xFC, &[0x02, 0x80, 0x10]);
+        let entries = inspect_stack(&cpu);
+        assert_eq!(entries.len(), 3);
+        assert!(entries
+            .iter()
+            .all(|entry| matches!(entry, StackEntry::Byte { .. })));
+        assert_eq!(entries[0].address(), 0x01FD);
+        assert_eq!(entries[0].to_string(), "$01FD  02");
+
+        assert!(inspect_stack(&cpu_with_stack(0xFF, &[])).is_empty());
+    }
+}
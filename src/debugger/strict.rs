@@ -0,0 +1,154 @@
+use crate::cpu::RAM_SIZE;
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum StrictAction {
+    // log a warning and keep running
+    #[default]
+    Warn,
+    // also stop with Status::UninitializedRead once the instruction finishes
+    Break,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct UninitializedRead {
+    pub address: u16,
+    // the instruction that made the read
+    pub pc: u16,
+}
+
+// shadows internal RAM with a bit per byte that's set once the byte has been written; reading a
+// byte before that depends on power-on contents, which differ between consoles. Each address is
+// reported once, the first time it's read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StrictMode {
+    action: StrictAction,
+    written: Vec<u64>,
+    reported: Vec<u64>,
+    reads: Vec<UninitializedRead>,
+    pending: Option<UninitializedRead>,
+}
+
+impl StrictMode {
+    pub fn new(action: StrictAction) -> Self {
+        Self {
+            action,
+            written: vec![0; RAM_SIZE / WORD_BITS],
+            reported: vec![0; RAM_SIZE / WORD_BITS],
+            reads: Vec::new(),
+            pending: None,
+        }
+    }
+
+    pub fn action(&self) -> StrictAction {
+        self.action
+    }
+
+    // every uninitialized read so far, in the order they happened
+    pub fn reads(&self) -> &[UninitializedRead] {
+        &self.reads
+    }
+
+    // anything outside internal RAM counts as initialized
+    pub fn is_initialized(&self, address: u16) -> bool {
+        (address as usize) >= RAM_SIZE || Self::get(&self.written, address)
+    }
+
+    // for memory the program can rely on without writing it first, e.g. what a loader set up
+    pub fn mark_initialized(&mut self, range: std::ops::RangeInclusive<u16>) {
+        for address in range {
+            self.record_write(address);
+        }
+    }
+
+    // forget what has been written and reported, e.g. when the program is restarted from power-on
+    pub fn clear(&mut self) {
+        *self = Self::new(self.action);
+    }
+
+    pub(crate) fn record_write(&mut self, address: u16) {
+        if (address as usize) < RAM_SIZE {
+            Self::set(&mut self.written, address);
+        }
+    }
+
+    pub(crate) fn record_read(&mut self, address: u16, pc: u16) {
+        if self.is_initialized(address) || Self::get(&self.reported, address) {
+            return;
+        }
+        Self::set(&mut self.reported, address);
+
+        log::warn!(
+            "read of uninitialized RAM at ${:04X} by the instruction at ${:04X}",
+            address,
+            pc
+        );
+        let read = UninitializedRead { address, pc };
+        self.reads.push(read);
+        if self.action == StrictAction::Break && self.pending.is_none() {
+            self.pending = Some(read);
+        }
+    }
+
+    pub(crate) fn take_break(&mut self) -> Option<UninitializedRead> {
+        self.pending.take()
+    }
+
+    fn get(bits: &[u64], address: u16) -> bool {
+        let address = address as usize;
+        bits[address / WORD_BITS] & (1 << (address % WORD_BITS)) != 0
+    }
+
+    fn set(bits: &mut [u64], address: u16) {
+        let address = address as usize;
+        bits[address / WORD_BITS] |= 1 << (address % WORD_BITS);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reads_before_writes_are_reported_once() {
+        let mut strict = StrictMode::new(StrictAction::Warn);
+        strict.record_write(0x0010);
+        strict.record_read(0x0010, 0x8000);
+        strict.record_read(0x0011, 0x8002);
+        strict.record_read(0x0011, 0x8004);
+        strict.record_read(0x8000, 0x8006);
+
+        assert_eq!(
+            strict.reads(),
+            &[UninitializedRead {
+                address: 0x0011,
+                pc: 0x8002
+            }]
+        );
+        assert_eq!(strict.take_break(), None);
+
+        strict.mark_initialized(0x0700..=0x07FF);
+        assert!(strict.is_initialized(0x07FF));
+        assert!(!strict.is_initialized(0x06FF));
+        strict.clear();
+        assert!(strict.reads().is_empty());
+        assert!(!strict.is_initialized(0x0010));
+    }
+
+    #[test]
+    fn test_break_keeps_first_read() {
+        let mut strict = StrictMode::new(StrictAction::Break);
+        strict.record_read(0x0001, 0x8000);
+        strict.record_read(0x0002, 0x8000);
+        assert_eq!(
+            strict.take_break(),
+            Some(UninitializedRead {
+                address: 0x0001,
+                pc: 0x8000
+            })
+        );
+        assert_eq!(strict.take_break(), None);
+        assert_eq!(strict.reads().len(), 2);
+    }
+}
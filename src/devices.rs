@@ -0,0 +1,182 @@
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::cpu::{Cpu, PROGRAM_COUNTER_RESET_ADDRESS};
+use crate::hooks::HookId;
+
+// the memory map of the easy6502 tutorial machine, which the well-known snake game is written for
+pub const RANDOM_ADDRESS: u16 = 0x00FE;
+pub const KEY_ADDRESS: u16 = 0x00FF;
+pub const SCREEN_ADDRESS: u16 = 0x0200;
+pub const SCREEN_WIDTH: usize = 32;
+pub const SCREEN_HEIGHT: usize = 32;
+// programs for it are assembled to start here
+pub const PROGRAM_ADDRESS: u16 = 0x0600;
+
+// every read of $FE returns a fresh pseudo-random byte; the seed makes runs repeatable
+pub fn install_random(cpu: &mut Cpu, seed: u64) -> HookId {
+    // xorshift gets stuck at zero
+    let mut state = seed.max(1);
+    cpu.hooks_mut()
+        .on_read(RANDOM_ADDRESS..=RANDOM_ADDRESS, move |_, _| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            Some((state >> 32) as u8)
+        })
+}
+
+// $FF holds the ASCII code of the last key pressed; the handle can be cloned into whatever thread
+// reads the keyboard
+#[derive(Debug, Clone, Default)]
+pub struct KeyInput(Arc<AtomicU8>);
+
+impl KeyInput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn press(&self, key: u8) {
+        self.0.store(key, Ordering::Relaxed);
+    }
+
+    pub fn last(&self) -> u8 {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    // programs may clear the key once they've handled it, so writes go to the device too
+    pub fn install(&self, cpu: &mut Cpu) -> [HookId; 2] {
+        let reader = self.0.clone();
+        let writer = self.0.clone();
+        let hooks = cpu.hooks_mut();
+        [
+            hooks.on_read(KEY_ADDRESS..=KEY_ADDRESS, move |_, _| {
+                Some(reader.load(Ordering::Relaxed))
+            }),
+            hooks.on_write(KEY_ADDRESS..=KEY_ADDRESS, move |_, value| {
+                writer.store(value, Ordering::Relaxed);
+                None
+            }),
+        ]
+    }
+}
+
+// unused by the console and by every common mapper, so test ROMs can write to it freely
+pub const DEBUG_PORT: RangeInclusive<u16> = 0x4018..=0x401F;
+
+// collects every byte the program writes into a range, so homebrew tests can print results and
+// harnesses can assert on them; the handle can be cloned to read the output from elsewhere
+#[derive(Debug, Clone, Default)]
+pub struct DebugOutput(Arc<Mutex<Vec<u8>>>);
+
+impl DebugOutput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // writes still reach memory, so a program can read back what it printed
+    pub fn install(&self, cpu: &mut Cpu, range: RangeInclusive<u16>) -> HookId {
+        let output = self.0.clone();
+        cpu.hooks_mut().on_write(range, move |_, value| {
+            output.lock().unwrap().push(value);
+            None
+        })
+    }
+
+    pub fn bytes(&self) -> Vec<u8> {
+        self.0.lock().unwrap().clone()
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+    }
+
+    // returns and clears what has been written so far, for streaming it out
+    pub fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+// copies a program to where tutorial programs expect to live and points the reset vector at it
+pub fn load_program(cpu: &mut Cpu, program: &[u8]) {
+    cpu.write_image(PROGRAM_ADDRESS, program);
+    cpu.mem_write_u16(PROGRAM_COUNTER_RESET_ADDRESS, PROGRAM_ADDRESS);
+}
+
+// the screen's pixels, a row at a time; only the low nibble of each is a colour
+pub fn screen(cpu: &Cpu) -> impl Iterator<Item = u8> + '_ {
+    (0..(SCREEN_WIDTH * SCREEN_HEIGHT) as u16).map(move |i| cpu.mem_peek(SCREEN_ADDRESS + i) & 0x0F)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random_reads_differ_and_repeat_per_seed() {
+        let run = |seed| {
+            let mut cpu = Cpu::new();
+            install_random(&mut cpu, seed);
+            (0..8)
+                .map(|_| cpu.mem_read(RANDOM_ADDRESS))
+                .collect::<Vec<_>>()
+        };
+
+        let bytes = run(42);
+        assert_eq!(bytes, run(42));
+        assert_ne!(bytes, run(43));
+        assert!(bytes.windows(2).any(|pair| pair[0] != pair[1]));
+    }
+
+    #[test]
+    fn test_program_sees_last_key() {
+        let mut cpu = Cpu::new();
+        let keys = KeyInput::new();
+        keys.install(&mut cpu);
+
+        // LDA $FF; TAX; LDA #$00; STA $FF; LDY $FF; BRK
+        load_program(
+            &mut cpu,
+            &[0xA5, 0xFF, 0xAA, 0xA9, 0x00, 0x85, 0xFF, 0xA4, 0xFF, 0x00],
+        );
+        cpu.reset();
+        assert_eq!(cpu.registers().pc, PROGRAM_ADDRESS);
+
+        keys.press(b'w');
+        cpu.run();
+        assert_eq!(cpu.registers().x, b'w');
+        assert_eq!(cpu.registers().y, 0);
+        assert_eq!(keys.last(), 0);
+    }
+
+    #[test]
+    fn test_debug_output_collects_writes() {
+        let mut cpu = Cpu::new();
+        let output = DebugOutput::new();
+        output.install(&mut cpu, DEBUG_PORT);
+
+        // LDA #'o'; STA $4018; LDX #'k'; STX $401F; STX $4020; BRK
+        cpu.load(vec![
+            0xA9, b'o', 0x8D, 0x18, 0x40, 0xA2, b'k', 0x8E, 0x1F, 0x40, 0x8E, 0x20, 0x40, 0x00,
+        ]);
+        cpu.reset();
+        cpu.run();
+
+        assert_eq!(output.text(), "ok");
+        assert_eq!(cpu.mem_peek(0x4018), b'o');
+        assert_eq!(output.take(), b"ok");
+        assert!(output.bytes().is_empty());
+    }
+
+    #[test]
+    fn test_screen_masks_colours() {
+        let mut cpu = Cpu::new();
+        cpu.mem_write(SCREEN_ADDRESS, 0x11);
+        cpu.mem_write(SCREEN_ADDRESS + 0x3FF, 0x05);
+
+        let pixels: Vec<u8> = screen(&cpu).collect();
+        assert_eq!(pixels.len(), 32 * 32);
+        assert_eq!((pixels[0], pixels[1], pixels[1023]), (0x01, 0x00, 0x05));
+    }
+}
@@ -0,0 +1,231 @@
+use std::fmt;
+
+use crate::cpu::instructions::{lookup, Instruction};
+use crate::cpu::AddressingMode;
+use crate::symbols::SymbolTable;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Disassembled {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    pub instruction: Option<&'static Instruction>,
+}
+
+impl Disassembled {
+    pub fn decode(address: u16, bytes: &[u8]) -> Self {
+        let instruction = bytes
+            .first()
+            .and_then(|&opcode| lookup(opcode))
+            .filter(|instruction| instruction.bytes as usize <= bytes.len());
+
+        let len = instruction.map_or(1, |instruction| instruction.bytes as usize);
+
+        Self {
+            address,
+            bytes: bytes[..len.min(bytes.len())].to_vec(),
+            instruction,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        self.instruction
+            .map_or(".byte", |instruction| instruction.mnemonic)
+    }
+
+    pub fn operand(&self) -> String {
+        self.operand_with(&SymbolTable::new())
+    }
+
+    // like operand, but addresses with a label are shown by name
+    pub fn operand_with(&self, symbols: &SymbolTable) -> String {
+        let Some(instruction) = self.instruction else {
+            return format!("${:02X}", self.bytes[0]);
+        };
+
+        let byte = || {
+            let addr = self.bytes[1];
+            symbols
+                .label(addr as u16)
+                .map_or_else(|| format!("${:02X}", addr), str::to_string)
+        };
+        let word = |addr: u16| {
+            symbols
+                .label(addr)
+                .map_or_else(|| format!("${:04X}", addr), str::to_string)
+        };
+        let absolute = || word(u16::from_le_bytes([self.bytes[1], self.bytes[2]]));
+
+        match instruction.addressing_mode {
+            AddressingMode::Implicit => String::new(),
+            AddressingMode::Accumulator => "A".to_string(),
+            AddressingMode::Immediate => format!("#${:02X}", self.bytes[1]),
+            AddressingMode::ZeroPage => byte(),
+            AddressingMode::ZeroPageX => format!("{},X", byte()),
+            AddressingMode::ZeroPageY => format!("{},Y", byte()),
+            AddressingMode::Absolute => absolute(),
+            AddressingMode::AbsoluteX => format!("{},X", absolute()),
+            AddressingMode::AbsoluteY => format!("{},Y", absolute()),
+            AddressingMode::Relative => word(self.branch_target()),
+            AddressingMode::Indirect => format!("({})", absolute()),
+            AddressingMode::IndirectX => format!("({},X)", byte()),
+            AddressingMode::IndirectY => format!("({}),Y", byte()),
+        }
+    }
+
+    // mnemonic and labelled operand, without the comment
+    pub(crate) fn format_with(&self, symbols: &SymbolTable) -> String {
+        let operand = self.operand_with(symbols);
+        if operand.is_empty() {
+            self.mnemonic().to_string()
+        } else {
+            format!("{} {}", self.mnemonic(), operand)
+        }
+    }
+
+    // listing line with labels substituted and any comment for this address appended
+    pub fn to_string_with(&self, symbols: &SymbolTable) -> String {
+        match symbols.comment(self.address) {
+            Some(comment) => format!("{} ; {}", self.format_with(symbols), comment),
+            None => self.format_with(symbols),
+        }
+    }
+
+    // branch offsets are signed and relative to the instruction that follows the branch
+    pub fn branch_target(&self) -> u16 {
+        let next = self.address.wrapping_add(self.bytes.len() as u16);
+        next.wrapping_add(self.bytes[1] as i8 as u16)
+    }
+}
+
+impl fmt::Display for Disassembled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.format_with(&SymbolTable::new()))
+    }
+}
+
+pub struct Disassembly<'a> {
+    bytes: &'a [u8],
+    origin: u16,
+    offset: usize,
+}
+
+impl Iterator for Disassembly<'_> {
+    type Item = Disassembled;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset >= self.bytes.len() {
+            return None;
+        }
+
+        let address = self.origin.wrapping_add(self.offset as u16);
+        let disassembled = Disassembled::decode(address, &self.bytes[self.offset..]);
+        self.offset += disassembled.len();
+        Some(disassembled)
+    }
+}
+
+// `origin` is the address the first byte is (or will be) loaded at
+pub fn disassemble(bytes: &[u8], origin: u16) -> Disassembly<'_> {
+    Disassembly {
+        bytes,
+        origin,
+        offset: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(bytes: &[u8]) -> Vec<String> {
+        disassemble(bytes, 0x8000)
+            .map(|line| line.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_operand_rendering() {
+        assert_eq!(
+            lines(&[
+                0xA9, 0x05, 0xA5, 0x10, 0xB5, 0x10, 0xB6, 0x10, 0xAD, 0x34, 0x12, 0xBD, 0x34, 0x12,
+                0xB9, 0x34, 0x12, 0xA1, 0x10, 0xB1, 0x10, 0x0A, 0xAA, 0x00,
+            ]),
+            vec![
+                "LDA #$05",
+                "LDA $10",
+                "LDA $10,X",
+                "LDX $10,Y",
+                "LDA $1234",
+                "LDA $1234,X",
+                "LDA $1234,Y",
+                "LDA ($10,X)",
+                "LDA ($10),Y",
+                "ASL A",
+                "TAX",
+                "BRK",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_addresses_advance_by_instruction_length() {
+        let addresses: Vec<u16> = disassemble(&[0xA9, 0x05, 0xAD, 0x34, 0x12, 0xE8], 0xC000)
+            .map(|line| line.address)
+            .collect();
+        assert_eq!(addresses, vec![0xC000, 0xC002, 0xC005]);
+    }
+
+    #[test]
+    fn test_unknown_and_truncated_bytes() {
+        assert_eq!(
+            lines(&[0xFF, 0xAD, 0x34]),
+            vec![".byte $FF", ".byte $AD", ".byte $34"]
+        );
+    }
+
+    #[test]
+    fn test_labels_and_comments() {
+        let symbols =
+            SymbolTable::from_nl("$8000#Reset#entry point\n$0010#Pointer#\n$1234#Table#").unwrap();
+        let lines: Vec<String> = disassemble(
+            &[0xA9, 0x10, 0xB1, 0x10, 0xBD, 0x34, 0x12, 0xAD, 0x00, 0x02],
+            0x8000,
+        )
+        .map(|line| line.to_string_with(&symbols))
+        .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "LDA #$10 ; entry point",
+                "LDA (Pointer),Y",
+                "LDA Table,X",
+                "LDA $0200",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_branch_target() {
+        let forward = Disassembled {
+            address: 0x8000,
+            bytes: vec![0xD0, 0x04],
+            instruction: None,
+        };
+        assert_eq!(forward.branch_target(), 0x8006);
+
+        let backward = Disassembled {
+            address: 0x8000,
+            bytes: vec![0xD0, 0xFC],
+            instruction: None,
+        };
+        assert_eq!(backward.branch_target(), 0x7FFE);
+    }
+}
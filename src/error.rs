@@ -0,0 +1,61 @@
+use crate::assembler::AssembleError;
+use crate::builder::BuildError;
+use crate::cartridge::RomError;
+use crate::cheat::GameGenieError;
+use crate::config::ConfigError;
+use crate::debugger::ConditionError;
+use crate::patch::PatchError;
+use crate::savestate::StateError;
+use crate::symbols::SymbolError;
+
+// every module keeps its own error type; this wraps them for callers that just want one `?`
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error(transparent)]
+    Rom(#[from] RomError),
+    #[error(transparent)]
+    Build(#[from] BuildError),
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+    #[error(transparent)]
+    Cheat(#[from] GameGenieError),
+    #[error(transparent)]
+    Symbols(#[from] SymbolError),
+    #[error(transparent)]
+    Assemble(#[from] AssembleError),
+    #[error(transparent)]
+    Condition(#[from] ConditionError),
+    #[error(transparent)]
+    State(#[from] StateError),
+    #[error(transparent)]
+    Patch(#[from] PatchError),
+    #[cfg(feature = "lua")]
+    #[error(transparent)]
+    Script(#[from] mlua::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cheat::GameGenieCode;
+
+    fn add_code(code: &str) -> Result<GameGenieCode> {
+        Ok(GameGenieCode::decode(code)?)
+    }
+
+    #[test]
+    fn test_module_errors_convert() {
+        let error = add_code("AAA").unwrap_err();
+        assert!(matches!(
+            error,
+            Error::Cheat(GameGenieError::InvalidLength(3))
+        ));
+        assert_eq!(
+            error.to_string(),
+            "game genie codes are 6 or 8 letters long, got 3"
+        );
+    }
+}
@@ -0,0 +1,66 @@
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Event {
+    Reset,
+    StateLoaded,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SubscriberId(usize);
+
+type Subscriber = Box<dyn FnMut(&Event) + Send>;
+
+#[derive(Default)]
+pub struct EventBus {
+    next_id: usize,
+    subscribers: Vec<(SubscriberId, Subscriber)>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn subscribe<F>(&mut self, subscriber: F) -> SubscriberId
+    where
+        F: FnMut(&Event) + Send + 'static,
+    {
+        let id = SubscriberId(self.next_id);
+        self.next_id += 1;
+
+        self.subscribers.push((id, Box::new(subscriber)));
+        id
+    }
+
+    pub fn unsubscribe(&mut self, id: SubscriberId) -> bool {
+        let before = self.subscribers.len();
+        self.subscribers.retain(|(subscriber, _)| *subscriber != id);
+        self.subscribers.len() != before
+    }
+
+    pub fn publish(&mut self, event: Event) {
+        for (_, subscriber) in self.subscribers.iter_mut() {
+            subscriber(&event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_publish_reaches_subscribers() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let mut events = EventBus::new();
+
+        let log = Arc::clone(&received);
+        let id = events.subscribe(move |event| log.lock().unwrap().push(*event));
+        events.publish(Event::Reset);
+
+        assert!(events.unsubscribe(id));
+        events.publish(Event::StateLoaded);
+
+        assert_eq!(*received.lock().unwrap(), vec![Event::Reset]);
+    }
+}
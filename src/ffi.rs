@@ -0,0 +1,315 @@
+// C API for embedding the emulator; include/nes.h is generated from this file with cbindgen
+//
+// Every function taking a `NesCpu *` expects a pointer from nes_cpu_new that hasn't been freed.
+// Byte buffers are (pointer, length) pairs and may be null only when the length is 0.
+
+use std::slice;
+
+use crate::cartridge::load_nrom;
+use crate::cpu::{Cpu, Registers, Status, MAX_PROGRAM_SIZE};
+use crate::savestate::{self, STATE_SIZE};
+
+pub const NES_OK: i32 = 0;
+pub const NES_ERROR: i32 = -1;
+
+pub const NES_STATUS_RUNNING: i32 = 0;
+pub const NES_STATUS_HALTED: i32 = 1;
+pub const NES_STATUS_BREAKPOINT: i32 = 2;
+pub const NES_STATUS_WATCHPOINT: i32 = 3;
+pub const NES_STATUS_UNKNOWN_OPCODE: i32 = 4;
+pub const NES_STATUS_UNINITIALIZED_READ: i32 = 5;
+
+// opaque to C
+pub struct NesCpu(Cpu);
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct NesRegisters {
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub status: u8,
+    pub sp: u8,
+    pub pc: u16,
+}
+
+impl From<Registers> for NesRegisters {
+    fn from(registers: Registers) -> Self {
+        Self {
+            a: registers.a,
+            x: registers.x,
+            y: registers.y,
+            status: registers.status,
+            sp: registers.sp,
+            pc: registers.pc,
+        }
+    }
+}
+
+impl From<NesRegisters> for Registers {
+    fn from(registers: NesRegisters) -> Self {
+        Self {
+            a: registers.a,
+            x: registers.x,
+            y: registers.y,
+            status: registers.status,
+            sp: registers.sp,
+            pc: registers.pc,
+        }
+    }
+}
+
+fn status_code(status: Status) -> i32 {
+    match status {
+        Status::Running => NES_STATUS_RUNNING,
+        Status::Halted => NES_STATUS_HALTED,
+        Status::Breakpoint(_) => NES_STATUS_BREAKPOINT,
+        Status::Watchpoint(_) => NES_STATUS_WATCHPOINT,
+        Status::UnknownOpcode(_) => NES_STATUS_UNKNOWN_OPCODE,
+        Status::UninitializedRead(_) => NES_STATUS_UNINITIALIZED_READ,
+    }
+}
+
+unsafe fn bytes<'a>(data: *const u8, len: usize) -> &'a [u8] {
+    if len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(data, len)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn nes_cpu_new() -> *mut NesCpu {
+    Box::into_raw(Box::new(NesCpu(Cpu::new())))
+}
+
+/// # Safety
+/// `cpu` must come from nes_cpu_new and not be used afterwards; null is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn nes_cpu_free(cpu: *mut NesCpu) {
+    if !cpu.is_null() {
+        drop(Box::from_raw(cpu));
+    }
+}
+
+/// # Safety
+/// `cpu` must be live and `data` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn nes_cpu_load_rom(cpu: *mut NesCpu, data: *const u8, len: usize) -> i32 {
+    let cpu = &mut (*cpu).0;
+    match load_nrom(cpu, bytes(data, len)) {
+        Ok(_) => {
+            cpu.reset();
+            NES_OK
+        }
+        Err(_) => NES_ERROR,
+    }
+}
+
+/// # Safety
+/// `cpu` must be live and `data` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn nes_cpu_load_program(
+    cpu: *mut NesCpu,
+    data: *const u8,
+    len: usize,
+) -> i32 {
+    if len > MAX_PROGRAM_SIZE {
+        return NES_ERROR;
+    }
+    let cpu = &mut (*cpu).0;
+    cpu.load(bytes(data, len).to_vec());
+    cpu.reset();
+    NES_OK
+}
+
+/// # Safety
+/// `cpu` must be live.
+#[no_mangle]
+pub unsafe extern "C" fn nes_cpu_reset(cpu: *mut NesCpu) {
+    (*cpu).0.reset();
+}
+
+/// # Safety
+/// `cpu` must be live.
+#[no_mangle]
+pub unsafe extern "C" fn nes_cpu_step(cpu: *mut NesCpu) -> i32 {
+    status_code((*cpu).0.step())
+}
+
+/// Runs until at least `cycles` more cycles have passed or the CPU stops, e.g. a frame's worth.
+///
+/// # Safety
+/// `cpu` must be live.
+#[no_mangle]
+pub unsafe extern "C" fn nes_cpu_run_cycles(cpu: *mut NesCpu, cycles: u64) -> i32 {
+    let cpu = &mut (*cpu).0;
+    let end = cpu.cycles().saturating_add(cycles);
+    while cpu.cycles() < end {
+        let status = cpu.step();
+        if status != Status::Running {
+            return status_code(status);
+        }
+    }
+    NES_STATUS_RUNNING
+}
+
+/// # Safety
+/// `cpu` must be live.
+#[no_mangle]
+pub unsafe extern "C" fn nes_cpu_cycles(cpu: *const NesCpu) -> u64 {
+    (*cpu).0.cycles()
+}
+
+/// # Safety
+/// `cpu` must be live and `out` writable.
+#[no_mangle]
+pub unsafe extern "C" fn nes_cpu_registers(cpu: *const NesCpu, out: *mut NesRegisters) {
+    *out = (*cpu).0.registers().into();
+}
+
+/// # Safety
+/// `cpu` must be live and `registers` readable.
+#[no_mangle]
+pub unsafe extern "C" fn nes_cpu_set_registers(cpu: *mut NesCpu, registers: *const NesRegisters) {
+    (*cpu).0.set_registers((*registers).into());
+}
+
+/// Reads without side effects, like Cpu::mem_peek.
+///
+/// # Safety
+/// `cpu` must be live.
+#[no_mangle]
+pub unsafe extern "C" fn nes_cpu_peek(cpu: *const NesCpu, address: u16) -> u8 {
+    (*cpu).0.mem_peek(address)
+}
+
+/// A bus write, e.g. for memory-mapped input.
+///
+/// # Safety
+/// `cpu` must be live.
+#[no_mangle]
+pub unsafe extern "C" fn nes_cpu_write(cpu: *mut NesCpu, address: u16, value: u8) {
+    (*cpu).0.mem_write(address, value);
+}
+
+#[no_mangle]
+pub extern "C" fn nes_state_size() -> usize {
+    STATE_SIZE
+}
+
+/// Writes nes_state_size() bytes into `buffer`, in the versioned save state format.
+///
+/// # Safety
+/// `cpu` must be live and `buffer` must point to `len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn nes_cpu_save_state(
+    cpu: *const NesCpu,
+    buffer: *mut u8,
+    len: usize,
+) -> i32 {
+    if len < STATE_SIZE {
+        return NES_ERROR;
+    }
+    let state = savestate::encode(&(*cpu).0.save_state());
+    slice::from_raw_parts_mut(buffer, STATE_SIZE).copy_from_slice(&state);
+    NES_OK
+}
+
+/// Also accepts states saved by older versions.
+///
+/// # Safety
+/// `cpu` must be live and `buffer` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn nes_cpu_load_state(
+    cpu: *mut NesCpu,
+    buffer: *const u8,
+    len: usize,
+) -> i32 {
+    match savestate::decode(bytes(buffer, len)) {
+        Ok(state) => {
+            (*cpu).0.load_state(&state);
+            NES_OK
+        }
+        Err(_) => NES_ERROR,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_through_c_api() {
+        let program = [0xA9, 0x42, 0x85, 0x10, 0x00];
+        unsafe {
+            let cpu = nes_cpu_new();
+            assert_eq!(
+                nes_cpu_load_program(cpu, program.as_ptr(), program.len()),
+                NES_OK
+            );
+            assert_eq!(nes_cpu_run_cycles(cpu, 1_000), NES_STATUS_HALTED);
+            assert_eq!(nes_cpu_peek(cpu, 0x10), 0x42);
+
+            let mut registers = NesRegisters::default();
+            nes_cpu_registers(cpu, &mut registers);
+            assert_eq!(registers.a, 0x42);
+
+            assert_eq!(
+                nes_cpu_load_rom(cpu, program.as_ptr(), program.len()),
+                NES_ERROR
+            );
+            nes_cpu_free(cpu);
+        }
+    }
+
+    #[test]
+    fn test_run_cycles_without_a_budget() {
+        let program = [0xE8, 0xE0, 0x05, 0xD0, 0xFB, 0x00];
+        unsafe {
+            let cpu = nes_cpu_new();
+            nes_cpu_load_program(cpu, program.as_ptr(), program.len());
+            nes_cpu_step(cpu);
+            assert_eq!(nes_cpu_run_cycles(cpu, u64::MAX), NES_STATUS_HALTED);
+
+            let mut registers = NesRegisters::default();
+            nes_cpu_registers(cpu, &mut registers);
+            assert_eq!(registers.x, 5);
+            nes_cpu_free(cpu);
+        }
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        unsafe {
+            let cpu = nes_cpu_new();
+            nes_cpu_write(cpu, 0x0200, 0x99);
+            let mut registers = NesRegisters {
+                a: 1,
+                pc: 0x1234,
+                ..NesRegisters::default()
+            };
+            nes_cpu_set_registers(cpu, &registers);
+
+            let mut state = vec![0; nes_state_size()];
+            assert_eq!(
+                nes_cpu_save_state(cpu, state.as_mut_ptr(), state.len()),
+                NES_OK
+            );
+            assert_eq!(nes_cpu_save_state(cpu, state.as_mut_ptr(), 10), NES_ERROR);
+
+            let other = nes_cpu_new();
+            assert_eq!(
+                nes_cpu_load_state(other, state.as_ptr(), state.len()),
+                NES_OK
+            );
+            assert_eq!(nes_cpu_peek(other, 0x0200), 0x99);
+            assert_eq!(nes_cpu_load_state(other, state.as_ptr(), 10), NES_ERROR);
+            nes_cpu_registers(other, &mut registers);
+            assert_eq!((registers.a, registers.pc), (1, 0x1234));
+
+            nes_cpu_free(cpu);
+            nes_cpu_free(other);
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use crate::cpu::{Cpu, Status};
+use crate::hash::fnv1a;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct HeadlessReport {
+    pub steps: usize,
+    pub status: Status,
+    pub ram_hash: u64,
+}
+
+// runs until the program halts or `max_steps` instructions have executed, whichever comes first
+pub fn run_headless(cpu: &mut Cpu, max_steps: usize) -> HeadlessReport {
+    let mut steps = 0;
+    let mut status = Status::Running;
+
+    while status == Status::Running && steps < max_steps {
+        status = cpu.step();
+        steps += 1;
+    }
+
+    HeadlessReport {
+        steps,
+        status,
+        ram_hash: fnv1a(cpu.ram()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_headless_until_halt() {
+        let mut cpu = Cpu::new();
+        cpu.load(vec![0xA9, 0x42, 0x85, 0x10, 0x00]);
+        cpu.reset();
+
+        let report = run_headless(&mut cpu, 100);
+        assert_eq!(report.steps, 3);
+        assert_eq!(report.status, Status::Halted);
+    }
+
+    #[test]
+    fn test_run_headless_step_budget() {
+        let mut cpu = Cpu::new();
+        cpu.load(vec![0xE8, 0xE8, 0xE8, 0xE8, 0x00]);
+        cpu.reset();
+
+        let report = run_headless(&mut cpu, 2);
+        assert_eq!(report.steps, 2);
+        assert_eq!(report.status, Status::Running);
+    }
+
+    #[test]
+    fn test_run_headless_ram_hash() {
+        let run = |value: u8| {
+            let mut cpu = Cpu::new();
+            cpu.load(vec![0xA9, value, 0x85, 0x10, 0x00]);
+            cpu.reset();
+            run_headless(&mut cpu, 100).ram_hash
+        };
+
+        assert_eq!(run(0x42), run(0x42));
+        assert_ne!(run(0x42), run(0x43));
+    }
+}
@@ -0,0 +1,167 @@
+use std::ops::RangeInclusive;
+
+use crate::cpu::Registers;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct HookId(usize);
+
+// returning Some(value) replaces the byte being read or written; hooks are Send so a Cpu can be
+// moved to another thread
+pub type AccessHook = Box<dyn FnMut(u16, u8) -> Option<u8> + Send>;
+pub type ExecuteHook = Box<dyn FnMut(u16, &mut Registers) + Send>;
+
+struct Hook<F> {
+    id: HookId,
+    range: RangeInclusive<u16>,
+    callback: F,
+}
+
+#[derive(Default)]
+pub struct Hooks {
+    next_id: usize,
+    read: Vec<Hook<AccessHook>>,
+    write: Vec<Hook<AccessHook>>,
+    execute: Vec<Hook<ExecuteHook>>,
+}
+
+impl Hooks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn on_read<F>(&mut self, range: RangeInclusive<u16>, callback: F) -> HookId
+    where
+        F: FnMut(u16, u8) -> Option<u8> + Send + 'static,
+    {
+        let id = self.next_id();
+        self.read.push(Hook {
+            id,
+            range,
+            callback: Box::new(callback),
+        });
+        id
+    }
+
+    pub fn on_write<F>(&mut self, range: RangeInclusive<u16>, callback: F) -> HookId
+    where
+        F: FnMut(u16, u8) -> Option<u8> + Send + 'static,
+    {
+        let id = self.next_id();
+        self.write.push(Hook {
+            id,
+            range,
+            callback: Box::new(callback),
+        });
+        id
+    }
+
+    pub fn on_execute<F>(&mut self, range: RangeInclusive<u16>, callback: F) -> HookId
+    where
+        F: FnMut(u16, &mut Registers) + Send + 'static,
+    {
+        let id = self.next_id();
+        self.execute.push(Hook {
+            id,
+            range,
+            callback: Box::new(callback),
+        });
+        id
+    }
+
+    pub fn remove(&mut self, id: HookId) -> bool {
+        let before = self.len();
+        self.read.retain(|hook| hook.id != id);
+        self.write.retain(|hook| hook.id != id);
+        self.execute.retain(|hook| hook.id != id);
+        self.len() != before
+    }
+
+    pub fn clear(&mut self) {
+        self.read.clear();
+        self.write.clear();
+        self.execute.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.read.len() + self.write.len() + self.execute.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub(crate) fn has_read(&self) -> bool {
+        !self.read.is_empty()
+    }
+
+    pub(crate) fn has_execute(&self) -> bool {
+        !self.execute.is_empty()
+    }
+
+    pub(crate) fn read(&mut self, addr: u16, value: u8) -> u8 {
+        Self::access(&mut self.read, addr, value)
+    }
+
+    pub(crate) fn write(&mut self, addr: u16, value: u8) -> u8 {
+        Self::access(&mut self.write, addr, value)
+    }
+
+    pub(crate) fn execute(&mut self, addr: u16, registers: &mut Registers) {
+        for hook in self
+            .execute
+            .iter_mut()
+            .filter(|hook| hook.range.contains(&addr))
+        {
+            (hook.callback)(addr, registers);
+        }
+    }
+
+    fn access(hooks: &mut [Hook<AccessHook>], addr: u16, value: u8) -> u8 {
+        hooks
+            .iter_mut()
+            .filter(|hook| hook.range.contains(&addr))
+            .fold(value, |value, hook| {
+                (hook.callback)(addr, value).unwrap_or(value)
+            })
+    }
+
+    fn next_id(&mut self) -> HookId {
+        let id = HookId(self.next_id);
+        self.next_id += 1;
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hooks_only_fire_in_range() {
+        let mut hooks = Hooks::new();
+        hooks.on_read(0x10..=0x1F, |_, value| Some(value + 1));
+        assert_eq!(hooks.read(0x0F, 0x01), 0x01);
+        assert_eq!(hooks.read(0x10, 0x01), 0x02);
+        assert_eq!(hooks.read(0x1F, 0x01), 0x02);
+        assert_eq!(hooks.write(0x10, 0x01), 0x01);
+    }
+
+    #[test]
+    fn test_hooks_chain_overrides() {
+        let mut hooks = Hooks::new();
+        hooks.on_write(0x10..=0x10, |_, value| Some(value * 2));
+        hooks.on_write(0x10..=0x10, |_, _| None);
+        hooks.on_write(0x10..=0x10, |_, value| Some(value + 1));
+        assert_eq!(hooks.write(0x10, 0x03), 0x07);
+    }
+
+    #[test]
+    fn test_remove_hook() {
+        let mut hooks = Hooks::new();
+        let id = hooks.on_read(0x10..=0x10, |_, _| Some(0xFF));
+        assert!(hooks.remove(id));
+        assert!(!hooks.remove(id));
+        assert!(hooks.is_empty());
+        assert_eq!(hooks.read(0x10, 0x01), 0x01);
+    }
+}
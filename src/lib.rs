@@ -0,0 +1,36 @@
+pub mod assembler;
+pub mod builder;
+pub mod cartridge;
+pub mod cheat;
+pub mod config;
+pub mod cpu;
+pub mod debugger;
+pub mod devices;
+pub mod disassembler;
+pub mod error;
+pub mod events;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod headless;
+pub mod hooks;
+#[cfg(feature = "lua")]
+pub mod lua;
+pub mod pacing;
+pub mod patch;
+pub mod perf;
+pub mod prelude;
+pub mod profiler;
+pub mod rewind;
+pub mod savestate;
+pub mod scale;
+pub mod state_diff;
+pub mod stats;
+pub mod symbols;
+pub mod system_memory;
+pub mod testing;
+pub mod trace;
+
+mod hash;
+
+pub use cpu::Cpu;
+pub use error::{Error, Result};
@@ -0,0 +1,207 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use mlua::{Function, Lua, RegistryKey, Result, Scope};
+
+use crate::cpu::{Cpu, Status};
+
+// mirrors the FCEUX `memory` table: readbyte/writebyte, getregister/setregister and registerexec
+pub struct LuaEngine {
+    lua: Lua,
+    exec_hooks: Rc<RefCell<Vec<(u16, RegistryKey)>>>,
+}
+
+impl Default for LuaEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LuaEngine {
+    pub fn new() -> Self {
+        Self {
+            lua: Lua::new(),
+            exec_hooks: Rc::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    pub fn exec(&self, cpu: &mut Cpu, source: &str) -> Result<()> {
+        let cpu = RefCell::new(cpu);
+        self.lua.scope(|scope| {
+            self.bind(scope, &cpu)?;
+            self.lua.load(source).exec()
+        })
+    }
+
+    // like Cpu::run, but calls any hooks registered with memory.registerexec before each instruction
+    pub fn run(&self, cpu: &mut Cpu) -> Result<Status> {
+        let cpu = RefCell::new(cpu);
+        self.lua.scope(|scope| {
+            self.bind(scope, &cpu)?;
+
+            loop {
+                let pc = cpu.borrow().registers().pc;
+                for hook in self.hooks_at(pc)? {
+                    hook.call::<()>(pc)?;
+                }
+
+                let status = cpu.borrow_mut().step();
+                if status != Status::Running {
+                    return Ok(status);
+                }
+            }
+        })
+    }
+
+    fn hooks_at(&self, pc: u16) -> Result<Vec<Function>> {
+        self.exec_hooks
+            .borrow()
+            .iter()
+            .filter(|(address, _)| *address == pc)
+            .map(|(_, key)| self.lua.registry_value(key))
+            .collect()
+    }
+
+    fn bind<'scope, 'env: 'scope>(
+        &self,
+        scope: &'scope Scope<'scope, 'env>,
+        cpu: &'env RefCell<&mut Cpu>,
+    ) -> Result<()> {
+        let memory = self.lua.create_table()?;
+
+        memory.set(
+            "readbyte",
+            scope.create_function(|_, addr: u16| Ok(cpu.borrow().mem_peek(addr)))?,
+        )?;
+        memory.set(
+            "writebyte",
+            scope.create_function(|_, (addr, value): (u16, u8)| {
+                cpu.borrow_mut().mem_write(addr, value);
+                Ok(())
+            })?,
+        )?;
+        memory.set(
+            "getregister",
+            scope.create_function(|_, name: String| {
+                let registers = cpu.borrow().registers();
+                match name.to_ascii_lowercase().as_str() {
+                    "a" => Ok(registers.a as u16),
+                    "x" => Ok(registers.x as u16),
+                    "y" => Ok(registers.y as u16),
+                    "s" => Ok(registers.sp as u16),
+                    "p" => Ok(registers.status as u16),
+                    "pc" => Ok(registers.pc),
+                    _ => Err(unknown_register(&name)),
+                }
+            })?,
+        )?;
+        memory.set(
+            "setregister",
+            scope.create_function(|_, (name, value): (String, u16)| {
+                let mut cpu = cpu.borrow_mut();
+                let mut registers = cpu.registers();
+                match name.to_ascii_lowercase().as_str() {
+                    "a" => registers.a = value as u8,
+                    "x" => registers.x = value as u8,
+                    "y" => registers.y = value as u8,
+                    "s" => registers.sp = value as u8,
+                    "p" => registers.status = value as u8,
+                    "pc" => registers.pc = value,
+                    _ => return Err(unknown_register(&name)),
+                }
+                cpu.set_registers(registers);
+                Ok(())
+            })?,
+        )?;
+
+        let exec_hooks = Rc::clone(&self.exec_hooks);
+        memory.set(
+            "registerexec",
+            self.lua
+                .create_function(move |lua, (address, hook): (u16, Function)| {
+                    let key = lua.create_registry_value(hook)?;
+                    exec_hooks.borrow_mut().push((address, key));
+                    Ok(())
+                })?,
+        )?;
+
+        self.lua.globals().set("memory", memory)
+    }
+}
+
+fn unknown_register(name: &str) -> mlua::Error {
+    mlua::Error::runtime(format!("unknown register '{}'", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::debugger::{Heatmap, StrictAction, StrictMode, WatchKind};
+
+    fn cpu_with_program(program: Vec<u8>) -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.load(program);
+        cpu.reset();
+        cpu
+    }
+
+    #[test]
+    fn test_memory_access() {
+        let mut cpu = cpu_with_program(vec![0x00]);
+        let lua = LuaEngine::new();
+        lua.exec(
+            &mut cpu,
+            "memory.writebyte(0x10, memory.readbyte(0x8000) + 0x42)",
+        )
+        .unwrap();
+        assert_eq!(cpu.mem_read(0x10), 0x42);
+    }
+
+    #[test]
+    fn test_readbyte_has_no_side_effects() {
+        let mut cpu = cpu_with_program(vec![0xEA, 0x00]);
+        let debugger = cpu.debugger_mut();
+        debugger.add_watchpoint(0x10..=0x10, WatchKind::Read);
+        debugger.set_heatmap(Some(Heatmap::new(1)));
+        debugger.set_strict_mode(Some(StrictMode::new(StrictAction::Break)));
+
+        let lua = LuaEngine::new();
+        lua.exec(
+            &mut cpu,
+            "memory.registerexec(0x8000, function() memory.readbyte(0x10) end)",
+        )
+        .unwrap();
+        assert_eq!(lua.run(&mut cpu).unwrap(), Status::Halted);
+        assert_eq!(cpu.debugger().heatmap().unwrap().get(0x10).reads, 0);
+        assert!(cpu.debugger().strict_mode().unwrap().reads().is_empty());
+    }
+
+    #[test]
+    fn test_register_access() {
+        let mut cpu = cpu_with_program(vec![0xA9, 0x05, 0x00]);
+        let lua = LuaEngine::new();
+        cpu.run();
+        lua.exec(
+            &mut cpu,
+            "memory.setregister('x', memory.getregister('a') * 2)",
+        )
+        .unwrap();
+        assert_eq!(cpu.registers().x, 0x0A);
+
+        assert!(lua.exec(&mut cpu, "memory.getregister('q')").is_err());
+    }
+
+    #[test]
+    fn test_registerexec_hook() {
+        // LDA #$01; STA $10; BRK
+        let mut cpu = cpu_with_program(vec![0xA9, 0x01, 0x85, 0x10, 0x00]);
+        let lua = LuaEngine::new();
+        lua.exec(
+            &mut cpu,
+            "memory.registerexec(0x8002, function() memory.setregister('a', 0x33) end)",
+        )
+        .unwrap();
+        assert_eq!(lua.run(&mut cpu).unwrap(), Status::Halted);
+        assert_eq!(cpu.mem_read(0x10), 0x33);
+    }
+}
@@ -1,7 +1,5 @@
-pub mod cpu;
-
-use cpu::Cpu;
+use nes::cpu::Cpu;
 
 fn main() {
-    let mut cpu = Cpu::new();
+    let _cpu = Cpu::new();
 }
@@ -0,0 +1,160 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+// frames late before the pacer gives up catching up and restarts from now
+const DEFAULT_MAX_LAG_FRAMES: u32 = 5;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Region {
+    #[default]
+    Ntsc,
+    Pal,
+    // the Famiclone timing: PAL's frame rate with a 3:1 PPU to CPU clock ratio
+    Dendy,
+}
+
+impl Region {
+    pub fn frame_rate(&self) -> f64 {
+        match self {
+            Region::Ntsc => 60.0988,
+            Region::Pal | Region::Dendy => 50.0070,
+        }
+    }
+
+    // averaged; the PPU's frames alternate a dot longer or shorter
+    pub fn cycles_per_frame(&self) -> f64 {
+        match self {
+            Region::Ntsc => 29_780.5,
+            Region::Pal => 33_247.5,
+            Region::Dendy => 35_464.0,
+        }
+    }
+}
+
+// schedules frames against a monotonic clock; deadlines are computed from the first frame rather
+// than accumulated, so sleep overshoot and rounding don't drift the rate
+#[derive(Debug, Clone)]
+pub struct FramePacer {
+    frame_duration: f64,
+    max_lag: u32,
+    anchor: Option<Instant>,
+    frames: u64,
+}
+
+impl FramePacer {
+    pub fn new(frame_rate: f64) -> Self {
+        assert!(frame_rate > 0.0, "frame rate must be positive");
+        Self {
+            frame_duration: 1.0 / frame_rate,
+            max_lag: DEFAULT_MAX_LAG_FRAMES,
+            anchor: None,
+            frames: 0,
+        }
+    }
+
+    pub fn for_region(region: Region) -> Self {
+        Self::new(region.frame_rate())
+    }
+
+    pub fn with_max_lag(mut self, frames: u32) -> Self {
+        self.max_lag = frames;
+        self
+    }
+
+    pub fn frame_duration(&self) -> Duration {
+        Duration::from_secs_f64(self.frame_duration)
+    }
+
+    // when frame `frames` is due to start
+    fn deadline(&self, anchor: Instant) -> Instant {
+        anchor + Duration::from_secs_f64(self.frame_duration * self.frames as f64)
+    }
+
+    // call once per frame before running it; returns how long to wait first
+    pub fn next_frame(&mut self, now: Instant) -> Duration {
+        let Some(anchor) = self.anchor else {
+            self.anchor = Some(now);
+            self.frames = 1;
+            return Duration::ZERO;
+        };
+
+        let deadline = self.deadline(anchor);
+        let late = now.saturating_duration_since(deadline);
+        if late > self.frame_duration().mul_f64(self.max_lag as f64) {
+            // e.g. the window was dragged or the host was suspended; don't fast-forward to catch up
+            log::debug!("frame pacer fell {:?} behind, resynchronising", late);
+            self.anchor = Some(now);
+            self.frames = 1;
+            return Duration::ZERO;
+        }
+
+        self.frames += 1;
+        deadline.saturating_duration_since(now)
+    }
+
+    // next_frame against the real clock, sleeping as needed
+    pub fn wait(&mut self) {
+        let wait = self.next_frame(Instant::now());
+        if !wait.is_zero() {
+            std::thread::sleep(wait);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.anchor = None;
+        self.frames = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn test_waits_for_deadline() {
+        let start = Instant::now();
+        let mut pacer = FramePacer::new(50.0);
+
+        assert_eq!(pacer.next_frame(start), Duration::ZERO);
+        // the frame took 5ms, so wait out the remaining 15ms
+        assert_eq!(pacer.next_frame(start + ms(5)), ms(15));
+        // sleeping overshot by 3ms; the next deadline is still at 40ms
+        assert_eq!(pacer.next_frame(start + ms(23)), ms(17));
+    }
+
+    #[test]
+    fn test_late_frames_catch_up_then_resync() {
+        let start = Instant::now();
+        let mut pacer = FramePacer::new(50.0).with_max_lag(2);
+        pacer.next_frame(start);
+
+        // a little behind: run straight away and let later frames make up the difference
+        assert_eq!(pacer.next_frame(start + ms(30)), Duration::ZERO);
+        assert_eq!(pacer.next_frame(start + ms(35)), ms(5));
+
+        // far behind: start over from now instead of rushing through missed frames
+        assert_eq!(pacer.next_frame(start + ms(500)), Duration::ZERO);
+        assert_eq!(pacer.next_frame(start + ms(505)), ms(15));
+    }
+
+    #[test]
+    fn test_no_drift_over_many_frames() {
+        let start = Instant::now();
+        let mut pacer = FramePacer::for_region(Region::Ntsc);
+        let mut now = start;
+        for _ in 0..6010 {
+            now += pacer.next_frame(now);
+        }
+
+        // 6010 frames at 60.0988Hz take just over 100 seconds, to within a microsecond
+        let expected = Duration::from_secs_f64(6009.0 / 60.0988);
+        let elapsed = now - start;
+        assert!(elapsed.abs_diff(expected) < Duration::from_micros(1));
+    }
+}
@@ -0,0 +1,376 @@
+use crate::hash::crc32;
+
+const IPS_MAGIC: &[u8] = b"PATCH";
+const IPS_EOF: &[u8] = b"EOF";
+const BPS_MAGIC: &[u8] = b"BPS1";
+// source, target and patch CRC-32s
+const BPS_FOOTER_SIZE: usize = 12;
+// as far as IPS can address, and far past any NES image
+const MAX_TARGET_SIZE: usize = 0x100_0000;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum PatchError {
+    #[error("not an IPS or BPS patch")]
+    NotAPatch,
+    #[error("patch is truncated")]
+    Truncated,
+    #[error("patch refers to data past the end of the {0}")]
+    OutOfBounds(&'static str),
+    #[error("patch is for a different ROM (CRC-32 {actual:08X}, expected {expected:08X})")]
+    WrongSource { expected: u32, actual: u32 },
+    #[error("{what} checksum mismatch: {actual:08X}, expected {expected:08X}")]
+    Checksum {
+        what: &'static str,
+        expected: u32,
+        actual: u32,
+    },
+}
+
+// picks the format from the patch's magic
+pub fn apply_patch(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    if patch.starts_with(IPS_MAGIC) {
+        apply_ips(rom, patch)
+    } else if patch.starts_with(BPS_MAGIC) {
+        apply_bps(rom, patch)
+    } else {
+        Err(PatchError::NotAPatch)
+    }
+}
+
+// records of (24-bit offset, 16-bit size, data), where a size of 0 means a run of one byte; the
+// ROM grows to fit writes past its end, and an optional 24-bit length after EOF truncates it
+pub fn apply_ips(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let mut reader = Reader::new(patch.strip_prefix(IPS_MAGIC).ok_or(PatchError::NotAPatch)?);
+    let mut out = rom.to_vec();
+
+    loop {
+        if reader.rest().starts_with(IPS_EOF) && matches!(reader.rest().len(), 3 | 6) {
+            reader.take(3)?;
+            break;
+        }
+        let offset = reader.u24_be()?;
+        let size = reader.u16_be()? as usize;
+        let (len, data) = if size == 0 {
+            let len = reader.u16_be()? as usize;
+            (len, Data::Run(reader.byte()?))
+        } else {
+            (size, Data::Bytes(reader.take(size)?))
+        };
+
+        if out.len() < offset + len {
+            out.resize(offset + len, 0);
+        }
+        match data {
+            Data::Run(value) => out[offset..offset + len].fill(value),
+            Data::Bytes(bytes) => out[offset..offset + len].copy_from_slice(bytes),
+        }
+    }
+
+    if !reader.rest().is_empty() {
+        out.truncate(reader.u24_be()?);
+    }
+    Ok(out)
+}
+
+// beat's BPS format; the source, result and patch are all checked against the CRC-32s in the
+// footer
+pub fn apply_bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    if !patch.starts_with(BPS_MAGIC) {
+        return Err(PatchError::NotAPatch);
+    }
+    if patch.len() < BPS_MAGIC.len() + BPS_FOOTER_SIZE {
+        return Err(PatchError::Truncated);
+    }
+
+    let (body, footer) = patch.split_at(patch.len() - BPS_FOOTER_SIZE);
+    let checksum = |i: usize| u32::from_le_bytes(footer[i * 4..i * 4 + 4].try_into().unwrap());
+    let (source_crc, target_crc, patch_crc) = (checksum(0), checksum(1), checksum(2));
+
+    let actual = crc32(&patch[..patch.len() - 4]);
+    if actual != patch_crc {
+        return Err(PatchError::Checksum {
+            what: "patch",
+            expected: patch_crc,
+            actual,
+        });
+    }
+    let actual = crc32(rom);
+    if actual != source_crc {
+        return Err(PatchError::WrongSource {
+            expected: source_crc,
+            actual,
+        });
+    }
+
+    let mut reader = Reader::new(&body[BPS_MAGIC.len()..]);
+    let source_size = reader.varint()?;
+    let target_size = reader.varint()?;
+    let metadata_size = reader.varint()?;
+    reader.take(metadata_size)?;
+    if source_size != rom.len() {
+        return Err(PatchError::OutOfBounds("source"));
+    }
+    // the size comes from the patch, and one TargetCopy can claim any length, so it's capped
+    // rather than trusted
+    if target_size > MAX_TARGET_SIZE {
+        return Err(PatchError::OutOfBounds("target"));
+    }
+
+    let mut out = Vec::new();
+    let mut source_offset = 0usize;
+    let mut target_offset = 0usize;
+    while !reader.rest().is_empty() {
+        let action = reader.varint()?;
+        let len = (action >> 2) + 1;
+        if out.len().saturating_add(len) > target_size {
+            return Err(PatchError::OutOfBounds("target"));
+        }
+
+        match action & 3 {
+            // SourceRead: the source byte at the same position
+            0 => {
+                let start = out.len();
+                let bytes = rom
+                    .get(start..start + len)
+                    .ok_or(PatchError::OutOfBounds("source"))?;
+                out.extend_from_slice(bytes);
+            }
+            // TargetRead: bytes from the patch
+            1 => out.extend_from_slice(reader.take(len)?),
+            // SourceCopy: from anywhere in the source, relative to the last copy
+            2 => {
+                source_offset = reader.relative(source_offset)?;
+                let bytes = rom
+                    .get(source_offset..source_offset + len)
+                    .ok_or(PatchError::OutOfBounds("source"))?;
+                out.extend_from_slice(bytes);
+                source_offset += len;
+            }
+            // TargetCopy: from what's been written so far, byte by byte as the ranges may overlap
+            _ => {
+                target_offset = reader.relative(target_offset)?;
+                for _ in 0..len {
+                    let byte = *out
+                        .get(target_offset)
+                        .ok_or(PatchError::OutOfBounds("target"))?;
+                    out.push(byte);
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+
+    if out.len() != target_size {
+        return Err(PatchError::OutOfBounds("target"));
+    }
+    let actual = crc32(&out);
+    if actual != target_crc {
+        return Err(PatchError::Checksum {
+            what: "result",
+            expected: target_crc,
+            actual,
+        });
+    }
+    Ok(out)
+}
+
+enum Data<'a> {
+    Run(u8),
+    Bytes(&'a [u8]),
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn rest(&self) -> &'a [u8] {
+        self.data
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], PatchError> {
+        if len > self.data.len() {
+            return Err(PatchError::Truncated);
+        }
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<u8, PatchError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16_be(&mut self) -> Result<u16, PatchError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u24_be(&mut self) -> Result<usize, PatchError> {
+        let bytes = self.take(3)?;
+        Ok(u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]) as usize)
+    }
+
+    // BPS numbers: 7 bits at a time, low first, with the top bit marking the last byte and each
+    // continuation adding one so there's only one encoding per value
+    fn varint(&mut self) -> Result<usize, PatchError> {
+        let mut value = 0usize;
+        let mut shift = 1usize;
+        loop {
+            let byte = self.byte()?;
+            value = value
+                .checked_add((byte & 0x7F) as usize * shift)
+                .ok_or(PatchError::Truncated)?;
+            if byte & 0x80 != 0 {
+                return Ok(value);
+            }
+            shift = shift.checked_shl(7).ok_or(PatchError::Truncated)?;
+            value = value.checked_add(shift).ok_or(PatchError::Truncated)?;
+        }
+    }
+
+    // a copy offset: a varint whose low bit is the sign, applied to the previous offset
+    fn relative(&mut self, offset: usize) -> Result<usize, PatchError> {
+        let delta = self.varint()?;
+        let magnitude = delta >> 1;
+        if delta & 1 != 0 {
+            offset.checked_sub(magnitude)
+        } else {
+            offset.checked_add(magnitude)
+        }
+        .ok_or(PatchError::OutOfBounds("source"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ips(records: &[u8], truncate: Option<[u8; 3]>) -> Vec<u8> {
+        let mut patch = IPS_MAGIC.to_vec();
+        patch.extend_from_slice(records);
+        patch.extend_from_slice(IPS_EOF);
+        if let Some(length) = truncate {
+            patch.extend_from_slice(&length);
+        }
+        patch
+    }
+
+    fn varint(mut value: usize, out: &mut Vec<u8>) {
+        loop {
+            let byte = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                out.push(byte | 0x80);
+                return;
+            }
+            out.push(byte);
+            value -= 1;
+        }
+    }
+
+    fn bps(source: &[u8], target: &[u8], actions: &[u8]) -> Vec<u8> {
+        let mut patch = BPS_MAGIC.to_vec();
+        varint(source.len(), &mut patch);
+        varint(target.len(), &mut patch);
+        varint(0, &mut patch);
+        patch.extend_from_slice(actions);
+        patch.extend_from_slice(&crc32(source).to_le_bytes());
+        patch.extend_from_slice(&crc32(target).to_le_bytes());
+        let checksum = crc32(&patch);
+        patch.extend_from_slice(&checksum.to_le_bytes());
+        patch
+    }
+
+    #[test]
+    fn test_ips_records() {
+        let rom = [0u8; 8];
+        // two bytes at 1, a run of four 0xEE at 4, and a byte past the end
+        let patch = ips(
+            &[
+                0, 0, 1, 0, 2, 0xAA, 0xBB, //
+                0, 0, 4, 0, 0, 0, 4, 0xEE, //
+                0, 0, 9, 0, 1, 0xCC,
+            ],
+            None,
+        );
+        assert_eq!(
+            apply_patch(&rom, &patch).unwrap(),
+            [0, 0xAA, 0xBB, 0, 0xEE, 0xEE, 0xEE, 0xEE, 0, 0xCC]
+        );
+
+        let patch = ips(&[0, 0, 0, 0, 1, 0x11], Some([0, 0, 2]));
+        assert_eq!(apply_ips(&rom, &patch).unwrap(), [0x11, 0]);
+
+        assert_eq!(
+            apply_ips(&rom, b"PATCH\x00\x00\x01\x00\x05\xAA"),
+            Err(PatchError::Truncated)
+        );
+    }
+
+    #[test]
+    fn test_bps_actions() {
+        let source = b"hello world";
+        let target = b"hello, hello there";
+        let mut actions = Vec::new();
+        // SourceRead "hello"
+        varint((5 - 1) << 2, &mut actions);
+        // TargetRead ", "
+        varint(((2 - 1) << 2) | 1, &mut actions);
+        actions.extend_from_slice(b", ");
+        // TargetCopy "hello" from offset 0
+        varint(((5 - 1) << 2) | 3, &mut actions);
+        varint(0, &mut actions);
+        // SourceCopy " " from offset 5
+        varint(2, &mut actions);
+        varint(5 << 1, &mut actions);
+        // TargetRead "there"
+        varint(((5 - 1) << 2) | 1, &mut actions);
+        actions.extend_from_slice(b"there");
+
+        let patch = bps(source, target, &actions);
+        assert_eq!(apply_patch(source, &patch).unwrap(), target);
+
+        assert!(matches!(
+            apply_bps(b"hello wOrld", &patch),
+            Err(PatchError::WrongSource { .. })
+        ));
+        let mut corrupt = patch.clone();
+        corrupt[6] ^= 1;
+        assert!(matches!(
+            apply_bps(source, &corrupt),
+            Err(PatchError::Checksum { what: "patch", .. })
+        ));
+        assert_eq!(apply_patch(source, b"nope"), Err(PatchError::NotAPatch));
+    }
+
+    #[test]
+    fn test_bps_oversized_target() {
+        let source = b"hello";
+        let mut patch = BPS_MAGIC.to_vec();
+        varint(source.len(), &mut patch);
+        varint(usize::MAX / 2, &mut patch);
+        varint(0, &mut patch);
+        // a TargetRead of one byte, then a TargetCopy claiming a huge length
+        varint(1, &mut patch);
+        patch.push(b'x');
+        varint(((usize::MAX >> 4) << 2) | 3, &mut patch);
+        varint(0, &mut patch);
+        patch.extend_from_slice(&crc32(source).to_le_bytes());
+        patch.extend_from_slice(&0u32.to_le_bytes());
+        let checksum = crc32(&patch);
+        patch.extend_from_slice(&checksum.to_le_bytes());
+
+        assert_eq!(
+            apply_bps(source, &patch),
+            Err(PatchError::OutOfBounds("target"))
+        );
+
+        let mut reader = Reader::new(&[0x7F; 16]);
+        assert_eq!(reader.varint(), Err(PatchError::Truncated));
+    }
+}
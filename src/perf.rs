@@ -0,0 +1,181 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::cpu::Cpu;
+
+const DEFAULT_WINDOW: usize = 60;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FrameTiming {
+    pub started: Instant,
+    // host time spent emulating the frame, between begin_frame and end_frame
+    pub host_time: Duration,
+    pub cycles: u64,
+}
+
+// averages over the monitor's window of recent frames; there is only a CPU so far, so all of the
+// host time is CPU time
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct PerfStats {
+    pub frames: u64,
+    // frames completed per second of wall time, including whatever the host did between them
+    pub emulated_fps: f64,
+    pub host_frame_time: Duration,
+    pub cycles_per_frame: f64,
+}
+
+// fed by the frontend around each frame it runs, e.g. for a performance HUD
+#[derive(Debug, Clone)]
+pub struct PerfMonitor {
+    window: usize,
+    frames: u64,
+    recent: VecDeque<FrameTiming>,
+    current: Option<(Instant, u64)>,
+}
+
+impl Default for PerfMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PerfMonitor {
+    pub fn new() -> Self {
+        Self::with_window(DEFAULT_WINDOW)
+    }
+
+    pub fn with_window(frames: usize) -> Self {
+        Self {
+            window: frames.max(1),
+            frames: 0,
+            recent: VecDeque::new(),
+            current: None,
+        }
+    }
+
+    pub fn begin_frame(&mut self, cpu: &Cpu) {
+        self.current = Some((Instant::now(), cpu.cycles()));
+    }
+
+    // does nothing without a matching begin_frame; a frame that loaded a state or rewound to an
+    // earlier cycle count is dropped, as its cycles can't be measured
+    pub fn end_frame(&mut self, cpu: &Cpu) {
+        let Some((started, cycles)) = self.current.take() else {
+            return;
+        };
+        let Some(cycles) = cpu.cycles().checked_sub(cycles) else {
+            return;
+        };
+        self.push(FrameTiming {
+            started,
+            host_time: started.elapsed(),
+            cycles,
+        });
+    }
+
+    // oldest first
+    pub fn recent(&self) -> impl Iterator<Item = &FrameTiming> {
+        self.recent.iter()
+    }
+
+    pub fn stats(&self) -> PerfStats {
+        let (Some(first), Some(last)) = (self.recent.front(), self.recent.back()) else {
+            return PerfStats {
+                frames: self.frames,
+                ..PerfStats::default()
+            };
+        };
+
+        let count = self.recent.len() as u32;
+        let wall = (last.started + last.host_time).duration_since(first.started);
+        let host_time: Duration = self.recent.iter().map(|frame| frame.host_time).sum();
+        let cycles: u64 = self.recent.iter().map(|frame| frame.cycles).sum();
+
+        PerfStats {
+            frames: self.frames,
+            emulated_fps: if wall.is_zero() {
+                0.0
+            } else {
+                count as f64 / wall.as_secs_f64()
+            },
+            host_frame_time: host_time / count,
+            cycles_per_frame: cycles as f64 / count as f64,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.frames = 0;
+        self.recent.clear();
+        self.current = None;
+    }
+
+    fn push(&mut self, frame: FrameTiming) {
+        if self.recent.len() == self.window {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(frame);
+        self.frames += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_averages_over_window() {
+        let start = Instant::now();
+        let mut monitor = PerfMonitor::with_window(2);
+        for (i, cycles) in [10_000, 29_000, 31_000].into_iter().enumerate() {
+            monitor.push(FrameTiming {
+                started: start + Duration::from_millis(20 * i as u64),
+                host_time: Duration::from_millis(5 * (i as u64 + 1)),
+                cycles,
+            });
+        }
+
+        let stats = monitor.stats();
+        assert_eq!(stats.frames, 3);
+        assert_eq!(stats.cycles_per_frame, 30_000.0);
+        assert_eq!(stats.host_frame_time, Duration::from_micros(12_500));
+        // two frames between t=20ms and t=55ms
+        assert!((stats.emulated_fps - 2.0 / 0.035).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_frames_from_cpu() {
+        let mut monitor = PerfMonitor::new();
+        let mut cpu = Cpu::new();
+        cpu.load(vec![0xE8, 0xE8, 0x00]);
+        cpu.reset();
+
+        monitor.end_frame(&cpu);
+        assert_eq!(monitor.stats(), PerfStats::default());
+
+        monitor.begin_frame(&cpu);
+        cpu.run();
+        monitor.end_frame(&cpu);
+        assert_eq!(monitor.stats().frames, 1);
+        assert_eq!(monitor.recent().next().unwrap().cycles, 2 + 2 + 7);
+    }
+
+    #[test]
+    fn test_frame_that_goes_back_in_time_is_dropped() {
+        let mut monitor = PerfMonitor::new();
+        let mut cpu = Cpu::new();
+        cpu.load(vec![0xE8, 0xE8, 0x00]);
+        cpu.reset();
+        let state = cpu.save_state();
+        cpu.run();
+
+        monitor.begin_frame(&cpu);
+        cpu.load_state(&state);
+        monitor.end_frame(&cpu);
+        assert_eq!(monitor.stats().frames, 0);
+
+        monitor.begin_frame(&cpu);
+        cpu.run();
+        monitor.end_frame(&cpu);
+        assert_eq!(monitor.stats().frames, 1);
+    }
+}
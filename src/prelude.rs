@@ -0,0 +1,26 @@
+// the types most programs embedding the emulator need: `use nes::prelude::*;`
+pub use crate::builder::{CpuBuilder, Machine};
+pub use crate::cartridge::Cartridge;
+pub use crate::cheat::{Cheat, GameGenieCode};
+pub use crate::config::Config;
+pub use crate::cpu::{Cpu, CpuState, RamInit, Registers, Status};
+pub use crate::debugger::{Condition, Debugger, WatchKind};
+pub use crate::disassembler::Disassembled;
+pub use crate::error::Error;
+pub use crate::symbols::SymbolTable;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prelude_is_enough_to_run_a_program() -> Result<(), Error> {
+        let mut cpu: Cpu = CpuBuilder::new().program(vec![0xA9, 0x01, 0x00]).build()?;
+        cpu.debugger_mut()
+            .add_watchpoint(0x10..=0x10, WatchKind::Write);
+
+        assert_eq!(cpu.run(), Status::Halted);
+        assert_eq!(cpu.registers().a, 0x01);
+        Ok(())
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+
+use crate::cpu::Cpu;
+
+const JSR: u8 = 0x20;
+const RTS: u8 = 0x60;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct FunctionProfile {
+    pub address: u16,
+    pub calls: u64,
+    // cycles spent in the function's own instructions
+    pub self_cycles: u64,
+    // self cycles plus everything it called
+    pub total_cycles: u64,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Pending {
+    pc: u16,
+    opcode: u8,
+    cycles: u64,
+}
+
+// attributes executed cycles to addresses and, following JSR/RTS, to subroutines;
+// the first sampled PC stands in for the top-level function
+#[derive(Debug, Clone, Default)]
+pub struct Profiler {
+    by_address: HashMap<u16, u64>,
+    by_function: HashMap<u16, FunctionProfile>,
+    stack: Vec<u16>,
+    pending: Option<Pending>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // call before each instruction, e.g. from Cpu::run_with_callback, and once more after the
+    // run so the last instruction is counted
+    pub fn sample(&mut self, cpu: &Cpu) {
+        let pc = cpu.registers().pc;
+        let cycles = cpu.cycles();
+
+        if let Some(pending) = self.pending.take() {
+            // saturating in case a state load moved the counter backwards
+            let spent = cycles.saturating_sub(pending.cycles);
+            self.attribute(pending.pc, spent);
+
+            match pending.opcode {
+                JSR => self.enter(pc),
+                RTS if self.stack.len() > 1 => {
+                    self.stack.pop();
+                }
+                _ => {}
+            }
+        }
+
+        if self.stack.is_empty() {
+            self.enter(pc);
+        }
+
+        self.pending = Some(Pending {
+            pc,
+            opcode: cpu.mem_peek(pc),
+            cycles,
+        });
+    }
+
+    pub fn cycles_at(&self, address: u16) -> u64 {
+        self.by_address.get(&address).copied().unwrap_or_default()
+    }
+
+    pub fn function(&self, address: u16) -> Option<&FunctionProfile> {
+        self.by_function.get(&address)
+    }
+
+    pub fn total_cycles(&self) -> u64 {
+        self.by_address.values().sum()
+    }
+
+    // highest cycle count first, ties broken by address
+    pub fn hottest_addresses(&self, n: usize) -> Vec<(u16, u64)> {
+        let mut addresses: Vec<(u16, u64)> = self
+            .by_address
+            .iter()
+            .map(|(&address, &cycles)| (address, cycles))
+            .collect();
+        addresses.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        addresses.truncate(n);
+        addresses
+    }
+
+    // ranked by total (inclusive) cycles
+    pub fn hottest_functions(&self, n: usize) -> Vec<FunctionProfile> {
+        let mut functions: Vec<FunctionProfile> = self.by_function.values().copied().collect();
+        functions.sort_by(|a, b| {
+            b.total_cycles
+                .cmp(&a.total_cycles)
+                .then(a.address.cmp(&b.address))
+        });
+        functions.truncate(n);
+        functions
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    fn enter(&mut self, address: u16) {
+        self.stack.push(address);
+        self.function_mut(address).calls += 1;
+    }
+
+    fn attribute(&mut self, pc: u16, cycles: u64) {
+        *self.by_address.entry(pc).or_default() += cycles;
+
+        let Some(&current) = self.stack.last() else {
+            return;
+        };
+        self.function_mut(current).self_cycles += cycles;
+
+        // recursive calls appear on the stack more than once but only count once
+        for (i, &address) in self.stack.iter().enumerate() {
+            if !self.stack[..i].contains(&address) {
+                self.by_function.get_mut(&address).unwrap().total_cycles += cycles;
+            }
+        }
+    }
+
+    fn function_mut(&mut self, address: u16) -> &mut FunctionProfile {
+        self.by_function
+            .entry(address)
+            .or_insert_with(|| FunctionProfile {
+                address,
+                ..FunctionProfile::default()
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::Registers;
+
+    // moves the CPU to `pc` as if an instruction there took `cycles` to get to
+    fn jump(cpu: &mut Cpu, pc: u16, cycles: u64) {
+        let mut state = cpu.save_state();
+        state.pc = pc;
+        state.cycles += cycles;
+        cpu.load_state(&state);
+    }
+
+    #[test]
+    fn test_cycles_by_address() {
+        let mut profiler = Profiler::new();
+        let mut cpu = Cpu::new();
+        // LDA #$01; INX; INX; BRK
+        cpu.load(vec![0xA9, 0x01, 0xE8, 0xE8, 0x00]);
+        cpu.reset();
+        cpu.run_with_callback(|cpu| profiler.sample(cpu));
+        profiler.sample(&cpu);
+
+        assert_eq!(profiler.cycles_at(0x8000), 2);
+        assert_eq!(profiler.cycles_at(0x8002), 2);
+        assert_eq!(profiler.cycles_at(0x8004), 7);
+        assert_eq!(profiler.total_cycles(), 13);
+        assert_eq!(
+            profiler.hottest_addresses(2),
+            vec![(0x8004, 7), (0x8000, 2)]
+        );
+    }
+
+    #[test]
+    fn test_cycles_by_subroutine() {
+        let mut profiler = Profiler::new();
+        let mut cpu = Cpu::new();
+        cpu.mem_write(0x8000, JSR);
+        cpu.mem_write(0x9000, 0xE8);
+        cpu.mem_write(0x9001, RTS);
+        cpu.mem_write(0x8003, 0xE8);
+        cpu.set_registers(Registers {
+            pc: 0x8000,
+            ..cpu.registers()
+        });
+
+        profiler.sample(&cpu);
+        jump(&mut cpu, 0x9000, 6);
+        profiler.sample(&cpu);
+        jump(&mut cpu, 0x9001, 2);
+        profiler.sample(&cpu);
+        jump(&mut cpu, 0x8003, 6);
+        profiler.sample(&cpu);
+        jump(&mut cpu, 0x8004, 2);
+        profiler.sample(&cpu);
+
+        let main = profiler.function(0x8000).unwrap();
+        assert_eq!(
+            (main.calls, main.self_cycles, main.total_cycles),
+            (1, 8, 16)
+        );
+
+        let subroutine = profiler.function(0x9000).unwrap();
+        assert_eq!(
+            (
+                subroutine.calls,
+                subroutine.self_cycles,
+                subroutine.total_cycles
+            ),
+            (1, 8, 8)
+        );
+
+        let hottest: Vec<u16> = profiler
+            .hottest_functions(10)
+            .iter()
+            .map(|function| function.address)
+            .collect();
+        assert_eq!(hottest, vec![0x8000, 0x9000]);
+    }
+}
@@ -0,0 +1,260 @@
+use std::collections::VecDeque;
+
+use crate::cpu::{Cpu, CpuState};
+
+enum Snapshot {
+    Full(CpuState),
+    Delta {
+        a: u8,
+        x: u8,
+        y: u8,
+        status: u8,
+        sp: u8,
+        pc: u16,
+        cycles: u64,
+        changes: Vec<(u16, u8)>,
+    },
+}
+
+impl Snapshot {
+    fn diff(previous: &CpuState, current: &CpuState) -> Self {
+        let changes = previous
+            .memory
+            .iter()
+            .zip(current.memory.iter())
+            .enumerate()
+            .filter(|(_, (old, new))| old != new)
+            .map(|(addr, (_, new))| (addr as u16, *new))
+            .collect();
+
+        Snapshot::Delta {
+            a: current.a,
+            x: current.x,
+            y: current.y,
+            status: current.status,
+            sp: current.sp,
+            pc: current.pc,
+            cycles: current.cycles,
+            changes,
+        }
+    }
+
+    fn apply(&self, state: &mut CpuState) {
+        match self {
+            Snapshot::Full(full) => *state = full.clone(),
+            Snapshot::Delta {
+                a,
+                x,
+                y,
+                status,
+                sp,
+                pc,
+                cycles,
+                changes,
+            } => {
+                state.a = *a;
+                state.x = *x;
+                state.y = *y;
+                state.status = *status;
+                state.sp = *sp;
+                state.pc = *pc;
+                state.cycles = *cycles;
+
+                for &(addr, value) in changes {
+                    state.memory[addr as usize] = value;
+                }
+            }
+        }
+    }
+}
+
+pub struct Rewind {
+    capacity: usize,
+    interval: usize,
+    delta_compression: bool,
+
+    counter: usize,
+    latest: Option<CpuState>,
+    snapshots: VecDeque<Snapshot>,
+}
+
+impl Rewind {
+    // keeps up to `capacity` snapshots, one taken every `interval` calls to `record`
+    pub fn new(capacity: usize, interval: usize) -> Self {
+        assert!(capacity > 0, "rewind capacity must be non-zero");
+        assert!(interval > 0, "rewind interval must be non-zero");
+
+        Self {
+            capacity,
+            interval,
+            delta_compression: false,
+
+            counter: 0,
+            latest: None,
+            snapshots: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn with_delta_compression(mut self, enabled: bool) -> Self {
+        self.delta_compression = enabled;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.counter = 0;
+        self.latest = None;
+        self.snapshots.clear();
+    }
+
+    pub fn record(&mut self, cpu: &Cpu) {
+        let due = self.counter == 0;
+        self.counter = (self.counter + 1) % self.interval;
+        if !due {
+            return;
+        }
+
+        let state = cpu.save_state();
+        let snapshot = match &self.latest {
+            Some(previous) if self.delta_compression => Snapshot::diff(previous, &state),
+            _ => Snapshot::Full(state.clone()),
+        };
+        self.latest = Some(state);
+
+        self.snapshots.push_back(snapshot);
+        if self.snapshots.len() > self.capacity {
+            self.evict_oldest();
+        }
+    }
+
+    // restores the snapshot taken `snapshots` records ago (1 being the most recent) and
+    // discards it along with everything newer; returns false if there was nothing to restore
+    pub fn rewind(&mut self, cpu: &mut Cpu, snapshots: usize) -> bool {
+        if self.snapshots.is_empty() || snapshots == 0 {
+            return false;
+        }
+
+        let index = self.snapshots.len().saturating_sub(snapshots);
+        log::debug!(
+            "rewinding {} of {} snapshots",
+            self.snapshots.len() - index,
+            self.snapshots.len()
+        );
+        let state = self.reconstruct(index);
+        cpu.load_state(&state);
+
+        self.snapshots.truncate(index);
+        self.counter = 0;
+        self.latest = None;
+
+        true
+    }
+
+    fn reconstruct(&self, index: usize) -> CpuState {
+        let mut snapshots = self.snapshots.iter().take(index + 1);
+        let mut state = match snapshots.next() {
+            Some(Snapshot::Full(state)) => state.clone(),
+            _ => unreachable!("oldest rewind snapshot is always a full state"),
+        };
+        for snapshot in snapshots {
+            snapshot.apply(&mut state);
+        }
+        state
+    }
+
+    fn evict_oldest(&mut self) {
+        let Some(Snapshot::Full(mut state)) = self.snapshots.pop_front() else {
+            unreachable!("oldest rewind snapshot is always a full state");
+        };
+
+        if let Some(next) = self.snapshots.front_mut() {
+            if let Snapshot::Delta { .. } = next {
+                next.apply(&mut state);
+                *next = Snapshot::Full(state);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_recorded(rewind: &mut Rewind) -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.load(vec![
+            0xA2, 0x00, 0xE8, 0x86, 0x10, 0xE8, 0x86, 0x10, 0xE8, 0x86, 0x10, 0x00,
+        ]);
+        cpu.reset();
+        cpu.run_with_callback(|cpu| rewind.record(cpu));
+        cpu
+    }
+
+    #[test]
+    fn test_rewind_restores_previous_state() {
+        let mut rewind = Rewind::new(16, 1);
+        let mut cpu = run_recorded(&mut rewind);
+        assert_eq!(cpu.save_state().x, 3);
+
+        assert!(rewind.rewind(&mut cpu, 3));
+        let state = cpu.save_state();
+        assert_eq!(state.x, 2);
+        assert_eq!(state.memory[0x10], 2);
+        assert_eq!(state.pc, 0x8008);
+    }
+
+    #[test]
+    fn test_rewind_discards_newer_snapshots() {
+        let mut rewind = Rewind::new(16, 1);
+        let mut cpu = run_recorded(&mut rewind);
+        assert_eq!(rewind.len(), 8);
+
+        assert!(rewind.rewind(&mut cpu, 2));
+        assert_eq!(rewind.len(), 6);
+        assert!(rewind.rewind(&mut cpu, 6));
+        assert_eq!(cpu.save_state().pc, 0x8000);
+        assert!(rewind.is_empty());
+        assert!(!rewind.rewind(&mut cpu, 1));
+    }
+
+    #[test]
+    fn test_rewind_interval() {
+        let mut rewind = Rewind::new(16, 3);
+        run_recorded(&mut rewind);
+        assert_eq!(rewind.len(), 3);
+    }
+
+    #[test]
+    fn test_rewind_capacity_evicts_oldest() {
+        let mut rewind = Rewind::new(4, 1).with_delta_compression(true);
+        let mut cpu = run_recorded(&mut rewind);
+        assert_eq!(rewind.len(), 4);
+
+        assert!(rewind.rewind(&mut cpu, 10));
+        let state = cpu.save_state();
+        assert_eq!(state.x, 2);
+        assert_eq!(state.memory[0x10], 1);
+        assert_eq!(state.pc, 0x8006);
+    }
+
+    #[test]
+    fn test_rewind_delta_compression_matches_full_snapshots() {
+        let mut full = Rewind::new(16, 1);
+        let mut delta = Rewind::new(16, 1).with_delta_compression(true);
+        let mut full_cpu = run_recorded(&mut full);
+        let mut delta_cpu = run_recorded(&mut delta);
+
+        for snapshots in [1, 2, 3] {
+            full.rewind(&mut full_cpu, snapshots);
+            delta.rewind(&mut delta_cpu, snapshots);
+            assert_eq!(full_cpu.save_state(), delta_cpu.save_state());
+        }
+    }
+}
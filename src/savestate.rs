@@ -0,0 +1,228 @@
+// versioned binary save states:
+//
+//   "NESS"  format version (u16)  component count (u16)
+//   then per component: tag (4 bytes)  version (u16)  length (u32)  data
+//
+// all little-endian. Components a reader doesn't know are skipped, so states from newer versions
+// that only add components still load; older component versions are upgraded on the way in
+
+use crate::cpu::CpuState;
+
+const MAGIC: &[u8; 4] = b"NESS";
+const FORMAT_VERSION: u16 = 1;
+const HEADER_SIZE: usize = 4 + 2 + 2;
+const COMPONENT_HEADER_SIZE: usize = 4 + 2 + 4;
+
+const CPU_TAG: [u8; 4] = *b"CPU ";
+const CPU_VERSION: u16 = 1;
+// A X Y P SP, PC, cycles
+const CPU_SIZE: usize = 5 + 2 + 8;
+
+const MEMORY_TAG: [u8; 4] = *b"MEM ";
+const MEMORY_VERSION: u16 = 1;
+const MEMORY_SIZE: usize = 0x10000;
+
+// the unversioned layout the C API wrote before this format existed: the CPU component's data
+// followed directly by memory
+const LEGACY_SIZE: usize = CPU_SIZE + MEMORY_SIZE;
+
+pub const STATE_SIZE: usize = HEADER_SIZE + 2 * COMPONENT_HEADER_SIZE + CPU_SIZE + MEMORY_SIZE;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum StateError {
+    #[error("not a save state")]
+    NotAState,
+    #[error("save state is truncated")]
+    Truncated,
+    #[error("save state format {0} is newer than this version supports")]
+    UnsupportedFormat(u16),
+    #[error("{tag} component version {version} can't be loaded by this version")]
+    UnsupportedComponent { tag: String, version: u16 },
+    #[error("save state has no {0} component")]
+    MissingComponent(String),
+    #[error("{0} component has the wrong size")]
+    InvalidComponent(String),
+}
+
+fn tag_name(tag: [u8; 4]) -> String {
+    String::from_utf8_lossy(&tag).trim_end().to_string()
+}
+
+pub fn encode(state: &CpuState) -> Vec<u8> {
+    let mut cpu = Vec::with_capacity(CPU_SIZE);
+    cpu.extend_from_slice(&[state.a, state.x, state.y, state.status, state.sp]);
+    cpu.extend_from_slice(&state.pc.to_le_bytes());
+    cpu.extend_from_slice(&state.cycles.to_le_bytes());
+
+    let components: [([u8; 4], u16, &[u8]); 2] = [
+        (CPU_TAG, CPU_VERSION, &cpu),
+        (MEMORY_TAG, MEMORY_VERSION, &state.memory[..]),
+    ];
+
+    let mut out = Vec::with_capacity(STATE_SIZE);
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    out.extend_from_slice(&(components.len() as u16).to_le_bytes());
+    for (tag, version, data) in components {
+        out.extend_from_slice(&tag);
+        out.extend_from_slice(&version.to_le_bytes());
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out.extend_from_slice(data);
+    }
+    out
+}
+
+pub fn decode(data: &[u8]) -> Result<CpuState, StateError> {
+    if !data.starts_with(MAGIC) {
+        return match data.len() {
+            LEGACY_SIZE => {
+                log::debug!("migrating an unversioned save state");
+                build(&data[..CPU_SIZE], &data[CPU_SIZE..])
+            }
+            _ => Err(StateError::NotAState),
+        };
+    }
+    if data.len() < HEADER_SIZE {
+        return Err(StateError::Truncated);
+    }
+
+    let format = u16::from_le_bytes([data[4], data[5]]);
+    if format > FORMAT_VERSION {
+        return Err(StateError::UnsupportedFormat(format));
+    }
+    let count = u16::from_le_bytes([data[6], data[7]]);
+
+    let mut cpu = None;
+    let mut memory = None;
+    let mut rest = &data[HEADER_SIZE..];
+    for _ in 0..count {
+        let header = rest
+            .get(..COMPONENT_HEADER_SIZE)
+            .ok_or(StateError::Truncated)?;
+        let tag: [u8; 4] = header[..4].try_into().unwrap();
+        let version = u16::from_le_bytes([header[4], header[5]]);
+        let len = u32::from_le_bytes(header[6..10].try_into().unwrap()) as usize;
+        let body = rest
+            .get(COMPONENT_HEADER_SIZE..COMPONENT_HEADER_SIZE + len)
+            .ok_or(StateError::Truncated)?;
+        rest = &rest[COMPONENT_HEADER_SIZE + len..];
+
+        match tag {
+            CPU_TAG => cpu = Some(upgrade(tag, version, CPU_VERSION, body)?),
+            MEMORY_TAG => memory = Some(upgrade(tag, version, MEMORY_VERSION, body)?),
+            _ => log::debug!("skipping unknown save state component {:?}", tag_name(tag)),
+        }
+    }
+
+    let cpu = cpu.ok_or_else(|| StateError::MissingComponent(tag_name(CPU_TAG)))?;
+    let memory = memory.ok_or_else(|| StateError::MissingComponent(tag_name(MEMORY_TAG)))?;
+    build(cpu, memory)
+}
+
+// brings a component up to the version this build writes; every version so far is the first,
+// so older data is only ever already current. Later versions add their conversions here
+fn upgrade(tag: [u8; 4], version: u16, current: u16, data: &[u8]) -> Result<&[u8], StateError> {
+    if version == current {
+        Ok(data)
+    } else {
+        Err(StateError::UnsupportedComponent {
+            tag: tag_name(tag),
+            version,
+        })
+    }
+}
+
+fn build(cpu: &[u8], memory: &[u8]) -> Result<CpuState, StateError> {
+    if cpu.len() != CPU_SIZE {
+        return Err(StateError::InvalidComponent(tag_name(CPU_TAG)));
+    }
+    let memory: Box<[u8; MEMORY_SIZE]> = memory
+        .to_vec()
+        .into_boxed_slice()
+        .try_into()
+        .map_err(|_| StateError::InvalidComponent(tag_name(MEMORY_TAG)))?;
+
+    Ok(CpuState {
+        a: cpu[0],
+        x: cpu[1],
+        y: cpu[2],
+        status: cpu[3],
+        sp: cpu[4],
+        pc: u16::from_le_bytes([cpu[5], cpu[6]]),
+        cycles: u64::from_le_bytes(cpu[7..CPU_SIZE].try_into().unwrap()),
+        memory,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::Cpu;
+
+    fn state() -> CpuState {
+        let mut cpu = Cpu::new();
+        cpu.load(vec![0xA9, 0x42, 0x85, 0x10, 0xE8, 0x00]);
+        cpu.reset();
+        cpu.run();
+        cpu.save_state()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let state = state();
+        let encoded = encode(&state);
+        assert_eq!(encoded.len(), STATE_SIZE);
+        assert_eq!(decode(&encoded), Ok(state));
+    }
+
+    #[test]
+    fn test_unknown_components_are_skipped() {
+        let state = state();
+        let mut encoded = encode(&state);
+        encoded[6] = 3;
+        encoded.extend_from_slice(b"PPU ");
+        encoded.extend_from_slice(&7u16.to_le_bytes());
+        encoded.extend_from_slice(&2u32.to_le_bytes());
+        encoded.extend_from_slice(&[0xAA, 0xBB]);
+
+        assert_eq!(decode(&encoded), Ok(state));
+    }
+
+    #[test]
+    fn test_legacy_layout_is_migrated() {
+        let state = state();
+        let encoded = encode(&state);
+        let cpu_data = HEADER_SIZE + COMPONENT_HEADER_SIZE;
+        let mut legacy = encoded[cpu_data..cpu_data + CPU_SIZE].to_vec();
+        legacy.extend_from_slice(&state.memory[..]);
+
+        assert_eq!(decode(&legacy), Ok(state));
+    }
+
+    #[test]
+    fn test_bad_states_are_rejected() {
+        let mut encoded = encode(&state());
+        assert_eq!(decode(&encoded[..20]), Err(StateError::Truncated));
+        assert_eq!(decode(b"hello"), Err(StateError::NotAState));
+
+        encoded[HEADER_SIZE + 4] = 2;
+        assert_eq!(
+            decode(&encoded),
+            Err(StateError::UnsupportedComponent {
+                tag: "CPU".to_string(),
+                version: 2
+            })
+        );
+
+        encoded[4] = 9;
+        assert_eq!(decode(&encoded), Err(StateError::UnsupportedFormat(9)));
+
+        encoded[4] = 1;
+        encoded[6] = 1;
+        encoded[HEADER_SIZE + 4] = 1;
+        assert_eq!(
+            decode(&encoded),
+            Err(StateError::MissingComponent("MEM".to_string()))
+        );
+    }
+}
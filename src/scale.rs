@@ -0,0 +1,152 @@
+// pixel-art upscalers for frontends that blit frames without shaders; pixels are packed RGBA
+// (0xRRGGBBAA), row by row, and only ever compared for equality
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Scaler {
+    Nearest2x,
+    Nearest3x,
+    // EPX/Scale2x: rounds off diagonal staircases without inventing new colours
+    Scale2x,
+    // AdvMAME3x, the 3x version of the same rules
+    Scale3x,
+}
+
+impl Scaler {
+    pub fn factor(&self) -> usize {
+        match self {
+            Scaler::Nearest2x | Scaler::Scale2x => 2,
+            Scaler::Nearest3x | Scaler::Scale3x => 3,
+        }
+    }
+}
+
+// returns a frame `factor` times as wide and as tall
+pub fn scale(pixels: &[u32], width: usize, height: usize, scaler: Scaler) -> Vec<u32> {
+    assert_eq!(pixels.len(), width * height, "frame size doesn't match");
+
+    let factor = scaler.factor();
+    let out_width = width * factor;
+    let mut out = vec![0; pixels.len() * factor * factor];
+
+    for y in 0..height {
+        for x in 0..width {
+            // edges repeat the border pixel
+            let at = |dx: isize, dy: isize| {
+                let x = (x as isize + dx).clamp(0, width as isize - 1) as usize;
+                let y = (y as isize + dy).clamp(0, height as isize - 1) as usize;
+                pixels[y * width + x]
+            };
+            let block = match scaler {
+                Scaler::Nearest2x | Scaler::Nearest3x => [at(0, 0); 9],
+                Scaler::Scale2x => scale2x(at),
+                Scaler::Scale3x => scale3x(at),
+            };
+
+            for by in 0..factor {
+                let row = (y * factor + by) * out_width + x * factor;
+                out[row..row + factor].copy_from_slice(&block[by * factor..(by + 1) * factor]);
+            }
+        }
+    }
+    out
+}
+
+// the 2x2 block for one source pixel, in the first four entries
+fn scale2x(at: impl Fn(isize, isize) -> u32) -> [u32; 9] {
+    let (b, d, e, f, h) = (at(0, -1), at(-1, 0), at(0, 0), at(1, 0), at(0, 1));
+    let mut block = [e; 9];
+    if b != h && d != f {
+        block[0] = if d == b { d } else { e };
+        block[1] = if b == f { f } else { e };
+        block[2] = if d == h { d } else { e };
+        block[3] = if h == f { f } else { e };
+    }
+    block
+}
+
+fn scale3x(at: impl Fn(isize, isize) -> u32) -> [u32; 9] {
+    let (a, b, c) = (at(-1, -1), at(0, -1), at(1, -1));
+    let (d, e, f) = (at(-1, 0), at(0, 0), at(1, 0));
+    let (g, h, i) = (at(-1, 1), at(0, 1), at(1, 1));
+    let mut block = [e; 9];
+    if b != h && d != f {
+        block[0] = if d == b { d } else { e };
+        block[1] = if (d == b && e != c) || (b == f && e != a) {
+            b
+        } else {
+            e
+        };
+        block[2] = if b == f { f } else { e };
+        block[3] = if (d == b && e != g) || (d == h && e != a) {
+            d
+        } else {
+            e
+        };
+        block[5] = if (b == f && e != i) || (h == f && e != c) {
+            f
+        } else {
+            e
+        };
+        block[6] = if d == h { d } else { e };
+        block[7] = if (d == h && e != i) || (h == f && e != g) {
+            h
+        } else {
+            e
+        };
+        block[8] = if h == f { f } else { e };
+    }
+    block
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const X: u32 = 0xFFFFFFFF;
+    const O: u32 = 0x000000FF;
+
+    #[test]
+    fn test_flat_areas_stay_flat() {
+        for scaler in [
+            Scaler::Nearest2x,
+            Scaler::Nearest3x,
+            Scaler::Scale2x,
+            Scaler::Scale3x,
+        ] {
+            let out = scale(&[X; 6], 3, 2, scaler);
+            assert_eq!(out.len(), 6 * scaler.factor() * scaler.factor());
+            assert!(out.iter().all(|&pixel| pixel == X));
+        }
+    }
+
+    #[test]
+    fn test_nearest_repeats_pixels() {
+        assert_eq!(
+            scale(&[X, O], 2, 1, Scaler::Nearest2x),
+            vec![X, X, O, O, X, X, O, O]
+        );
+    }
+
+    // a corner where the staircase gets rounded off
+    const CORNER: [u32; 4] = [O, X, X, X];
+
+    #[test]
+    fn test_scale2x_rounds_corners() {
+        let out = scale(&CORNER, 2, 2, Scaler::Scale2x);
+        let block = |x: usize, y: usize| [0, 1, 4, 5].map(|offset| out[y * 2 * 4 + x * 2 + offset]);
+
+        assert_eq!(block(0, 0), [O, O, O, X]);
+        assert_eq!(block(1, 1), [X; 4]);
+    }
+
+    #[test]
+    fn test_scale3x_rounds_corners() {
+        let out = scale(&CORNER, 2, 2, Scaler::Scale3x);
+        let block: Vec<u32> = (0..3)
+            .flat_map(|y| out[y * 6..y * 6 + 3].to_vec())
+            .collect();
+
+        assert_eq!(block, [O, O, O, O, O, X, O, X, X]);
+        assert!(out[3 * 6 + 3..].iter().all(|&pixel| pixel == X));
+    }
+}
@@ -0,0 +1,175 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use crate::cpu::{Cpu, CpuState};
+use crate::system_memory::REGIONS;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FieldDiff {
+    pub name: &'static str,
+    pub first: u64,
+    pub second: u64,
+}
+
+// a run of consecutive addresses that differ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryDiff {
+    pub range: RangeInclusive<u16>,
+    pub first: Vec<u8>,
+    pub second: Vec<u8>,
+}
+
+impl MemoryDiff {
+    // which part of memory the run starts in, named as in SystemMemory
+    pub fn region(&self) -> &'static str {
+        let start = *self.range.start();
+        REGIONS
+            .iter()
+            .find(|region| {
+                (region.address as usize..region.address as usize + region.len)
+                    .contains(&(start as usize))
+            })
+            .map_or("memory", |region| region.name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct StateDiff {
+    pub registers: Vec<FieldDiff>,
+    pub memory: Vec<MemoryDiff>,
+}
+
+impl StateDiff {
+    pub fn is_empty(&self) -> bool {
+        self.registers.is_empty() && self.memory.is_empty()
+    }
+}
+
+// one line per difference, e.g. for a desync report
+impl fmt::Display for StateDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "states are identical");
+        }
+        for field in &self.registers {
+            writeln!(f, "{}: {:X} vs {:X}", field.name, field.first, field.second)?;
+        }
+        for run in &self.memory {
+            writeln!(
+                f,
+                "{} ${:04X}-${:04X}: {:02X?} vs {:02X?}",
+                run.region(),
+                run.range.start(),
+                run.range.end(),
+                run.first,
+                run.second
+            )?;
+        }
+        Ok(())
+    }
+}
+
+pub fn diff_states(first: &CpuState, second: &CpuState) -> StateDiff {
+    let fields = [
+        ("A", first.a as u64, second.a as u64),
+        ("X", first.x as u64, second.x as u64),
+        ("Y", first.y as u64, second.y as u64),
+        ("P", first.status as u64, second.status as u64),
+        ("SP", first.sp as u64, second.sp as u64),
+        ("PC", first.pc as u64, second.pc as u64),
+        ("cycles", first.cycles, second.cycles),
+    ];
+    let registers = fields
+        .into_iter()
+        .filter(|(_, first, second)| first != second)
+        .map(|(name, first, second)| FieldDiff {
+            name,
+            first,
+            second,
+        })
+        .collect();
+
+    let mut memory: Vec<MemoryDiff> = Vec::new();
+    for (address, (&a, &b)) in first.memory.iter().zip(second.memory.iter()).enumerate() {
+        if a == b {
+            continue;
+        }
+        let address = address as u16;
+        match memory.last_mut() {
+            Some(run) if run.range.end().wrapping_add(1) == address => {
+                run.range = *run.range.start()..=address;
+                run.first.push(a);
+                run.second.push(b);
+            }
+            _ => memory.push(MemoryDiff {
+                range: address..=address,
+                first: vec![a],
+                second: vec![b],
+            }),
+        }
+    }
+
+    StateDiff { registers, memory }
+}
+
+pub fn diff_cpus(first: &Cpu, second: &Cpu) -> StateDiff {
+    diff_states(&first.save_state(), &second.save_state())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identical_states() {
+        let cpu = Cpu::new();
+        let diff = diff_cpus(&cpu, &Cpu::new());
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "states are identical");
+    }
+
+    #[test]
+    fn test_differences_are_grouped() {
+        let mut first = Cpu::new();
+        let mut second = Cpu::new();
+        first.load(vec![0xA2, 0x01, 0x00]);
+        second.load(vec![0xA2, 0x02, 0x00]);
+        for cpu in [&mut first, &mut second] {
+            cpu.reset();
+            cpu.run();
+        }
+        second.mem_write(0x0010, 0xAA);
+        second.mem_write(0x6000, 0xBB);
+
+        let diff = diff_cpus(&first, &second);
+        assert_eq!(
+            diff.registers,
+            vec![FieldDiff {
+                name: "X",
+                first: 1,
+                second: 2
+            }]
+        );
+        let runs: Vec<_> = diff
+            .memory
+            .iter()
+            .map(|run| (run.range.clone(), run.region()))
+            .collect();
+        assert_eq!(
+            runs,
+            vec![
+                (0x0010..=0x0010, "RAM"),
+                (0x6000..=0x6000, "PRG RAM"),
+                (0x8001..=0x8001, "memory"),
+            ]
+        );
+
+        second.mem_write(0x0011, 0xCC);
+        let diff = diff_cpus(&first, &second);
+        assert_eq!(diff.memory[0].range, 0x0010..=0x0011);
+        assert_eq!(diff.memory[0].second, vec![0xAA, 0xCC]);
+        assert!(diff
+            .to_string()
+            .contains("RAM $0010-$0011: [00, 00] vs [AA, CC]"));
+    }
+}
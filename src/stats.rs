@@ -0,0 +1,162 @@
+use std::collections::BTreeMap;
+
+use crate::cpu::instructions::lookup;
+use crate::cpu::{AddressingMode, Cpu};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct OpcodeCount {
+    pub opcode: u8,
+    // None for opcodes the instruction table doesn't know
+    pub mnemonic: Option<&'static str>,
+    pub addressing_mode: Option<AddressingMode>,
+    pub count: u64,
+    pub cycles: u64,
+}
+
+// how often each opcode ran and how many cycles it took; fed like the tracer and profiler
+#[derive(Debug, Clone)]
+pub struct InstructionStats {
+    counts: [u64; 256],
+    cycles: [u64; 256],
+    // instructions by the number of cycles they took
+    histogram: BTreeMap<u64, u64>,
+    pending: Option<(u8, u64)>,
+}
+
+impl Default for InstructionStats {
+    fn default() -> Self {
+        Self {
+            counts: [0; 256],
+            cycles: [0; 256],
+            histogram: BTreeMap::new(),
+            pending: None,
+        }
+    }
+}
+
+impl InstructionStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // call before each instruction, e.g. from Cpu::run_with_callback, and once more after the
+    // run so the last instruction is counted
+    pub fn sample(&mut self, cpu: &Cpu) {
+        let cycles = cpu.cycles();
+
+        if let Some((opcode, start)) = self.pending.take() {
+            let spent = cycles.saturating_sub(start);
+            self.counts[opcode as usize] += 1;
+            self.cycles[opcode as usize] += spent;
+            *self.histogram.entry(spent).or_default() += 1;
+        }
+
+        self.pending = Some((cpu.mem_peek(cpu.registers().pc), cycles));
+    }
+
+    pub fn count(&self, opcode: u8) -> u64 {
+        self.counts[opcode as usize]
+    }
+
+    pub fn cycles(&self, opcode: u8) -> u64 {
+        self.cycles[opcode as usize]
+    }
+
+    pub fn total_instructions(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    pub fn total_cycles(&self) -> u64 {
+        self.cycles.iter().sum()
+    }
+
+    // executed opcodes, most frequent first
+    pub fn by_opcode(&self) -> Vec<OpcodeCount> {
+        let mut opcodes: Vec<OpcodeCount> = (0..=255u8)
+            .filter(|&opcode| self.count(opcode) > 0)
+            .map(|opcode| {
+                let instruction = lookup(opcode);
+                OpcodeCount {
+                    opcode,
+                    mnemonic: instruction.map(|instruction| instruction.mnemonic),
+                    addressing_mode: instruction.map(|instruction| instruction.addressing_mode),
+                    count: self.count(opcode),
+                    cycles: self.cycles(opcode),
+                }
+            })
+            .collect();
+        opcodes.sort_by(|a, b| b.count.cmp(&a.count).then(a.opcode.cmp(&b.opcode)));
+        opcodes
+    }
+
+    // unknown opcodes are grouped under ".byte", like the disassembler shows them
+    pub fn by_mnemonic(&self) -> Vec<(&'static str, u64)> {
+        let mut mnemonics: Vec<(&'static str, u64)> = Vec::new();
+        for opcode in self.by_opcode() {
+            let mnemonic = opcode.mnemonic.unwrap_or(".byte");
+            match mnemonics.iter_mut().find(|(name, _)| *name == mnemonic) {
+                Some((_, count)) => *count += opcode.count,
+                None => mnemonics.push((mnemonic, opcode.count)),
+            }
+        }
+        mnemonics.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        mnemonics
+    }
+
+    pub fn by_addressing_mode(&self) -> Vec<(AddressingMode, u64)> {
+        let mut modes: Vec<(AddressingMode, u64)> = Vec::new();
+        for opcode in self.by_opcode() {
+            let Some(mode) = opcode.addressing_mode else {
+                continue;
+            };
+            match modes.iter_mut().find(|(known, _)| *known == mode) {
+                Some((_, count)) => *count += opcode.count,
+                None => modes.push((mode, opcode.count)),
+            }
+        }
+        modes.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+        modes
+    }
+
+    // cycles taken -> number of instructions that took that long
+    pub fn cycle_histogram(&self) -> &BTreeMap<u64, u64> {
+        &self.histogram
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_instruction_mix() {
+        let mut stats = InstructionStats::new();
+        let mut cpu = Cpu::new();
+        // LDA #$01; INX; INX; LDA $10; BRK
+        cpu.load(vec![0xA9, 0x01, 0xE8, 0xE8, 0xA5, 0x10, 0x00]);
+        cpu.reset();
+        cpu.run_with_callback(|cpu| stats.sample(cpu));
+        stats.sample(&cpu);
+
+        assert_eq!(stats.total_instructions(), 5);
+        assert_eq!(stats.total_cycles(), 2 + 2 + 2 + 3 + 7);
+        assert_eq!(stats.count(0xE8), 2);
+        assert_eq!(stats.cycles(0xE8), 4);
+
+        let top = stats.by_opcode()[0];
+        assert_eq!(
+            (top.opcode, top.mnemonic, top.count),
+            (0xE8, Some("INX"), 2)
+        );
+        assert_eq!(stats.by_mnemonic()[..2], [("INX", 2), ("LDA", 2)]);
+        assert_eq!(stats.by_addressing_mode()[0], (AddressingMode::Implicit, 3));
+        assert_eq!(
+            stats.cycle_histogram().iter().collect::<Vec<_>>(),
+            vec![(&2, &3), (&3, &1), (&7, &1)]
+        );
+    }
+}
@@ -0,0 +1,342 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::cpu::RAM_SIZE;
+
+const PRG_BANK_SIZE: usize = 0x4000;
+// $8000-$FFFF
+const PRG_WINDOW_SIZE: usize = 0x8000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Symbol {
+    // may be empty for comment-only entries
+    pub name: String,
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SymbolTable {
+    symbols: BTreeMap<u16, Symbol>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SymbolError {
+    #[error("could not read symbol file: {0}")]
+    Io(#[source] io::Error),
+    #[error("line {line}: {message}")]
+    Parse { line: usize, message: String },
+    #[error("unknown symbol file extension '{0}'")]
+    UnknownFormat(String),
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // picks the format from the extension: .nl (FCEUX), .mlb (Mesen) or .dbg (ca65/ld65)
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SymbolError> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let source = fs::read_to_string(path).map_err(SymbolError::Io)?;
+
+        let table = match extension.as_str() {
+            "nl" => Self::from_nl(&source),
+            "mlb" => Self::from_mlb(&source),
+            "dbg" => Self::from_ca65_dbg(&source),
+            _ => Err(SymbolError::UnknownFormat(extension)),
+        }?;
+        log::info!("loaded {} symbols from {}", table.len(), path.display());
+        Ok(table)
+    }
+
+    // FCEUX name lists: `$C000#Reset#comment`, optionally `$0200/10#buffer#` for arrays
+    pub fn from_nl(source: &str) -> Result<Self, SymbolError> {
+        let mut table = Self::new();
+
+        for (i, line) in source.lines().enumerate() {
+            let line = line.trim_end();
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut fields = line.splitn(3, '#');
+            let address = fields.next().unwrap_or_default();
+            let name = fields.next().ok_or_else(|| parse_error(i, "missing '#'"))?;
+            let comment = fields.next().unwrap_or_default();
+
+            let address = address
+                .strip_prefix('$')
+                .ok_or_else(|| parse_error(i, "address must start with '$'"))?;
+            let address = address.split('/').next().unwrap_or_default();
+            let address = parse_hex(address).ok_or_else(|| parse_error(i, "invalid address"))?;
+
+            table.insert(address, symbol(name, comment));
+        }
+
+        Ok(table)
+    }
+
+    // Mesen label files: `Type:Address[-End]:Name[:Comment]`. Addresses are offsets into each
+    // memory type; PRG ROM offsets are placed at $8000 + offset as for 32K NROM, see
+    // from_mlb_with_prg_size when the PRG size is known
+    pub fn from_mlb(source: &str) -> Result<Self, SymbolError> {
+        Self::from_mlb_with_prg_size(source, PRG_WINDOW_SIZE)
+    }
+
+    // with the cartridge's PRG size, e.g. from its iNES header: 16K is mapped at $8000 and
+    // mirrored at $C000, 32K fills $8000-$FFFF, and anything bigger needs a mapper to bank it, so
+    // its PRG labels are skipped. Offsets past the end of their memory are skipped too
+    pub fn from_mlb_with_prg_size(source: &str, prg_size: usize) -> Result<Self, SymbolError> {
+        // how much of the PRG is visible from $8000 and how many times it repeats up to $FFFF
+        let (prg_window, prg_copies) = if prg_size <= PRG_BANK_SIZE {
+            (PRG_BANK_SIZE, 2)
+        } else if prg_size <= PRG_WINDOW_SIZE {
+            (PRG_WINDOW_SIZE, 1)
+        } else {
+            (0, 0)
+        };
+
+        let mut table = Self::new();
+        let mut skipped = 0;
+        for (i, line) in source.lines().enumerate() {
+            let line = line.trim_end();
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut fields = line.splitn(4, ':');
+            let kind = fields.next().unwrap_or_default();
+            let offset = fields
+                .next()
+                .ok_or_else(|| parse_error(i, "missing address"))?;
+            let name = fields
+                .next()
+                .ok_or_else(|| parse_error(i, "missing name"))?;
+            let comment = fields.next().unwrap_or_default().replace("\\n", "\n");
+
+            let offset = offset.split('-').next().unwrap_or_default();
+            let offset = u32::from_str_radix(offset.trim(), 16)
+                .map_err(|_| parse_error(i, "invalid address"))? as usize;
+
+            // (base, window size, how many times the memory repeats across the window)
+            let (base, size, copies) = match kind {
+                "P" | "NesPrgRom" => (0x8000, prg_window, prg_copies),
+                "S" | "W" | "NesSaveRam" | "NesWorkRam" => (0x6000, 0x2000, 1),
+                "R" | "NesInternalRam" => (0x0000, RAM_SIZE, 1),
+                "G" | "NesMemory" => (0x0000, 0x10000, 1),
+                // CHR, palette and other PPU-side labels have no CPU address
+                _ => continue,
+            };
+            if offset >= size {
+                skipped += 1;
+                continue;
+            }
+            let symbol = symbol(name, &comment);
+            for copy in 0..copies {
+                table.insert((base + copy * size + offset) as u16, symbol.clone());
+            }
+        }
+        if skipped > 0 {
+            log::debug!("skipped {} Mesen labels outside the CPU's view", skipped);
+        }
+
+        Ok(table)
+    }
+
+    // ld65 --dbgfile output; only `sym` records of type `lab` carry code and data addresses
+    pub fn from_ca65_dbg(source: &str) -> Result<Self, SymbolError> {
+        let mut table = Self::new();
+
+        for (i, line) in source.lines().enumerate() {
+            let Some(record) = line.strip_prefix("sym\t") else {
+                continue;
+            };
+
+            let mut name = None;
+            let mut value = None;
+            let mut is_label = false;
+            for attribute in record.split(',') {
+                match attribute.split_once('=') {
+                    Some(("name", quoted)) => name = Some(quoted.trim_matches('"')),
+                    Some(("val", val)) => value = Some(val),
+                    Some(("type", kind)) => is_label = kind == "lab",
+                    _ => {}
+                }
+            }
+
+            if !is_label {
+                continue;
+            }
+            let name = name.ok_or_else(|| parse_error(i, "symbol without a name"))?;
+            let value = value.ok_or_else(|| parse_error(i, "label without a value"))?;
+            let address = value
+                .strip_prefix("0x")
+                .and_then(parse_hex)
+                .ok_or_else(|| parse_error(i, "invalid value"))?;
+
+            table.insert(address, symbol(name, ""));
+        }
+
+        Ok(table)
+    }
+
+    pub fn insert(&mut self, address: u16, symbol: Symbol) -> Option<Symbol> {
+        self.symbols.insert(address, symbol)
+    }
+
+    pub fn remove(&mut self, address: u16) -> Option<Symbol> {
+        self.symbols.remove(&address)
+    }
+
+    // entries from `other` win over existing ones at the same address
+    pub fn extend(&mut self, other: SymbolTable) {
+        self.symbols.extend(other.symbols);
+    }
+
+    pub fn get(&self, address: u16) -> Option<&Symbol> {
+        self.symbols.get(&address)
+    }
+
+    pub fn label(&self, address: u16) -> Option<&str> {
+        self.get(address)
+            .map(|symbol| symbol.name.as_str())
+            .filter(|name| !name.is_empty())
+    }
+
+    pub fn comment(&self, address: u16) -> Option<&str> {
+        self.get(address)
+            .and_then(|symbol| symbol.comment.as_deref())
+    }
+
+    pub fn address_of(&self, name: &str) -> Option<u16> {
+        self.symbols
+            .iter()
+            .find(|(_, symbol)| symbol.name == name)
+            .map(|(&address, _)| address)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u16, &Symbol)> {
+        self.symbols
+            .iter()
+            .map(|(&address, symbol)| (address, symbol))
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+}
+
+fn symbol(name: &str, comment: &str) -> Symbol {
+    Symbol {
+        name: name.trim().to_string(),
+        comment: Some(comment.trim())
+            .filter(|comment| !comment.is_empty())
+            .map(str::to_string),
+    }
+}
+
+fn parse_hex(digits: &str) -> Option<u16> {
+    u16::from_str_radix(digits.trim(), 16).ok()
+}
+
+fn parse_error(index: usize, message: &str) -> SymbolError {
+    SymbolError::Parse {
+        line: index + 1,
+        message: message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fceux_nl() {
+        let table =
+            SymbolTable::from_nl("$C000#Reset#entry point\n\n$0200/10#OamBuffer#\n$00FE#Temp\n")
+                .unwrap();
+        assert_eq!(table.len(), 3);
+        assert_eq!(table.label(0xC000), Some("Reset"));
+        assert_eq!(table.comment(0xC000), Some("entry point"));
+        assert_eq!(table.label(0x0200), Some("OamBuffer"));
+        assert_eq!(table.comment(0x0200), None);
+        assert_eq!(table.address_of("Temp"), Some(0x00FE));
+
+        assert!(matches!(
+            SymbolTable::from_nl("C000#Reset#"),
+            Err(SymbolError::Parse { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_mesen_mlb() {
+        let table = SymbolTable::from_mlb(
+            "P:0010:Reset:power on\\nand reset\nR:0000-0001:Pointer\nNesWorkRam:0000:SaveSlot\n\
+             G:2000:PpuCtrl:\nP:0020::just a comment: with a colon\nN:0000:Pattern",
+        )
+        .unwrap();
+        assert_eq!(table.label(0x8010), Some("Reset"));
+        assert_eq!(table.comment(0x8010), Some("power on\nand reset"));
+        assert_eq!(table.label(0x0000), Some("Pointer"));
+        assert_eq!(table.label(0x6000), Some("SaveSlot"));
+        assert_eq!(table.label(0x2000), Some("PpuCtrl"));
+        assert_eq!(table.label(0x8020), None);
+        assert_eq!(table.comment(0x8020), Some("just a comment: with a colon"));
+        assert_eq!(table.len(), 5);
+    }
+
+    #[test]
+    fn test_mesen_mlb_prg_sizes() {
+        // 16K NROM is mirrored at $C000, so its labels show up in both halves
+        let table =
+            SymbolTable::from_mlb_with_prg_size("P:0000:Reset\nP:3FFA:Nmi", PRG_BANK_SIZE).unwrap();
+        assert_eq!(table.label(0x8000), Some("Reset"));
+        assert_eq!(table.label(0xC000), Some("Reset"));
+        assert_eq!(table.label(0xFFFA), Some("Nmi"));
+
+        // 32K NROM with every label in the first bank isn't mistaken for 16K
+        let table = SymbolTable::from_mlb("P:0000:Reset\nP:3FFA:Nmi").unwrap();
+        assert_eq!(table.label(0x8000), Some("Reset"));
+        assert_eq!(table.label(0xC000), None);
+        assert_eq!(table.label(0xBFFA), Some("Nmi"));
+
+        // past 32K the banks can't be placed without a mapper, and RAM offsets past its end
+        // don't alias back onto it
+        let table = SymbolTable::from_mlb_with_prg_size(
+            "P:00010:Start\nP:1C000:Fixed\nR:0800:Beyond\nW:2000:Beyond\nR:0010:Temp",
+            8 * PRG_BANK_SIZE,
+        )
+        .unwrap();
+        assert_eq!(table.len(), 1);
+        assert_eq!(table.label(0x0010), Some("Temp"));
+
+        let table = SymbolTable::from_mlb("P:8000:Beyond").unwrap();
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn test_ca65_dbg() {
+        let table = SymbolTable::from_ca65_dbg(
+            "version\tmajor=2,minor=0\n\
+             sym\tid=0,name=\"Reset\",addrsize=absolute,size=1,scope=0,def=5,ref=8,val=0x8000,seg=0,type=lab\n\
+             sym\tid=1,name=\"SPEED\",addrsize=zeropage,scope=0,def=2,val=0x4,type=equ\n\
+             sym\tid=2,name=\"@loop\",addrsize=absolute,scope=0,def=9,val=0x8005,seg=0,type=lab\n",
+        )
+        .unwrap();
+        assert_eq!(table.label(0x8000), Some("Reset"));
+        assert_eq!(table.label(0x8005), Some("@loop"));
+        assert_eq!(table.label(0x0004), None);
+        assert_eq!(table.len(), 2);
+    }
+}
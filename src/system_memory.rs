@@ -0,0 +1,113 @@
+use crate::cpu::Cpu;
+
+// one block of the flat layout; `offset` is where it starts in the view, `address` on the CPU bus
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MemoryRegion {
+    pub name: &'static str,
+    pub offset: usize,
+    pub address: u16,
+    pub len: usize,
+}
+
+// the layout is fixed so offsets stay valid across versions and between games; mapper RAM will be
+// appended after PRG RAM once there are mappers that have any
+pub const REGIONS: [MemoryRegion; 2] = [
+    MemoryRegion {
+        name: "RAM",
+        offset: 0x0000,
+        address: 0x0000,
+        len: 0x0800,
+    },
+    MemoryRegion {
+        name: "PRG RAM",
+        offset: 0x0800,
+        address: 0x6000,
+        len: 0x2000,
+    },
+];
+
+pub const SYSTEM_MEMORY_SIZE: usize = 0x2800;
+
+// read-only view of the memory a game keeps its state in, as used by achievement runtimes and
+// trainers; reads come straight from memory, so there are no hooks, cheats or I/O side effects
+#[derive(Debug, Copy, Clone)]
+pub struct SystemMemory<'a> {
+    memory: &'a [u8],
+}
+
+impl<'a> SystemMemory<'a> {
+    pub fn new(cpu: &'a Cpu) -> Self {
+        Self {
+            memory: cpu.memory(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        SYSTEM_MEMORY_SIZE
+    }
+
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    pub fn region_of(offset: usize) -> Option<&'static MemoryRegion> {
+        REGIONS
+            .iter()
+            .find(|region| (region.offset..region.offset + region.len).contains(&offset))
+    }
+
+    pub fn read(&self, offset: usize) -> Option<u8> {
+        let region = Self::region_of(offset)?;
+        let address = region.address as usize + offset - region.offset;
+        Some(self.memory[address])
+    }
+
+    // fills as much of `buffer` as the view has from `offset` on and returns how many bytes that was
+    pub fn read_into(&self, offset: usize, buffer: &mut [u8]) -> usize {
+        let len = buffer.len().min(SYSTEM_MEMORY_SIZE.saturating_sub(offset));
+        for (i, byte) in buffer[..len].iter_mut().enumerate() {
+            *byte = self.read(offset + i).unwrap_or(0);
+        }
+        len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cheat::Cheat;
+
+    #[test]
+    fn test_layout_is_contiguous() {
+        let mut offset = 0;
+        for region in REGIONS {
+            assert_eq!(region.offset, offset);
+            offset += region.len;
+        }
+        assert_eq!(offset, SYSTEM_MEMORY_SIZE);
+    }
+
+    #[test]
+    fn test_reads_map_to_bus_addresses() {
+        let mut cpu = Cpu::new();
+        cpu.mem_write(0x07FF, 0x11);
+        cpu.mem_write(0x6000, 0x22);
+        cpu.mem_write(0x7FFF, 0x33);
+        cpu.cheats_mut().add(Cheat::Freeze {
+            address: 0x07FF,
+            value: 0x99,
+        });
+
+        let memory = SystemMemory::new(&cpu);
+        assert_eq!(memory.read(0x07FF), Some(0x11));
+        assert_eq!(memory.read(0x0800), Some(0x22));
+        assert_eq!(memory.read(0x27FF), Some(0x33));
+        assert_eq!(memory.read(0x2800), None);
+        assert_eq!(SystemMemory::region_of(0x0900).unwrap().name, "PRG RAM");
+
+        let mut buffer = [0; 4];
+        assert_eq!(memory.read_into(0x07FE, &mut buffer), 4);
+        assert_eq!(buffer, [0x00, 0x11, 0x22, 0x00]);
+        assert_eq!(memory.read_into(0x27FE, &mut buffer), 2);
+    }
+}
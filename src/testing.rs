@@ -0,0 +1,7 @@
+// the harnesses the core is checked against: golden logs, test ROMs, per-opcode test suites and
+// determinism runs. Public so front ends can run them against their own builds, but kept out of
+// the way of the emulator's API
+pub mod blargg;
+pub mod determinism;
+pub mod nestest;
+pub mod processor_tests;
@@ -0,0 +1,186 @@
+use crate::cartridge::{load_nrom, RomError};
+use crate::cpu::{Cpu, Status};
+
+// blargg's test ROMs report through PRG RAM: a status byte, a signature and a C string
+pub const STATUS_ADDRESS: u16 = 0x6000;
+const SIGNATURE_ADDRESS: u16 = 0x6001;
+const SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+const TEXT_ADDRESS: u16 = 0x6004;
+const MAX_TEXT_LEN: usize = 0x1000;
+
+const STATUS_RUNNING: u8 = 0x80;
+const STATUS_NEEDS_RESET: u8 = 0x81;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum BlarggError {
+    #[error(transparent)]
+    Rom(#[from] RomError),
+    // the ROM reported a non-zero result code
+    #[error("failed with code {code}: {}", text.trim_end())]
+    Failed { code: u8, text: String },
+    // the CPU stopped before the ROM reported a result
+    #[error("stopped ({status:?}) before finishing: {}", text.trim_end())]
+    Stopped { status: Status, text: String },
+    #[error("didn't finish in time: {}", text.trim_end())]
+    Timeout { text: String },
+}
+
+// loads `rom`, runs it from the reset vector for at most `max_instructions` and returns the
+// result text if the ROM reports success
+pub fn run_test_rom(
+    cpu: &mut Cpu,
+    rom: &[u8],
+    max_instructions: u64,
+) -> Result<String, BlarggError> {
+    load_nrom(cpu, rom)?;
+    // the ROMs test BRK itself, so it has to go through the vector
+    cpu.set_halt_on_brk(false);
+    cpu.reset();
+
+    let mut reset_requested = false;
+    for _ in 0..max_instructions {
+        let status = cpu.step();
+        if status != Status::Running {
+            return Err(BlarggError::Stopped {
+                status,
+                text: result_text(cpu),
+            });
+        }
+
+        if !has_signature(cpu) {
+            continue;
+        }
+        match cpu.mem_peek(STATUS_ADDRESS) {
+            STATUS_RUNNING => reset_requested = false,
+            // the ROM wants the reset button pressed, e.g. to check what survives a reset
+            STATUS_NEEDS_RESET if !reset_requested => {
+                reset_requested = true;
+                cpu.reset();
+            }
+            STATUS_NEEDS_RESET => {}
+            0 => return Ok(result_text(cpu)),
+            code => {
+                return Err(BlarggError::Failed {
+                    code,
+                    text: result_text(cpu),
+                })
+            }
+        }
+    }
+
+    Err(BlarggError::Timeout {
+        text: result_text(cpu),
+    })
+}
+
+fn has_signature(cpu: &Cpu) -> bool {
+    (0..SIGNATURE.len()).all(|i| cpu.mem_peek(SIGNATURE_ADDRESS + i as u16) == SIGNATURE[i])
+}
+
+// whatever the ROM has printed so far
+pub fn result_text(cpu: &Cpu) -> String {
+    (0..MAX_TEXT_LEN as u16)
+        .map(|offset| cpu.mem_peek(TEXT_ADDRESS + offset))
+        .take_while(|&byte| byte != 0)
+        .map(char::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRG_BANK_SIZE: usize = 0x4000;
+
+    // an NROM image whose reset vector points at `program` at $8000
+    fn rom(program: &[u8]) -> Vec<u8> {
+        let mut rom = vec![0; 16 + PRG_BANK_SIZE];
+        rom[..4].copy_from_slice(b"NES\x1A");
+        rom[4] = 1;
+        rom[16..16 + program.len()].copy_from_slice(program);
+        rom[16 + 0x3FFC] = 0x00;
+        rom[16 + 0x3FFD] = 0x80;
+        rom
+    }
+
+    // writes "ok", the signature and then `code` to the status byte
+    fn reporting(code: u8) -> Vec<u8> {
+        let mut program = Vec::new();
+        let mut store = |address: u16, value: u8| {
+            let [lo, hi] = address.to_le_bytes();
+            program.extend_from_slice(&[0xA9, value, 0x8D, lo, hi]);
+        };
+        store(TEXT_ADDRESS, b'o');
+        store(TEXT_ADDRESS + 1, b'k');
+        store(STATUS_ADDRESS, STATUS_RUNNING);
+        for (i, &byte) in SIGNATURE.iter().enumerate() {
+            store(SIGNATURE_ADDRESS + i as u16, byte);
+        }
+        store(STATUS_ADDRESS, code);
+        program.push(0x00);
+        program
+    }
+
+    #[test]
+    fn test_passing_rom() {
+        let mut cpu = Cpu::new();
+        assert_eq!(
+            run_test_rom(&mut cpu, &rom(&reporting(0)), 1000),
+            Ok("ok".to_string())
+        );
+    }
+
+    #[test]
+    fn test_failing_rom() {
+        let mut cpu = Cpu::new();
+        assert_eq!(
+            run_test_rom(&mut cpu, &rom(&reporting(3)), 1000),
+            Err(BlarggError::Failed {
+                code: 3,
+                text: "ok".to_string()
+            })
+        );
+
+        let mut cpu = Cpu::new();
+        assert!(matches!(
+            run_test_rom(&mut cpu, &rom(&reporting(0)), 3),
+            Err(BlarggError::Timeout { .. })
+        ));
+
+        let mut cpu = Cpu::new();
+        assert!(matches!(
+            run_test_rom(&mut cpu, &rom(&[0xA9, 0x01, 0x02]), 1000),
+            Err(BlarggError::Stopped {
+                status: Status::UnknownOpcode(0x02),
+                ..
+            })
+        ));
+    }
+
+    // BLARGG_ROMS points at a directory of instr_test-v5 / cpu_timing_test ROMs; only NROM
+    // images load until the cartridge has mappers
+    #[test]
+    #[ignore = "needs blargg's test ROMs"]
+    fn test_blargg_roms() {
+        let dir = std::env::var("BLARGG_ROMS").unwrap();
+        let mut paths: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "nes"))
+            .collect();
+        paths.sort();
+
+        let failures: Vec<String> = paths
+            .iter()
+            .filter_map(|path| {
+                let rom = std::fs::read(path).unwrap();
+                let mut cpu = Cpu::new();
+                run_test_rom(&mut cpu, &rom, 100_000_000)
+                    .err()
+                    .map(|error| format!("{}: {}", path.display(), error))
+            })
+            .collect();
+
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+}
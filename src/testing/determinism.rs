@@ -0,0 +1,163 @@
+use crate::cpu::{Cpu, Status};
+
+// a write the host makes before a given instruction, e.g. a key press landing in an input register
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct InputEvent {
+    pub step: u64,
+    pub address: u16,
+    pub value: u8,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum Nondeterminism {
+    // `step` is the instructions executed when the hashes were taken
+    #[error("runs diverge by step {step}: state hash {first:016X} vs {second:016X}")]
+    Diverged { step: u64, first: u64, second: u64 },
+    // every hash up to here matched, but one run stopped at `step` and the other didn't
+    #[error("one run stopped at step {step} while the other kept going")]
+    StoppedEarly { step: u64 },
+}
+
+// Cpu::state_hash every `interval` instructions as (step, hash), starting before the first one
+// and ending with the state the run stopped in
+pub fn state_hashes(
+    cpu: &mut Cpu,
+    inputs: &[InputEvent],
+    steps: u64,
+    interval: u64,
+) -> Vec<(u64, u64)> {
+    let interval = interval.max(1);
+    let mut hashes = vec![(0, cpu.state_hash())];
+
+    for step in 0..steps {
+        for input in inputs.iter().filter(|input| input.step == step) {
+            cpu.mem_write(input.address, input.value);
+        }
+
+        let status = cpu.step();
+        let done = step + 1;
+        if status != Status::Running {
+            hashes.push((done, cpu.state_hash()));
+            return hashes;
+        }
+        if done % interval == 0 || done == steps {
+            hashes.push((done, cpu.state_hash()));
+        }
+    }
+    hashes
+}
+
+// builds the machine twice with `power_on`, plays the same inputs into both and compares the
+// periodic state hashes; returns how many hashes matched
+pub fn check_determinism<F>(
+    power_on: F,
+    inputs: &[InputEvent],
+    steps: u64,
+    interval: u64,
+) -> Result<usize, Nondeterminism>
+where
+    F: Fn() -> Cpu,
+{
+    let first = state_hashes(&mut power_on(), inputs, steps, interval);
+    let second = state_hashes(&mut power_on(), inputs, steps, interval);
+
+    for (&(step, a), &(other_step, b)) in first.iter().zip(second.iter()) {
+        // a run that stops records its last hash off the interval
+        if step != other_step {
+            return Err(Nondeterminism::StoppedEarly {
+                step: step.min(other_step),
+            });
+        }
+        if a != b {
+            return Err(Nondeterminism::Diverged {
+                step,
+                first: a,
+                second: b,
+            });
+        }
+    }
+    if first.len() != second.len() {
+        let shorter = if first.len() < second.len() {
+            &first
+        } else {
+            &second
+        };
+        let (step, _) = shorter[shorter.len() - 1];
+        return Err(Nondeterminism::StoppedEarly { step });
+    }
+    Ok(first.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    // LDA $FF; STA $10; LDA $FF; STA $11; BRK
+    const PROGRAM: [u8; 9] = [0xA5, 0xFF, 0x85, 0x10, 0xA5, 0xFF, 0x85, 0x11, 0x00];
+
+    fn power_on() -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.load(PROGRAM.to_vec());
+        cpu.reset();
+        cpu
+    }
+
+    #[test]
+    fn test_deterministic_run() {
+        let inputs = [InputEvent {
+            step: 2,
+            address: 0xFF,
+            value: 0x77,
+        }];
+
+        let hashes = state_hashes(&mut power_on(), &inputs, 100, 2);
+        assert_eq!(
+            hashes.iter().map(|&(step, _)| step).collect::<Vec<_>>(),
+            vec![0, 2, 4, 5]
+        );
+        assert_eq!(check_determinism(power_on, &inputs, 100, 2), Ok(4));
+
+        let mut cpu = power_on();
+        state_hashes(&mut cpu, &inputs, 100, 2);
+        assert_eq!(cpu.mem_peek(0x11), 0x77);
+    }
+
+    #[test]
+    fn test_divergence_is_caught() {
+        // the second machine has host state leaking into the input register
+        let runs = Cell::new(0);
+        let leaky = || {
+            runs.set(runs.get() + 1);
+            let mut cpu = power_on();
+            if runs.get() == 2 {
+                cpu.hooks_mut().on_read(0xFF..=0xFF, |_, _| Some(0x01));
+            }
+            cpu
+        };
+
+        let divergence = check_determinism(leaky, &[], 100, 2).unwrap_err();
+        assert!(matches!(
+            divergence,
+            Nondeterminism::Diverged { step: 2, first, second } if first != second
+        ));
+    }
+
+    #[test]
+    fn test_early_stop_is_reported_where_it_happens() {
+        // the second machine pauses at the second LDA, on its third step
+        let runs = Cell::new(0);
+        let pausing = || {
+            runs.set(runs.get() + 1);
+            let mut cpu = power_on();
+            if runs.get() == 2 {
+                cpu.debugger_mut().add_breakpoint(0x8004);
+            }
+            cpu
+        };
+        assert_eq!(
+            check_determinism(pausing, &[], 100, 2),
+            Err(Nondeterminism::StoppedEarly { step: 3 })
+        );
+    }
+}
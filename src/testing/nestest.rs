@@ -0,0 +1,132 @@
+use std::fmt;
+
+use crate::cartridge::{load_nrom, RomError};
+use crate::cpu::{Cpu, Registers};
+use crate::trace::trace;
+
+// nestest's automated mode starts here instead of at the reset vector
+pub const NESTEST_START: u16 = 0xC000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    // 1-based line in the golden log
+    pub line: usize,
+    pub expected: String,
+    pub actual: String,
+    // the last line that still matched, for context
+    pub previous: Option<String>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "trace diverges at line {}", self.line)?;
+        if let Some(previous) = &self.previous {
+            writeln!(f, "  previous: {}", previous)?;
+        }
+        writeln!(f, "  expected: {}", self.expected)?;
+        write!(f, "  actual:   {}", self.actual)
+    }
+}
+
+impl std::error::Error for Divergence {}
+
+// traces the CPU before each instruction and compares against `log` line by line, in
+// nestest.log format; returns how many lines matched
+pub fn compare_with_log(cpu: &mut Cpu, log: &str) -> Result<usize, Divergence> {
+    let mut previous = None;
+
+    for (i, expected) in log.lines().map(str::trim_end).enumerate() {
+        if expected.is_empty() {
+            continue;
+        }
+
+        let actual = trace(cpu);
+        if actual != expected {
+            return Err(Divergence {
+                line: i + 1,
+                expected: expected.to_string(),
+                actual,
+                previous,
+            });
+        }
+
+        cpu.step();
+        previous = Some(actual);
+    }
+
+    Ok(log
+        .lines()
+        .filter(|line| !line.trim_end().is_empty())
+        .count())
+}
+
+// loads nestest.nes into `cpu` in the state nestest.log starts from
+pub fn prepare(cpu: &mut Cpu, rom: &[u8]) -> Result<(), RomError> {
+    load_nrom(cpu, rom)?;
+    cpu.set_halt_on_brk(false);
+
+    let mut state = cpu.save_state();
+    state.cycles = 7;
+    cpu.load_state(&state);
+    cpu.set_registers(Registers {
+        a: 0,
+        x: 0,
+        y: 0,
+        status: 0x24,
+        sp: 0xFD,
+        pc: NESTEST_START,
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn program_cpu() -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.load(vec![0xA9, 0x05, 0xAA, 0xE8, 0x00]);
+        cpu.reset();
+        cpu
+    }
+
+    fn golden_log() -> String {
+        let mut cpu = program_cpu();
+        let mut log = String::new();
+        cpu.run_with_callback(|cpu| {
+            log.push_str(&trace(cpu));
+            log.push_str("\r\n");
+        });
+        log
+    }
+
+    #[test]
+    fn test_matching_log() {
+        assert_eq!(compare_with_log(&mut program_cpu(), &golden_log()), Ok(4));
+    }
+
+    #[test]
+    fn test_first_divergence_is_reported() {
+        let log = golden_log().replace("X:05", "X:06");
+        let divergence = compare_with_log(&mut program_cpu(), &log).unwrap_err();
+
+        assert_eq!(divergence.line, 3);
+        assert!(divergence.expected.contains("X:06"));
+        assert!(divergence.actual.contains("X:05"));
+        assert!(divergence.previous.unwrap().starts_with("8002"));
+    }
+
+    // NESTEST_ROM and NESTEST_LOG point at nestest.nes and the canonical nestest.log
+    #[test]
+    #[ignore = "needs nestest.nes and nestest.log"]
+    fn test_nestest() {
+        let rom = std::fs::read(std::env::var("NESTEST_ROM").unwrap()).unwrap();
+        let log = std::fs::read_to_string(std::env::var("NESTEST_LOG").unwrap()).unwrap();
+
+        let mut cpu = Cpu::new();
+        prepare(&mut cpu, &rom).unwrap();
+        if let Err(divergence) = compare_with_log(&mut cpu, &log) {
+            panic!("{}", divergence);
+        }
+    }
+}
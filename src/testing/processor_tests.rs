@@ -0,0 +1,200 @@
+use serde::Deserialize;
+
+use crate::cpu::{Cpu, Registers};
+
+// one case from the SingleStepTests/ProcessorTests 6502 suite, one JSON file of these per opcode
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TestCase {
+    pub name: String,
+    pub initial: TestState,
+    #[serde(rename = "final")]
+    pub expected: TestState,
+    // (address, value, "read" | "write") for every bus cycle
+    pub cycles: Vec<(u16, u8, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TestState {
+    pub pc: u16,
+    pub s: u8,
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub p: u8,
+    pub ram: Vec<(u16, u8)>,
+}
+
+impl TestState {
+    fn registers(&self) -> Registers {
+        Registers {
+            a: self.a,
+            x: self.x,
+            y: self.y,
+            status: self.p,
+            sp: self.s,
+            pc: self.pc,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum Mismatch {
+    #[error("registers: expected {expected:02X?}, got {actual:02X?}")]
+    Registers {
+        expected: Registers,
+        actual: Registers,
+    },
+    #[error("memory at ${address:04X}: expected {expected:02X}, got {actual:02X}")]
+    Memory {
+        address: u16,
+        expected: u8,
+        actual: u8,
+    },
+    // the core doesn't log individual bus accesses, so only the number of cycles is compared
+    #[error("cycles: expected {expected}, got {actual}")]
+    Cycles { expected: u64, actual: u64 },
+}
+
+// sets up the initial state on a fresh CPU, steps once and compares the final state
+pub fn run_case(case: &TestCase) -> Result<(), Mismatch> {
+    let mut cpu = Cpu::new();
+    cpu.set_halt_on_brk(false);
+    for &(address, value) in &case.initial.ram {
+        cpu.mem_write(address, value);
+    }
+    cpu.set_registers(case.initial.registers());
+
+    let start = cpu.cycles();
+    cpu.step();
+
+    let expected = case.expected.registers();
+    let actual = cpu.registers();
+    if actual != expected {
+        return Err(Mismatch::Registers { expected, actual });
+    }
+
+    for &(address, expected) in &case.expected.ram {
+        let actual = cpu.mem_peek(address);
+        if actual != expected {
+            return Err(Mismatch::Memory {
+                address,
+                expected,
+                actual,
+            });
+        }
+    }
+
+    let expected = case.cycles.len() as u64;
+    let actual = cpu.cycles() - start;
+    if actual != expected {
+        return Err(Mismatch::Cycles { expected, actual });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::instructions::instructions;
+
+    fn case(json: &str) -> TestCase {
+        serde_json::from_str(json).unwrap()
+    }
+
+    const INX: &str = r#"{
+        "name": "e8 00 00",
+        "initial": { "pc": 4096, "s": 253, "a": 0, "x": 255, "y": 0, "p": 36,
+                     "ram": [[4096, 232], [4097, 0]] },
+        "final": { "pc": 4097, "s": 253, "a": 0, "x": 0, "y": 0, "p": 38,
+                   "ram": [[4096, 232], [4097, 0]] },
+        "cycles": [[4096, 232, "read"], [4097, 0, "read"]]
+    }"#;
+
+    #[test]
+    fn test_passing_case() {
+        assert_eq!(run_case(&case(INX)), Ok(()));
+    }
+
+    // pushes PC+2 and P with B set, then takes the $FFFE vector
+    const BRK: &str = r#"{
+        "name": "00 ab 00",
+        "initial": { "pc": 4096, "s": 253, "a": 0, "x": 0, "y": 0, "p": 33,
+                     "ram": [[4096, 0], [4097, 171], [65534, 0], [65535, 32]] },
+        "final": { "pc": 8192, "s": 250, "a": 0, "x": 0, "y": 0, "p": 37,
+                   "ram": [[4096, 0], [4097, 171], [65534, 0], [65535, 32],
+                           [509, 16], [508, 2], [507, 49]] },
+        "cycles": [[4096, 0, "read"], [4097, 171, "read"], [509, 16, "write"],
+                   [508, 2, "write"], [507, 49, "write"], [65534, 0, "read"],
+                   [65535, 32, "read"]]
+    }"#;
+
+    #[test]
+    fn test_brk_case() {
+        assert_eq!(run_case(&case(BRK)), Ok(()));
+    }
+
+    #[test]
+    fn test_mismatches_are_reported() {
+        let mut wrong = case(INX);
+        wrong.expected.x = 1;
+        assert!(matches!(
+            run_case(&wrong),
+            Err(Mismatch::Registers { actual, .. }) if actual.x == 0
+        ));
+
+        let mut wrong = case(INX);
+        wrong.expected.ram.push((0x0200, 0x01));
+        assert_eq!(
+            run_case(&wrong),
+            Err(Mismatch::Memory {
+                address: 0x0200,
+                expected: 0x01,
+                actual: 0x00
+            })
+        );
+
+        let mut wrong = case(INX);
+        wrong.cycles.pop();
+        assert_eq!(
+            run_case(&wrong),
+            Err(Mismatch::Cycles {
+                expected: 1,
+                actual: 2
+            })
+        );
+    }
+
+    // PROCESSOR_TESTS points at the 6502/v1 directory of the suite, with files named like
+    // a9.json; opcodes the core doesn't decode yet are skipped
+    #[test]
+    #[ignore = "needs the ProcessorTests JSON files"]
+    fn test_processor_tests() {
+        let dir = std::env::var("PROCESSOR_TESTS").unwrap();
+        let mut failures = Vec::new();
+
+        for opcode in instructions().map(|instruction| instruction.opcode) {
+            let path = std::path::Path::new(&dir).join(format!("{:02x}.json", opcode));
+            let Ok(json) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            let cases: Vec<TestCase> = serde_json::from_str(&json).unwrap();
+
+            let failed: Vec<_> = cases
+                .iter()
+                .filter_map(|case| run_case(case).err().map(|mismatch| (case, mismatch)))
+                .collect();
+            if let Some((case, mismatch)) = failed.first() {
+                failures.push(format!(
+                    "{:02x}: {} of {} failed, first {:?}: {}",
+                    opcode,
+                    failed.len(),
+                    cases.len(),
+                    case.name,
+                    mismatch
+                ));
+            }
+        }
+
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+}
@@ -0,0 +1,511 @@
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::ops::RangeInclusive;
+
+use crate::cpu::instructions::lookup;
+use crate::cpu::{AddressingMode, Cpu, Registers};
+use crate::disassembler::Disassembled;
+use crate::symbols::SymbolTable;
+
+// there is no PPU yet; an NTSC PPU runs exactly 3 dots per CPU cycle, 341 dots by 262 scanlines
+const PPU_DOTS_PER_CPU_CYCLE: u64 = 3;
+const PPU_DOTS_PER_SCANLINE: u64 = 341;
+const PPU_SCANLINES_PER_FRAME: u64 = 262;
+
+pub struct Tracer<W: Write> {
+    sink: W,
+    symbols: SymbolTable,
+}
+
+impl<W: Write> Tracer<W> {
+    pub fn new(sink: W) -> Self {
+        Self {
+            sink,
+            symbols: SymbolTable::new(),
+        }
+    }
+
+    // labelled addresses get a `Label:` line of their own before the instruction
+    pub fn with_symbols(mut self, symbols: SymbolTable) -> Self {
+        self.symbols = symbols;
+        self
+    }
+
+    // call before each instruction, e.g. from Cpu::run_with_callback
+    pub fn trace(&mut self, cpu: &Cpu) -> io::Result<()> {
+        if let Some(label) = self.symbols.label(cpu.registers().pc) {
+            writeln!(self.sink, "{}:", label)?;
+        }
+        writeln!(self.sink, "{}", trace_with(cpu, &self.symbols))
+    }
+
+    pub fn into_inner(self) -> W {
+        self.sink
+    }
+}
+
+// one nestest.log line for the instruction about to execute at PC
+pub fn trace(cpu: &Cpu) -> String {
+    trace_with(cpu, &SymbolTable::new())
+}
+
+// as trace, with labels in the disassembly and the address's comment after it
+pub fn trace_with(cpu: &Cpu, symbols: &SymbolTable) -> String {
+    let registers = cpu.registers();
+    let disassembled = cpu.disassemble_at(registers.pc);
+
+    format_line(
+        registers,
+        cpu.cycles(),
+        &disassembled.bytes,
+        &annotate(cpu, &disassembled, symbols),
+    )
+}
+
+fn format_line(registers: Registers, cycles: u64, bytes: &[u8], disassembly: &str) -> String {
+    // nestest marks unofficial opcodes with a * in front of the mnemonic
+    let marker = match bytes.first().and_then(|&opcode| lookup(opcode)) {
+        Some(instruction) if !instruction.official => '*',
+        _ => ' ',
+    };
+    let bytes = bytes
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let (scanline, dot) = ppu_position(cycles);
+
+    format!(
+        "{:04X}  {:<8} {}{:<32}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} PPU:{:>3},{:>3} CYC:{}",
+        registers.pc,
+        bytes,
+        marker,
+        disassembly,
+        registers.a,
+        registers.x,
+        registers.y,
+        registers.status,
+        registers.sp,
+        scanline,
+        dot,
+        cycles,
+    )
+}
+
+// which instructions a TraceRing keeps; an empty list of ranges or mnemonics allows everything
+#[derive(Debug, Clone, Default)]
+pub struct TraceFilter {
+    ranges: Vec<RangeInclusive<u16>>,
+    mnemonics: Vec<String>,
+    branches_only: bool,
+}
+
+impl TraceFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn address_range(mut self, range: RangeInclusive<u16>) -> Self {
+        self.ranges.push(range);
+        self
+    }
+
+    pub fn mnemonic(mut self, mnemonic: &str) -> Self {
+        self.mnemonics.push(mnemonic.to_ascii_uppercase());
+        self
+    }
+
+    // conditional branches plus JMP, JSR, RTS, RTI and BRK
+    pub fn branches_only(mut self) -> Self {
+        self.branches_only = true;
+        self
+    }
+
+    pub fn matches(&self, disassembled: &Disassembled) -> bool {
+        let in_range = self.ranges.is_empty()
+            || self
+                .ranges
+                .iter()
+                .any(|range| range.contains(&disassembled.address));
+        let mnemonic = disassembled.mnemonic();
+        let listed = self.mnemonics.is_empty() || self.mnemonics.iter().any(|m| m == mnemonic);
+
+        in_range && listed && (!self.branches_only || is_branch(disassembled))
+    }
+}
+
+fn is_branch(disassembled: &Disassembled) -> bool {
+    disassembled.instruction.is_some_and(|instruction| {
+        instruction.addressing_mode == AddressingMode::Relative
+            || matches!(instruction.mnemonic, "JMP" | "JSR" | "RTS" | "RTI" | "BRK")
+    })
+}
+
+// an instruction as captured by a TraceRing, kept small so long captures stay cheap
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TraceRecord {
+    pub registers: Registers,
+    pub cycles: u64,
+    bytes: [u8; 3],
+    len: u8,
+}
+
+impl TraceRecord {
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes[..self.len as usize]
+    }
+
+    // the nestest.log layout, minus the memory annotations which need the memory of the time
+    pub fn line(&self) -> String {
+        let disassembled = Disassembled::decode(self.registers.pc, self.bytes());
+        format_line(
+            self.registers,
+            self.cycles,
+            self.bytes(),
+            &disassembled.to_string(),
+        )
+    }
+}
+
+// keeps the last `capacity` instructions that pass the filter, for dumping on demand, e.g. once
+// run stops at a breakpoint
+#[derive(Debug, Clone)]
+pub struct TraceRing {
+    capacity: usize,
+    filter: TraceFilter,
+    records: VecDeque<TraceRecord>,
+}
+
+impl TraceRing {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            filter: TraceFilter::new(),
+            records: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn with_filter(mut self, filter: TraceFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    // call before each instruction, e.g. from Cpu::run_with_callback
+    pub fn record(&mut self, cpu: &Cpu) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let registers = cpu.registers();
+        let disassembled = cpu.disassemble_at(registers.pc);
+        if !self.filter.matches(&disassembled) {
+            return;
+        }
+
+        let mut bytes = [0; 3];
+        bytes[..disassembled.len()].copy_from_slice(&disassembled.bytes);
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(TraceRecord {
+            registers,
+            cycles: cpu.cycles(),
+            bytes,
+            len: disassembled.len() as u8,
+        });
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    // oldest first
+    pub fn records(&self) -> impl Iterator<Item = &TraceRecord> {
+        self.records.iter()
+    }
+
+    pub fn dump<W: Write>(&self, mut sink: W) -> io::Result<()> {
+        for record in &self.records {
+            writeln!(sink, "{}", record.line())?;
+        }
+        Ok(())
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+    }
+}
+
+// (scanline, dot) the PPU would be at after `cycles` CPU cycles from power on
+pub(crate) fn ppu_position(cycles: u64) -> (u64, u64) {
+    let dots = cycles * PPU_DOTS_PER_CPU_CYCLE % (PPU_DOTS_PER_SCANLINE * PPU_SCANLINES_PER_FRAME);
+    (dots / PPU_DOTS_PER_SCANLINE, dots % PPU_DOTS_PER_SCANLINE)
+}
+
+// disassembly plus the effective address and value the way nestest prints them
+fn annotate(cpu: &Cpu, disassembled: &Disassembled, symbols: &SymbolTable) -> String {
+    let comment = symbols
+        .comment(disassembled.address)
+        .map(|comment| format!(" ; {}", comment))
+        .unwrap_or_default();
+    let Some(instruction) = disassembled.instruction else {
+        return format!("{}{}", disassembled.format_with(symbols), comment);
+    };
+
+    let registers = cpu.registers();
+    let byte = || disassembled.bytes[1];
+    let word = || u16::from_le_bytes([disassembled.bytes[1], disassembled.bytes[2]]);
+    let peek_u16_zero_page = |addr: u8| {
+        u16::from_le_bytes([
+            cpu.mem_peek(addr as u16),
+            cpu.mem_peek(addr.wrapping_add(1) as u16),
+        ])
+    };
+
+    let annotation = match instruction.addressing_mode {
+        AddressingMode::ZeroPage => format!(" = {:02X}", cpu.mem_peek(byte() as u16)),
+        AddressingMode::ZeroPageX | AddressingMode::ZeroPageY => {
+            let index = if instruction.addressing_mode == AddressingMode::ZeroPageX {
+                registers.x
+            } else {
+                registers.y
+            };
+            let addr = byte().wrapping_add(index);
+            format!(" @ {:02X} = {:02X}", addr, cpu.mem_peek(addr as u16))
+        }
+        AddressingMode::Absolute if matches!(instruction.mnemonic, "JMP" | "JSR") => String::new(),
+        AddressingMode::Absolute => format!(" = {:02X}", cpu.mem_peek(word())),
+        AddressingMode::AbsoluteX | AddressingMode::AbsoluteY => {
+            let index = if instruction.addressing_mode == AddressingMode::AbsoluteX {
+                registers.x
+            } else {
+                registers.y
+            };
+            let addr = word().wrapping_add(index as u16);
+            format!(" @ {:04X} = {:02X}", addr, cpu.mem_peek(addr))
+        }
+        AddressingMode::Indirect => {
+            // the high byte is fetched without carrying into the page, like the real JMP ($xxFF)
+            let pointer = word();
+            let hi_addr = (pointer & 0xFF00) | (pointer.wrapping_add(1) & 0x00FF);
+            let target = u16::from_le_bytes([cpu.mem_peek(pointer), cpu.mem_peek(hi_addr)]);
+            format!(" = {:04X}", target)
+        }
+        AddressingMode::IndirectX => {
+            let pointer = byte().wrapping_add(registers.x);
+            let addr = peek_u16_zero_page(pointer);
+            format!(
+                " @ {:02X} = {:04X} = {:02X}",
+                pointer,
+                addr,
+                cpu.mem_peek(addr)
+            )
+        }
+        AddressingMode::IndirectY => {
+            let base = peek_u16_zero_page(byte());
+            let addr = base.wrapping_add(registers.y as u16);
+            format!(
+                " = {:04X} @ {:04X} = {:02X}",
+                base,
+                addr,
+                cpu.mem_peek(addr)
+            )
+        }
+        AddressingMode::Implicit
+        | AddressingMode::Accumulator
+        | AddressingMode::Immediate
+        | AddressingMode::Relative => String::new(),
+    };
+
+    format!(
+        "{}{}{}",
+        disassembled.format_with(symbols),
+        annotation,
+        comment
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::Registers;
+
+    fn cpu_at(pc: u16, program: &[u8]) -> Cpu {
+        let mut cpu = Cpu::new();
+        for (offset, &byte) in program.iter().enumerate() {
+            cpu.mem_write(pc + offset as u16, byte);
+        }
+        cpu.reset();
+        cpu.set_registers(Registers {
+            status: 0x24,
+            sp: 0xFD,
+            pc,
+            ..Registers::default()
+        });
+        cpu
+    }
+
+    fn disassembly_column(line: &str) -> &str {
+        line[16..48].trim_end()
+    }
+
+    #[test]
+    fn test_nestest_line_layout() {
+        let mut cpu = cpu_at(0xC5F5, &[0xA2, 0x00]);
+        let mut state = cpu.save_state();
+        state.cycles = 10;
+        cpu.load_state(&state);
+
+        assert_eq!(
+            trace(&cpu),
+            "C5F5  A2 00     LDX #$00                        A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 30 CYC:10"
+        );
+    }
+
+    #[test]
+    fn test_memory_annotations() {
+        let mut cpu = cpu_at(0x0600, &[0xA5, 0x10]);
+        cpu.mem_write(0x10, 0x55);
+        assert_eq!(disassembly_column(&trace(&cpu)), "LDA $10 = 55");
+
+        let mut cpu = cpu_at(0x0600, &[0xB5, 0xFF]);
+        cpu.set_registers(Registers {
+            x: 0x02,
+            pc: 0x0600,
+            ..cpu.registers()
+        });
+        cpu.mem_write(0x01, 0xAA);
+        assert_eq!(disassembly_column(&trace(&cpu)), "LDA $FF,X @ 01 = AA");
+
+        let mut cpu = cpu_at(0x0600, &[0xA1, 0x80]);
+        cpu.mem_write(0x80, 0x00);
+        cpu.mem_write(0x81, 0x02);
+        cpu.mem_write(0x0200, 0x5A);
+        assert_eq!(
+            disassembly_column(&trace(&cpu)),
+            "LDA ($80,X) @ 80 = 0200 = 5A"
+        );
+
+        let mut cpu = cpu_at(0x0600, &[0xB1, 0x89]);
+        cpu.set_registers(Registers {
+            y: 0x01,
+            pc: 0x0600,
+            ..cpu.registers()
+        });
+        cpu.mem_write(0x89, 0xFF);
+        cpu.mem_write(0x8A, 0x02);
+        cpu.mem_write(0x0300, 0x89);
+        assert_eq!(
+            disassembly_column(&trace(&cpu)),
+            "LDA ($89),Y = 02FF @ 0300 = 89"
+        );
+    }
+
+    #[test]
+    fn test_symbols_in_trace() {
+        let symbols = SymbolTable::from_nl("$8000#Start#load it\n$0010#Counter#").unwrap();
+        let mut tracer = Tracer::new(Vec::new()).with_symbols(symbols);
+        let mut cpu = Cpu::new();
+        cpu.load(vec![0xA5, 0x10, 0x00]);
+        cpu.reset();
+        cpu.run_with_callback(|cpu| tracer.trace(cpu).unwrap());
+
+        let log = String::from_utf8(tracer.into_inner()).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "Start:");
+        assert_eq!(disassembly_column(lines[1]), "LDA Counter = 00 ; load it");
+    }
+
+    #[test]
+    fn test_ring_keeps_the_last_instructions() {
+        let mut ring = TraceRing::new(2);
+        let mut cpu = Cpu::new();
+        cpu.load(vec![0xA9, 0x01, 0xAA, 0xE8, 0x00]);
+        cpu.reset();
+        cpu.run_with_callback(|cpu| ring.record(cpu));
+
+        let pcs: Vec<u16> = ring.records().map(|record| record.registers.pc).collect();
+        assert_eq!(pcs, vec![0x8003, 0x8004]);
+
+        let mut dump = Vec::new();
+        ring.dump(&mut dump).unwrap();
+        let dump = String::from_utf8(dump).unwrap();
+        assert_eq!(
+            dump.lines().next().unwrap(),
+            "8003  E8        INX                             A:01 X:01 Y:00 P:24 SP:FD PPU:  0, 33 CYC:11"
+        );
+    }
+
+    #[test]
+    fn test_ring_filters() {
+        // LDX #$02; DEX; DEX; INX; BRK
+        let program = vec![0xA2, 0x02, 0xCA, 0xCA, 0xE8, 0x00];
+        let run = |filter: TraceFilter| {
+            let mut ring = TraceRing::new(100).with_filter(filter);
+            let mut cpu = Cpu::new();
+            cpu.load(program.clone());
+            cpu.reset();
+            cpu.run_with_callback(|cpu| ring.record(cpu));
+            ring.records()
+                .map(|record| record.registers.pc)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(run(TraceFilter::new().branches_only()), vec![0x8005]);
+        assert_eq!(
+            run(TraceFilter::new().mnemonic("dex")),
+            vec![0x8002, 0x8003]
+        );
+        assert_eq!(
+            run(TraceFilter::new()
+                .address_range(0x8000..=0x8000)
+                .address_range(0x8004..=0x8005)),
+            vec![0x8000, 0x8004, 0x8005]
+        );
+        assert_eq!(
+            run(TraceFilter::new()
+                .address_range(0x8003..=0x8005)
+                .mnemonic("DEX")),
+            vec![0x8003]
+        );
+    }
+
+    #[test]
+    fn test_tracer_writes_one_line_per_instruction() {
+        let mut tracer = Tracer::new(Vec::new());
+        let mut cpu = Cpu::new();
+        cpu.load(vec![0xA9, 0x01, 0xAA, 0x00]);
+        cpu.reset();
+        cpu.run_with_callback(|cpu| tracer.trace(cpu).unwrap());
+
+        let log = String::from_utf8(tracer.into_inner()).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[1],
+            "8002  AA        TAX                             A:01 X:00 Y:00 P:24 SP:FD PPU:  0, 27 CYC:9"
+        );
+    }
+
+    #[test]
+    fn test_unofficial_opcodes_are_marked() {
+        let mut cpu = Cpu::new();
+        cpu.mem_write(0x10, 0x5A);
+        cpu.load(vec![0x04, 0x10, 0x00]);
+        cpu.reset();
+        assert_eq!(
+            trace(&cpu),
+            "8000  04 10    *NOP $10 = 5A                    A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7"
+        );
+    }
+}